@@ -1,9 +1,12 @@
-use chrono::Utc;
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
 use rusqlite::{params, Connection, OptionalExtension};
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook, Worksheet, XlsxError};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
-use tauri::{Manager, State};
+use std::path::{Path, PathBuf};
+use tauri::{Emitter, Manager, State};
 use thiserror::Error;
 
 type CommandResult<T> = Result<T, String>;
@@ -20,21 +23,110 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             get_app_data,
+            update_setting,
+            format_amount,
             create_product,
+            get_product,
+            clone_product,
+            reorder_suggestions,
+            stockout_forecast,
+            purchase_order_estimate,
+            list_products,
+            low_stock_products,
+            products_by_supplier,
+            find_similar_products,
+            purge_archived,
+            bulk_archive_products,
+            archive_idle_products,
+            set_product_sku,
             update_product,
             delete_product,
+            bulk_adjust_prices,
+            recompute_quantities,
+            product_computed_qty,
+            oversold_events,
+            resolve_damaged_stock,
             create_customer,
             update_customer,
+            import_customers_csv,
+            import_template,
             delete_customer,
+            merge_customers,
+            set_customer_hold,
+            create_supplier,
             record_stock_entry,
+            reverse_stock_entry,
+            undo_stocktake,
+            record_stocktake,
+            record_purchase,
+            record_goods_receipt,
+            record_purchase_return,
+            expiring_lots,
+            customer_detail,
+            sale_ledger,
+            customer_statement,
+            export_customer_statement_csv,
+            export_movements_csv,
+            export_journal,
+            customer_insights,
+            movements_for_product,
+            product_state_at,
+            tag_transactions,
+            convert_stock,
+            set_transaction_ts,
+            largest_transactions,
+            sales_for_product,
+            top_products,
+            search_sales,
             record_sale,
+            record_invoice,
+            fetch_invoice,
+            fetch_returns,
+            generate_receipt,
             record_return,
             record_credit_payment,
+            settle_customer,
+            adjust_credit,
+            grant_discount,
+            delete_credit_payment,
+            customer_balances,
+            fetch_credits_page,
+            recent_activity,
+            settle_sale,
+            transfer_balance,
+            returnable_sales,
+            daily_sales_breakdown,
+            reconcile_drawer,
+            cogs_report,
+            turnover_report,
+            tax_summary_by_rate,
+            pricing_suggestion,
+            capture_daily_snapshot,
+            fetch_snapshot,
+            profit_and_loss,
+            profit_by_customer,
+            basket_metrics,
+            record_cash_entry,
+            fetch_cash_entries,
+            debtors,
+            customer_aging,
             update_sale,
+            correct_sale_price,
             delete_sale,
+            assign_sale_customer,
+            void_invoice,
             update_return,
             delete_return,
-            save_csv
+            save_csv,
+            checkpoint_wal,
+            recalc_balances,
+            list_backups,
+            configure_backups,
+            reset_all_data,
+            database_health,
+            find_orphans,
+            app_info,
+            export_xlsx
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -50,6 +142,10 @@ enum AppError {
     Config(String),
     #[error("validation error: {0}")]
     Validation(String),
+    #[error("validation error:\n{}", format_field_errors(.0))]
+    Fields(Vec<FieldError>),
+    #[error("xlsx error: {0}")]
+    Xlsx(#[from] XlsxError),
 }
 
 impl From<AppError> for String {
@@ -60,6 +156,20 @@ impl From<AppError> for String {
 
 struct DbState {
     path: PathBuf,
+    // why the startup auto backup failed, reported through app_info
+    auto_backup_error: Option<String>,
+}
+
+// every command opens its own connection, so a writer waits this long for another to finish
+// instead of failing with SQLITE_BUSY
+const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(5000);
+
+// stored in PRAGMA user_version; bump it whenever run_migrations changes the schema.
+// one step per schema change since tracking started, so an older build can tell it is behind
+const SCHEMA_VERSION: i64 = 19;
+
+fn read_schema_version(conn: &Connection) -> Result<i64, AppError> {
+    Ok(conn.query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0))?)
 }
 
 impl DbState {
@@ -72,14 +182,35 @@ impl DbState {
         let db_path = data_dir.join("inventory-ledger.db");
 
         let mut conn = Connection::open(&db_path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
         conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")?;
         Self::run_migrations(&mut conn)?;
 
-        Ok(Self { path: db_path })
+        // a failed backup should not keep the shop from opening, so it is only reported
+        let mut auto_backup_error = None;
+        if read_setting_bool(&conn, "auto_backup", false)? {
+            let retention = read_backup_retention(&conn)?;
+            if let Err(err) = write_backup(&conn, &data_dir.join(BACKUP_DIR), retention) {
+                auto_backup_error = Some(err.to_string());
+            }
+        }
+
+        Ok(Self {
+            path: db_path,
+            auto_backup_error,
+        })
+    }
+
+    fn backup_dir(&self) -> PathBuf {
+        self.path
+            .parent()
+            .map(|dir| dir.join(BACKUP_DIR))
+            .unwrap_or_else(|| PathBuf::from(BACKUP_DIR))
     }
 
     fn open(&self) -> Result<Connection, AppError> {
         let conn = Connection::open(&self.path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
         conn.execute("PRAGMA foreign_keys = ON;", [])?;
         Ok(conn)
     }
@@ -199,91 +330,911 @@ impl DbState {
             "CREATE INDEX IF NOT EXISTS idx_sales_origin ON sales(origin_sale_id);",
             [],
         )?;
-        Ok(())
-    }
-}
+        // link compensating stock movements to the row they reverse
+        ensure_column(
+            conn,
+            "transactions",
+            "reverses_id",
+            "ALTER TABLE transactions ADD COLUMN reverses_id INTEGER REFERENCES transactions(id)",
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_transactions_reverses ON transactions(reverses_id);",
+            [],
+        )?;
+        // idempotency key so a retried record_sale never inserts twice
+        ensure_column(
+            conn,
+            "sales",
+            "client_token",
+            "ALTER TABLE sales ADD COLUMN client_token TEXT",
+        )?;
+        conn.execute(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_sales_client_token ON sales(client_token) WHERE client_token IS NOT NULL;",
+            [],
+        )?;
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS price_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                ts TEXT NOT NULL,
+                product_id INTEGER NOT NULL,
+                old_price REAL NOT NULL,
+                new_price REAL NOT NULL,
+                note TEXT,
+                FOREIGN KEY(product_id) REFERENCES products(id) ON DELETE CASCADE
+            );
 
-fn now_iso() -> String {
-    Utc::now().to_rfc3339()
-}
+            CREATE INDEX IF NOT EXISTS idx_price_history_product ON price_history(product_id, ts);
 
-fn map_app_err(err: AppError) -> String {
-    err.into()
-}
+            CREATE TABLE IF NOT EXISTS invoices (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                ts TEXT NOT NULL,
+                customer_id INTEGER,
+                is_credit INTEGER NOT NULL DEFAULT 0,
+                note TEXT,
+                FOREIGN KEY(customer_id) REFERENCES customers(id) ON DELETE SET NULL
+            );
+            ",
+        )?;
+        // group multi-line sales and their single credit row under one invoice
+        ensure_column(
+            conn,
+            "sales",
+            "invoice_id",
+            "ALTER TABLE sales ADD COLUMN invoice_id INTEGER REFERENCES invoices(id)",
+        )?;
+        ensure_column(
+            conn,
+            "credits",
+            "invoice_id",
+            "ALTER TABLE credits ADD COLUMN invoice_id INTEGER REFERENCES invoices(id)",
+        )?;
+        conn.execute_batch(
+            "
+            CREATE INDEX IF NOT EXISTS idx_sales_invoice ON sales(invoice_id);
+            CREATE INDEX IF NOT EXISTS idx_credits_invoice ON credits(invoice_id);
 
-fn map_sql_err(err: rusqlite::Error) -> String {
-    AppError::from(err).into()
-}
+            CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+            ",
+        )?;
+        ensure_column(
+            conn,
+            "products",
+            "cost_price",
+            "ALTER TABLE products ADD COLUMN cost_price REAL",
+        )?;
+        // keep who bought on the sale itself so it survives customer deletion
+        ensure_column(
+            conn,
+            "sales",
+            "customer_name_snapshot",
+            "ALTER TABLE sales ADD COLUMN customer_name_snapshot TEXT",
+        )?;
+        ensure_column(
+            conn,
+            "sales",
+            "customer_phone_snapshot",
+            "ALTER TABLE sales ADD COLUMN customer_phone_snapshot TEXT",
+        )?;
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS suppliers (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                phone TEXT,
+                note TEXT,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
-enum TransactionKind {
-    In,
-    Out,
-    Return,
-}
+            CREATE INDEX IF NOT EXISTS idx_suppliers_name ON suppliers(name);
+            ",
+        )?;
+        ensure_column(
+            conn,
+            "transactions",
+            "supplier_id",
+            "ALTER TABLE transactions ADD COLUMN supplier_id INTEGER REFERENCES suppliers(id) ON DELETE SET NULL",
+        )?;
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS product_lots (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+                lot_code TEXT,
+                qty REAL NOT NULL,
+                received_qty REAL NOT NULL,
+                expiry_date TEXT,
+                received_at TEXT NOT NULL,
+                transaction_id INTEGER REFERENCES transactions(id) ON DELETE SET NULL
+            );
 
-impl TransactionKind {
-    fn as_str(&self) -> &'static str {
-        match self {
-            TransactionKind::In => "IN",
-            TransactionKind::Out => "OUT",
-            TransactionKind::Return => "RETURN",
+            CREATE INDEX IF NOT EXISTS idx_product_lots_product ON product_lots(product_id, expiry_date);
+            ",
+        )?;
+        ensure_column(
+            conn,
+            "products",
+            "version",
+            "ALTER TABLE products ADD COLUMN version INTEGER NOT NULL DEFAULT 1",
+        )?;
+        ensure_column(
+            conn,
+            "customers",
+            "version",
+            "ALTER TABLE customers ADD COLUMN version INTEGER NOT NULL DEFAULT 1",
+        )?;
+        ensure_column(
+            conn,
+            "products",
+            "allow_negative_stock",
+            "ALTER TABLE products ADD COLUMN allow_negative_stock INTEGER NOT NULL DEFAULT 0",
+        )?;
+        // phone keeps what the user typed; phone_normalized is the digits-only form used for matching
+        ensure_column(
+            conn,
+            "customers",
+            "phone_normalized",
+            "ALTER TABLE customers ADD COLUMN phone_normalized TEXT",
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_customers_phone_normalized ON customers(phone_normalized)",
+            [],
+        )?;
+        let pending_phones = {
+            let mut stmt = conn.prepare(
+                "SELECT id, phone FROM customers WHERE phone IS NOT NULL AND phone_normalized IS NULL",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+        for (id, phone) in pending_phones {
+            conn.execute(
+                "UPDATE customers SET phone_normalized = ? WHERE id = ?",
+                params![phone_digits(&phone), id],
+            )?;
         }
-    }
+        conn.execute(
+            "UPDATE products SET sku = TRIM(sku) WHERE sku IS NOT NULL AND sku <> TRIM(sku)",
+            [],
+        )?;
+        conn.execute("UPDATE products SET sku = NULL WHERE sku = ''", [])?;
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS balance_transfers (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                ts TEXT NOT NULL,
+                from_customer_id INTEGER NOT NULL,
+                to_customer_id INTEGER NOT NULL,
+                amount REAL NOT NULL,
+                note TEXT,
+                FOREIGN KEY(from_customer_id) REFERENCES customers(id) ON DELETE CASCADE,
+                FOREIGN KEY(to_customer_id) REFERENCES customers(id) ON DELETE CASCADE
+            );
+            ",
+        )?;
+        // both ledger rows of a balance transfer point at the transfer
+        ensure_column(
+            conn,
+            "credits",
+            "transfer_id",
+            "ALTER TABLE credits ADD COLUMN transfer_id INTEGER REFERENCES balance_transfers(id) ON DELETE CASCADE",
+        )?;
+        // unit cost at the time of sale; NULL on rows recorded before costs were tracked
+        ensure_column(
+            conn,
+            "sales",
+            "cost_snapshot",
+            "ALTER TABLE sales ADD COLUMN cost_snapshot REAL",
+        )?;
+        // money that is not tied to a product: rent, utilities, miscellaneous income
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS cash_entries (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                ts TEXT NOT NULL,
+                kind TEXT NOT NULL CHECK (kind IN ('EXPENSE', 'INCOME')),
+                amount REAL NOT NULL,
+                category TEXT,
+                note TEXT
+            );
 
-    fn from_db(value: &str) -> Option<Self> {
-        match value {
-            "IN" => Some(TransactionKind::In),
-            "OUT" => Some(TransactionKind::Out),
-            "RETURN" => Some(TransactionKind::Return),
-            _ => None,
+            CREATE INDEX IF NOT EXISTS idx_cash_entries_ts ON cash_entries(ts);
+            ",
+        )?;
+        // pack size purchases are rounded up to; NULL means any quantity
+        ensure_column(
+            conn,
+            "products",
+            "reorder_multiple",
+            "ALTER TABLE products ADD COLUMN reorder_multiple REAL",
+        )?;
+        // smallest quantity a product moves in; NULL keeps quantities free-form (fabric by the metre)
+        ensure_column(
+            conn,
+            "products",
+            "qty_step",
+            "ALTER TABLE products ADD COLUMN qty_step REAL",
+        )?;
+        // defective returns wait in damaged_qty; their RETURN movement is flagged so it adds no sellable stock
+        ensure_column(
+            conn,
+            "products",
+            "damaged_qty",
+            "ALTER TABLE products ADD COLUMN damaged_qty REAL NOT NULL DEFAULT 0",
+        )?;
+        ensure_column(
+            conn,
+            "transactions",
+            "to_damaged",
+            "ALTER TABLE transactions ADD COLUMN to_damaged INTEGER NOT NULL DEFAULT 0",
+        )?;
+        // manual write-offs and fees; they move the balance without any money changing hands
+        ensure_column(
+            conn,
+            "credits",
+            "is_adjustment",
+            "ALTER TABLE credits ADD COLUMN is_adjustment INTEGER NOT NULL DEFAULT 0",
+        )?;
+        // VAT percentage; prices already include it, and each sale keeps the rate it was sold at
+        ensure_column(
+            conn,
+            "products",
+            "tax_rate",
+            "ALTER TABLE products ADD COLUMN tax_rate REAL NOT NULL DEFAULT 0",
+        )?;
+        ensure_column(
+            conn,
+            "sales",
+            "tax_rate",
+            "ALTER TABLE sales ADD COLUMN tax_rate REAL NOT NULL DEFAULT 0",
+        )?;
+        // what cash rounding added to (or took off) qty * price on a cash sale
+        ensure_column(
+            conn,
+            "sales",
+            "rounding_adjustment",
+            "ALTER TABLE sales ADD COLUMN rounding_adjustment REAL NOT NULL DEFAULT 0",
+        )?;
+        // when to buy more, separate from the earlier low-stock warning; NULL falls back to the threshold
+        ensure_column(
+            conn,
+            "products",
+            "reorder_point",
+            "ALTER TABLE products ADD COLUMN reorder_point REAL",
+        )?;
+        // goodwill discounts reduce the balance like a payment but bring in no money
+        ensure_column(
+            conn,
+            "credits",
+            "is_discount",
+            "ALTER TABLE credits ADD COLUMN is_discount INTEGER NOT NULL DEFAULT 0",
+        )?;
+        // running charge and payment totals per customer, kept current by triggers on every credits
+        // write; a freshly added cache is filled once from the ledger
+        let balance_cache_exists = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM pragma_table_info('customers') WHERE name = 'cached_credit')",
+            [],
+            |row| row.get::<_, i64>(0),
+        )? != 0;
+        ensure_column(
+            conn,
+            "customers",
+            "cached_credit",
+            "ALTER TABLE customers ADD COLUMN cached_credit REAL NOT NULL DEFAULT 0",
+        )?;
+        ensure_column(
+            conn,
+            "customers",
+            "cached_paid",
+            "ALTER TABLE customers ADD COLUMN cached_paid REAL NOT NULL DEFAULT 0",
+        )?;
+        let new_charge = credit_charge_sql("NEW");
+        let old_charge = credit_charge_sql("OLD");
+        conn.execute_batch(&format!(
+            "
+            CREATE TRIGGER IF NOT EXISTS trg_credits_balance_insert AFTER INSERT ON credits
+            BEGIN
+                UPDATE customers SET
+                    cached_credit = cached_credit + CASE WHEN {new_charge} THEN NEW.amount ELSE 0 END,
+                    cached_paid = cached_paid + CASE WHEN NEW.is_payment = 1 THEN NEW.amount ELSE 0 END
+                WHERE id = NEW.customer_id;
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS trg_credits_balance_delete AFTER DELETE ON credits
+            BEGIN
+                UPDATE customers SET
+                    cached_credit = cached_credit - CASE WHEN {old_charge} THEN OLD.amount ELSE 0 END,
+                    cached_paid = cached_paid - CASE WHEN OLD.is_payment = 1 THEN OLD.amount ELSE 0 END
+                WHERE id = OLD.customer_id;
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS trg_credits_balance_update AFTER UPDATE ON credits
+            BEGIN
+                UPDATE customers SET
+                    cached_credit = cached_credit - CASE WHEN {old_charge} THEN OLD.amount ELSE 0 END,
+                    cached_paid = cached_paid - CASE WHEN OLD.is_payment = 1 THEN OLD.amount ELSE 0 END
+                WHERE id = OLD.customer_id;
+                UPDATE customers SET
+                    cached_credit = cached_credit + CASE WHEN {new_charge} THEN NEW.amount ELSE 0 END,
+                    cached_paid = cached_paid + CASE WHEN NEW.is_payment = 1 THEN NEW.amount ELSE 0 END
+                WHERE id = NEW.customer_id;
+            END;
+            "
+        ))?;
+        if !balance_cache_exists {
+            conn.execute(&rebuild_balance_cache_sql(), [])?;
         }
-    }
-}
-
-#[derive(Debug, Serialize)]
-struct Product {
-    id: i64,
-    name: String,
-    sku: Option<String>,
-    unit_price: f64,
-    qty: f64,
-    note: Option<String>,
-    low_stock_threshold: f64,
-    created_at: String,
-}
-
-#[derive(Debug, Serialize)]
-struct Customer {
-    id: i64,
-    name: String,
-    phone: Option<String>,
-    note: Option<String>,
-    created_at: String,
-}
-
-#[derive(Debug, Serialize)]
-struct SaleRecord {
-    id: i64,
-    ts: String,
-    product_id: i64,
-    product_name: String,
-    qty: f64,
-    unit_price: f64,
-    total_amount: f64,
-    customer_id: Option<i64>,
-    customer_name: Option<String>,
-    customer_phone: Option<String>,
-    note: Option<String>,
-    is_credit: bool,
-    is_return: bool,
-    origin_sale_id: Option<i64>,
-    customer_deleted: bool,
-}
-
-#[derive(Debug, Serialize)]
-struct StockMovement {
+        // free-form label for grouping movements during reconciliation
+        ensure_column(
+            conn,
+            "transactions",
+            "tag",
+            "ALTER TABLE transactions ADD COLUMN tag TEXT",
+        )?;
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_transactions_tag ON transactions(tag);",
+        )?;
+        // closing cash counts; a day can be recounted, each count kept as its own row
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS drawer_reconciliations (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                ts TEXT NOT NULL,
+                business_date TEXT NOT NULL,
+                expected_cash REAL NOT NULL,
+                counted_cash REAL NOT NULL,
+                variance REAL NOT NULL,
+                note TEXT
+            );
+            CREATE INDEX IF NOT EXISTS idx_drawer_reconciliations_date ON drawer_reconciliations(business_date);
+            ",
+        )?;
+        // where a product is normally bought from, for per-supplier reorder lists
+        ensure_column(
+            conn,
+            "products",
+            "preferred_supplier_id",
+            "ALTER TABLE products ADD COLUMN preferred_supplier_id INTEGER REFERENCES suppliers(id) ON DELETE SET NULL",
+        )?;
+        // customers the shop has stopped selling to on credit
+        ensure_column(
+            conn,
+            "customers",
+            "on_hold",
+            "ALTER TABLE customers ADD COLUMN on_hold INTEGER NOT NULL DEFAULT 0",
+        )?;
+        ensure_column(
+            conn,
+            "customers",
+            "hold_reason",
+            "ALTER TABLE customers ADD COLUMN hold_reason TEXT",
+        )?;
+        // marks the ADJUST rows record_stocktake writes, the only ones undo_stocktake may revert;
+        // older counts are recognised by their price, which repair re-stocks never carry
+        let stocktake_flag_exists = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM pragma_table_info('transactions') WHERE name = 'is_stocktake')",
+            [],
+            |row| row.get::<_, i64>(0),
+        )? != 0;
+        ensure_column(
+            conn,
+            "transactions",
+            "is_stocktake",
+            "ALTER TABLE transactions ADD COLUMN is_stocktake INTEGER NOT NULL DEFAULT 0",
+        )?;
+        if !stocktake_flag_exists {
+            conn.execute(
+                "UPDATE transactions SET is_stocktake = 1
+                 WHERE kind = 'ADJUST' AND reverses_id IS NULL AND unit_price IS NOT NULL",
+                [],
+            )?;
+        }
+        // the IN leg of a convert_stock pair points at its OUT leg; tags can be edited, so older
+        // pairs are linked once from the tag and timestamp they were written with
+        let conversion_link_exists = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM pragma_table_info('transactions') WHERE name = 'conversion_of')",
+            [],
+            |row| row.get::<_, i64>(0),
+        )? != 0;
+        ensure_column(
+            conn,
+            "transactions",
+            "conversion_of",
+            "ALTER TABLE transactions ADD COLUMN conversion_of INTEGER REFERENCES transactions(id)",
+        )?;
+        if !conversion_link_exists {
+            conn.execute(
+                "UPDATE transactions SET conversion_of = (
+                    SELECT o.id FROM transactions o
+                    WHERE o.kind = 'OUT' AND o.tag = ?1 AND o.ts = transactions.ts
+                    ORDER BY o.id DESC LIMIT 1
+                 )
+                 WHERE kind = 'IN' AND tag = ?1",
+                params![STOCK_CONVERSION_TAG],
+            )?;
+        }
+        // end-of-day stock levels; one row per product per shop day
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS daily_snapshots (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                snapshot_date TEXT NOT NULL,
+                captured_at TEXT NOT NULL,
+                product_id INTEGER NOT NULL,
+                product_name TEXT NOT NULL,
+                qty REAL NOT NULL,
+                unit_price REAL NOT NULL,
+                cost_price REAL,
+                UNIQUE(snapshot_date, product_id),
+                FOREIGN KEY(product_id) REFERENCES products(id) ON DELETE CASCADE
+            );
+            ",
+        )?;
+        // one header per delivery; its IN movements point back through receipt_id
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS goods_receipts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                ts TEXT NOT NULL,
+                supplier_id INTEGER,
+                note TEXT,
+                FOREIGN KEY(supplier_id) REFERENCES suppliers(id) ON DELETE SET NULL
+            );
+            ",
+        )?;
+        ensure_column(
+            conn,
+            "transactions",
+            "receipt_id",
+            "ALTER TABLE transactions ADD COLUMN receipt_id INTEGER REFERENCES goods_receipts(id)",
+        )?;
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_transactions_receipt ON transactions(receipt_id);",
+        )?;
+        // older databases may already hold duplicate SKUs; the commands still enforce uniqueness there
+        let has_duplicate_skus = conn.query_row(
+            "SELECT EXISTS(
+                SELECT 1 FROM products
+                WHERE sku IS NOT NULL AND archived = 0
+                GROUP BY sku HAVING COUNT(*) > 1
+            )",
+            [],
+            |row| row.get::<_, i64>(0),
+        )? != 0;
+        if !has_duplicate_skus {
+            conn.execute(
+                "CREATE UNIQUE INDEX IF NOT EXISTS idx_products_sku_active ON products(sku) WHERE sku IS NOT NULL AND archived = 0",
+                [],
+            )?;
+        }
+        // customers may share a phone (family members, a shop line), so an earlier unique
+        // index on it is removed again
+        conn.execute("DROP INDEX IF EXISTS idx_customers_phone_unique", [])?;
+        conn.execute(
+            &format!(
+                "{SALE_CUSTOMER_SNAPSHOT_SQL} WHERE customer_id IS NOT NULL AND customer_name_snapshot IS NULL"
+            ),
+            [],
+        )?;
+        // never lower the version a newer build already stamped on this file
+        if read_schema_version(conn)? < SCHEMA_VERSION {
+            conn.execute_batch(&format!("PRAGMA user_version = {SCHEMA_VERSION};"))?;
+        }
+        Ok(())
+    }
+}
+
+const SALE_CUSTOMER_SNAPSHOT_SQL: &str = "UPDATE sales SET
+        customer_name_snapshot = (SELECT name FROM customers WHERE id = sales.customer_id),
+        customer_phone_snapshot = (SELECT phone FROM customers WHERE id = sales.customer_id)";
+
+fn snapshot_sale_customer(conn: &Connection, sale_id: i64) -> Result<(), AppError> {
+    conn.execute(
+        &format!("{SALE_CUSTOMER_SNAPSHOT_SQL} WHERE id = ?"),
+        params![sale_id],
+    )?;
+    Ok(())
+}
+
+// one entry per invalid form field so the UI can highlight every problem in one pass
+#[derive(Debug, Serialize)]
+struct FieldError {
+    field: &'static str,
+    message: String,
+}
+
+fn field_error(field: &'static str, message: impl Into<String>) -> FieldError {
+    FieldError {
+        field,
+        message: message.into(),
+    }
+}
+
+// rendered as one "field: message" line per error after the header line
+fn format_field_errors(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|error| format!("{}: {}", error.field, error.message))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+const MAX_NAME_LEN: usize = 200;
+const MAX_SHORT_TEXT_LEN: usize = 100;
+const MAX_NOTE_LEN: usize = 2000;
+
+fn check_text_len(
+    errors: &mut Vec<FieldError>,
+    field: &'static str,
+    value: Option<&str>,
+    max: usize,
+) {
+    if value.is_some_and(|text| text.chars().count() > max) {
+        errors.push(field_error(field, format!("{max}자 이하로 입력해주세요.")));
+    }
+}
+
+// length limits for commands that otherwise validate one condition at a time
+fn ensure_text_len(field: &'static str, value: Option<&str>, max: usize) -> Result<(), AppError> {
+    let mut errors = Vec::new();
+    check_text_len(&mut errors, field, value, max);
+    check_fields(errors)
+}
+
+fn check_fields(errors: Vec<FieldError>) -> Result<(), AppError> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Fields(errors))
+    }
+}
+
+fn now_iso() -> String {
+    Utc::now().to_rfc3339()
+}
+
+fn map_app_err(err: AppError) -> String {
+    err.into()
+}
+
+fn map_sql_err(err: rusqlite::Error) -> String {
+    AppError::from(err).into()
+}
+
+// for writes that can hit a UNIQUE index: names the clashing field instead of echoing SQLite
+fn map_unique_err(err: rusqlite::Error) -> String {
+    let message = match &err {
+        rusqlite::Error::SqliteFailure(failure, Some(detail))
+            if failure.code == rusqlite::ErrorCode::ConstraintViolation
+                && detail.starts_with("UNIQUE constraint failed") =>
+        {
+            if detail.contains("products.name") {
+                Some("이미 같은 이름의 상품이 있습니다.")
+            } else if detail.contains("products.sku") {
+                Some("이미 같은 SKU의 상품이 있습니다.")
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+    match message {
+        Some(message) => AppError::Validation(message.into()).into(),
+        None => map_sql_err(err),
+    }
+}
+
+// keys accepted by update_setting; anything else is rejected
+const SETTING_KEYS: &[&str] = &[
+    "price_override_max_multiplier",
+    "allow_below_cost_sales",
+    "currency_code",
+    "currency_symbol",
+    "return_note_qty_threshold",
+    "return_note_amount_threshold",
+    "default_low_stock_threshold",
+    "timezone",
+    "auto_backup",
+    "backup_retention",
+    "shop_name",
+    "journal_account_cash",
+    "journal_account_receivable",
+    "journal_account_revenue",
+    "journal_account_inventory",
+    "cash_rounding",
+];
+
+fn read_setting(conn: &Connection, key: &str) -> Result<Option<String>, AppError> {
+    let value = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = ?",
+            params![key],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?;
+    Ok(value)
+}
+
+fn read_setting_f64(conn: &Connection, key: &str, default: f64) -> Result<f64, AppError> {
+    Ok(read_setting(conn, key)?
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .unwrap_or(default))
+}
+
+// raised when an optimistic update matches no row because another window saved first
+fn stale_record_error() -> AppError {
+    AppError::Validation(
+        "다른 곳에서 수정된 항목입니다. 새로고침 후 다시 시도해주세요.".into(),
+    )
+}
+
+// the shop's calendar: an IANA zone from the timezone setting, or the machine's own zone when unset
+#[derive(Debug, Clone, Copy)]
+enum BusinessTz {
+    System,
+    Named(Tz),
+}
+
+impl BusinessTz {
+    fn from_setting(value: Option<&str>) -> Self {
+        value
+            .and_then(|name| name.trim().parse::<Tz>().ok())
+            .map_or(BusinessTz::System, BusinessTz::Named)
+    }
+
+    fn read(conn: &Connection) -> Result<Self, AppError> {
+        let value = read_setting(conn, "timezone")?;
+        Ok(Self::from_setting(value.as_deref()))
+    }
+
+    // earliest instant of the local wall-clock time, so DST gaps still resolve
+    fn to_utc(self, local: &NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            BusinessTz::System => Local
+                .from_local_datetime(local)
+                .earliest()
+                .map(|dt| dt.with_timezone(&Utc)),
+            BusinessTz::Named(tz) => tz
+                .from_local_datetime(local)
+                .earliest()
+                .map(|dt| dt.with_timezone(&Utc)),
+        }
+    }
+
+    fn today(self) -> NaiveDate {
+        match self {
+            BusinessTz::System => Local::now().date_naive(),
+            BusinessTz::Named(tz) => Utc::now().with_timezone(&tz).date_naive(),
+        }
+    }
+
+    // stored UTC ts as shop wall-clock time
+    fn local_datetime(self, ts: &str) -> Option<NaiveDateTime> {
+        let dt = DateTime::parse_from_rfc3339(ts).ok()?;
+        Some(match self {
+            BusinessTz::System => dt.with_timezone(&Local).naive_local(),
+            BusinessTz::Named(tz) => dt.with_timezone(&tz).naive_local(),
+        })
+    }
+}
+
+// range bounds accept RFC3339 timestamps or shop-local YYYY-MM-DD dates and are normalised to
+// UTC RFC3339 so they compare correctly against stored ts values; a date-only `to` covers that whole day
+fn resolve_date_range(
+    tz: BusinessTz,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<(Option<String>, Option<String>), AppError> {
+    let from = from
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| normalize_range_bound(tz, value, false))
+        .transpose()?;
+    let to = to
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| normalize_range_bound(tz, value, true))
+        .transpose()?;
+    if let (Some(start), Some(end)) = (&from, &to) {
+        if start >= end {
+            return Err(AppError::Validation(
+                "조회 시작일은 종료일보다 앞서야 합니다.".into(),
+            ));
+        }
+    }
+    Ok((from, to))
+}
+
+fn normalize_range_bound(tz: BusinessTz, value: &str, is_end: bool) -> Result<String, AppError> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.with_timezone(&Utc).to_rfc3339());
+    }
+    let invalid = || AppError::Validation(format!("날짜 형식이 올바르지 않습니다: {value}"));
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| invalid())?;
+    let date = if is_end {
+        date.succ_opt().ok_or_else(invalid)?
+    } else {
+        date
+    };
+    let midnight = date.and_hms_opt(0, 0, 0).ok_or_else(invalid)?;
+    let start = tz.to_utc(&midnight).ok_or_else(invalid)?;
+    Ok(start.to_rfc3339())
+}
+
+const MIN_PHONE_DIGITS: usize = 7;
+const MAX_PHONE_DIGITS: usize = 15;
+
+// digits only, keeping a leading + for international numbers
+fn phone_digits(phone: &str) -> String {
+    let trimmed = phone.trim();
+    let digits: String = trimmed.chars().filter(char::is_ascii_digit).collect();
+    if trimmed.starts_with('+') {
+        format!("+{digits}")
+    } else {
+        digits
+    }
+}
+
+fn normalize_phone(phone: &str) -> Result<String, AppError> {
+    if phone.trim().is_empty() {
+        return Err(AppError::Validation("고객 연락처를 입력해주세요.".into()));
+    }
+    let normalized = phone_digits(phone);
+    let digit_count = normalized.trim_start_matches('+').len();
+    if !(MIN_PHONE_DIGITS..=MAX_PHONE_DIGITS).contains(&digit_count) {
+        return Err(AppError::Validation(
+            "올바른 연락처 형식이 아닙니다.".into(),
+        ));
+    }
+    Ok(normalized)
+}
+
+// optional text is stored trimmed, with blank input saved as NULL rather than whitespace
+fn clean_text(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|text| !text.is_empty())
+}
+
+fn has_text(value: Option<&str>) -> bool {
+    value.is_some_and(|text| !text.trim().is_empty())
+}
+
+fn read_setting_bool(conn: &Connection, key: &str, default: bool) -> Result<bool, AppError> {
+    Ok(match read_setting(conn, key)?.as_deref().map(str::trim) {
+        Some("1") | Some("true") => true,
+        Some("0") | Some("false") => false,
+        _ => default,
+    })
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum TransactionKind {
+    In,
+    Out,
+    Return,
+    Adjust,
+    SupplierReturn,
+}
+
+impl TransactionKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TransactionKind::In => "IN",
+            TransactionKind::Out => "OUT",
+            TransactionKind::Return => "RETURN",
+            TransactionKind::Adjust => "ADJUST",
+            TransactionKind::SupplierReturn => "SUPPLIER_RETURN",
+        }
+    }
+
+    fn from_db(value: &str) -> Option<Self> {
+        match value {
+            "IN" => Some(TransactionKind::In),
+            "OUT" => Some(TransactionKind::Out),
+            "RETURN" => Some(TransactionKind::Return),
+            "ADJUST" => Some(TransactionKind::Adjust),
+            "SUPPLIER_RETURN" => Some(TransactionKind::SupplierReturn),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum CashEntryKind {
+    Expense,
+    Income,
+}
+
+impl CashEntryKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CashEntryKind::Expense => "EXPENSE",
+            CashEntryKind::Income => "INCOME",
+        }
+    }
+
+    fn from_db(value: &str) -> Option<Self> {
+        match value {
+            "EXPENSE" => Some(CashEntryKind::Expense),
+            "INCOME" => Some(CashEntryKind::Income),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Product {
+    id: i64,
+    name: String,
+    sku: Option<String>,
+    unit_price: f64,
+    qty: f64,
+    note: Option<String>,
+    low_stock_threshold: f64,
+    created_at: String,
+    archived: bool,
+    cost_price: Option<f64>,
+    version: i64,
+    allow_negative_stock: bool,
+    reorder_multiple: Option<f64>,
+    qty_step: Option<f64>,
+    damaged_qty: f64,
+    tax_rate: f64,
+    reorder_point: Option<f64>,
+    preferred_supplier_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+struct Customer {
+    id: i64,
+    name: String,
+    phone: Option<String>,
+    note: Option<String>,
+    created_at: String,
+    version: i64,
+    on_hold: bool,
+    hold_reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Supplier {
+    id: i64,
+    name: String,
+    phone: Option<String>,
+    note: Option<String>,
+    created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SaleRecord {
+    id: i64,
+    ts: String,
+    product_id: i64,
+    product_name: String,
+    qty: f64,
+    unit_price: f64,
+    total_amount: f64,
+    customer_id: Option<i64>,
+    customer_name: Option<String>,
+    customer_phone: Option<String>,
+    note: Option<String>,
+    is_credit: bool,
+    is_return: bool,
+    origin_sale_id: Option<i64>,
+    customer_deleted: bool,
+    invoice_id: Option<i64>,
+    // cash rounding already included in total_amount
+    rounding_adjustment: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct StockMovement {
     id: i64,
     ts: String,
     kind: TransactionKind,
@@ -297,6 +1248,10 @@ struct StockMovement {
     customer_name: Option<String>,
     note: Option<String>,
     sale_id: Option<i64>,
+    reverses_id: Option<i64>,
+    supplier_id: Option<i64>,
+    receipt_id: Option<i64>,
+    tag: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -310,6 +1265,8 @@ struct CreditEntry {
     amount: f64,
     is_payment: bool,
     note: Option<String>,
+    is_adjustment: bool,
+    is_discount: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -327,10 +1284,56 @@ struct CustomerBalance {
 struct AppData {
     products: Vec<Product>,
     customers: Vec<Customer>,
+    suppliers: Vec<Supplier>,
     sales: Vec<SaleRecord>,
     stock_movements: Vec<StockMovement>,
     credits: Vec<CreditEntry>,
     customer_balances: Vec<CustomerBalance>,
+    cash_entries: Vec<CashEntry>,
+    settings: AppSettings,
+}
+
+#[derive(Debug, Serialize)]
+struct AppSettings {
+    currency_code: String,
+    currency_symbol: String,
+    currency_decimals: usize,
+    price_override_max_multiplier: f64,
+    allow_below_cost_sales: bool,
+    return_note_qty_threshold: f64,
+    return_note_amount_threshold: f64,
+    default_low_stock_threshold: f64,
+    // None means the system zone is used
+    timezone: Option<String>,
+    auto_backup: bool,
+    backup_retention: usize,
+    shop_name: Option<String>,
+    journal_accounts: JournalAccounts,
+    // smallest coin cash amounts are rounded to; 0 disables rounding
+    cash_rounding: f64,
+}
+
+// account names export_journal books each side of an entry to
+#[derive(Debug, Serialize)]
+struct JournalAccounts {
+    cash: String,
+    receivable: String,
+    revenue: String,
+    inventory: String,
+}
+
+impl JournalAccounts {
+    fn read(conn: &Connection) -> Result<Self, AppError> {
+        let account = |key: &str, default: &str| -> Result<String, AppError> {
+            Ok(read_setting(conn, key)?.unwrap_or_else(|| default.to_string()))
+        };
+        Ok(JournalAccounts {
+            cash: account("journal_account_cash", "현금")?,
+            receivable: account("journal_account_receivable", "외상매출금")?,
+            revenue: account("journal_account_revenue", "상품매출")?,
+            inventory: account("journal_account_inventory", "상품")?,
+        })
+    }
 }
 
 #[tauri::command]
@@ -338,6 +1341,89 @@ fn get_app_data(state: State<DbState>) -> CommandResult<AppData> {
     load_app_data(&state).map_err(Into::into)
 }
 
+#[tauri::command]
+fn update_setting(
+    state: State<DbState>,
+    key: String,
+    value: Option<String>,
+) -> CommandResult<AppData> {
+    if !SETTING_KEYS.contains(&key.as_str()) {
+        return Err(AppError::Validation(format!("알 수 없는 설정 항목입니다: {key}")).into());
+    }
+    let value = value.as_deref().map(str::trim).filter(|v| !v.is_empty());
+    if let Some(value) = value {
+        validate_setting(&key, value).map_err(map_app_err)?;
+    }
+    let conn = state.open().map_err(map_app_err)?;
+    match value {
+        Some(value) => {
+            conn.execute(
+                "INSERT INTO settings (key, value) VALUES (?, ?)
+                 ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                params![key, value],
+            )
+            .map_err(map_sql_err)?;
+        }
+        // clearing a setting falls back to the built-in default
+        None => {
+            conn.execute("DELETE FROM settings WHERE key = ?", params![key])
+                .map_err(map_sql_err)?;
+        }
+    }
+    load_app_data(&state).map_err(Into::into)
+}
+
+// rejects a value the matching reader would silently replace with its default
+fn validate_setting(key: &str, value: &str) -> Result<(), AppError> {
+    let number = || value.parse::<f64>().ok().filter(|n| n.is_finite());
+    let message = match key {
+        "timezone" if value.parse::<Tz>().is_err() => format!("알 수 없는 시간대입니다: {value}"),
+        "cash_rounding" if !number().is_some_and(|step| step >= 0.0) => {
+            "현금 단수 단위는 0 이상의 숫자여야 합니다.".to_string()
+        }
+        "price_override_max_multiplier" if !number().is_some_and(|n| n > 0.0) => {
+            format!("{key} 값은 0보다 큰 숫자여야 합니다.")
+        }
+        "return_note_qty_threshold"
+        | "return_note_amount_threshold"
+        | "default_low_stock_threshold"
+            if !number().is_some_and(|n| n >= 0.0) =>
+        {
+            format!("{key} 값은 0 이상의 숫자여야 합니다.")
+        }
+        "allow_below_cost_sales" | "auto_backup"
+            if !matches!(value, "1" | "true" | "0" | "false") =>
+        {
+            format!("{key} 값은 true 또는 false여야 합니다.")
+        }
+        "backup_retention"
+            if !value
+                .parse::<i64>()
+                .is_ok_and(|n| (1..=MAX_BACKUP_RETENTION).contains(&n)) =>
+        {
+            format!("{key} 값은 1에서 {MAX_BACKUP_RETENTION} 사이여야 합니다.")
+        }
+        "currency_code"
+            if !(value.len() == 3 && value.chars().all(|c| c.is_ascii_alphabetic())) =>
+        {
+            format!("{key} 값은 영문 세 글자 통화 코드여야 합니다.")
+        }
+        _ => return Ok(()),
+    };
+    Err(AppError::Validation(message))
+}
+
+#[tauri::command]
+fn format_amount(state: State<DbState>, amount: f64) -> CommandResult<String> {
+    let conn = state.open().map_err(map_app_err)?;
+    let settings = fetch_settings(&conn).map_err(map_app_err)?;
+    Ok(format_currency(
+        amount,
+        &settings.currency_symbol,
+        settings.currency_decimals,
+    ))
+}
+
 #[derive(Debug, Deserialize)]
 struct ProductForm {
     name: String,
@@ -346,36 +1432,164 @@ struct ProductForm {
     note: Option<String>,
     low_stock_threshold: Option<f64>,
     initial_qty: Option<f64>,
+    cost_price: Option<f64>,
+    allow_negative_stock: Option<bool>,
+    reorder_multiple: Option<f64>,
+    qty_step: Option<f64>,
+    tax_rate: Option<f64>,
+    reorder_point: Option<f64>,
+    preferred_supplier_id: Option<i64>,
 }
 
-#[tauri::command]
-fn create_product(state: State<DbState>, payload: ProductForm) -> CommandResult<AppData> {
+// the fields create and update share, so both report every problem in one pass
+struct ProductFields<'a> {
+    name: &'a str,
+    sku: Option<&'a str>,
+    unit_price: f64,
+    note: Option<&'a str>,
+    low_stock_threshold: Option<f64>,
+    cost_price: Option<f64>,
+    reorder_point: Option<f64>,
+    reorder_multiple: Option<f64>,
+    qty_step: Option<f64>,
+    tax_rate: Option<f64>,
+}
+
+impl<'a> From<&'a ProductForm> for ProductFields<'a> {
+    fn from(form: &'a ProductForm) -> Self {
+        Self {
+            name: &form.name,
+            sku: form.sku.as_deref(),
+            unit_price: form.unit_price,
+            note: form.note.as_deref(),
+            low_stock_threshold: form.low_stock_threshold,
+            cost_price: form.cost_price,
+            reorder_point: form.reorder_point,
+            reorder_multiple: form.reorder_multiple,
+            qty_step: form.qty_step,
+            tax_rate: form.tax_rate,
+        }
+    }
+}
+
+impl<'a> From<&'a ProductUpdateForm> for ProductFields<'a> {
+    fn from(form: &'a ProductUpdateForm) -> Self {
+        Self {
+            name: &form.name,
+            sku: form.sku.as_deref(),
+            unit_price: form.unit_price,
+            note: form.note.as_deref(),
+            low_stock_threshold: form.low_stock_threshold,
+            cost_price: form.cost_price.flatten(),
+            reorder_point: form.reorder_point.flatten(),
+            reorder_multiple: form.reorder_multiple.flatten(),
+            qty_step: form.qty_step.flatten(),
+            tax_rate: form.tax_rate,
+        }
+    }
+}
+
+fn validate_product_form(payload: &ProductForm) -> Result<(), AppError> {
+    let mut errors = Vec::new();
+    if payload.initial_qty.is_some_and(|qty| qty < 0.0) {
+        errors.push(field_error(
+            "initial_qty",
+            "초기 재고는 0 이상이어야 합니다.",
+        ));
+    }
+    check_product_fields(&mut errors, payload.into());
+    check_fields(errors)
+}
+
+fn check_product_fields(errors: &mut Vec<FieldError>, payload: ProductFields<'_>) {
     if payload.name.trim().is_empty() {
-        return Err(AppError::Validation("품명을 입력해주세요.".into()).into());
+        errors.push(field_error("name", "품명을 입력해주세요."));
     }
     if payload.unit_price < 0.0 {
-        return Err(AppError::Validation("단가는 0 이상이어야 합니다.".into()).into());
+        errors.push(field_error("unit_price", "단가는 0 이상이어야 합니다."));
     }
-    if let Some(qty) = payload.initial_qty {
-        if qty < 0.0 {
-            return Err(AppError::Validation("초기 재고는 0 이상이어야 합니다.".into()).into());
-        }
+    if payload.cost_price.is_some_and(|cost| cost < 0.0) {
+        errors.push(field_error("cost_price", "원가는 0 이상이어야 합니다."));
+    }
+    if payload.low_stock_threshold.is_some_and(|level| level < 0.0) {
+        errors.push(field_error(
+            "low_stock_threshold",
+            "재고 경고 기준은 0 이상이어야 합니다.",
+        ));
+    }
+    if payload.reorder_point.is_some_and(|level| level < 0.0) {
+        errors.push(field_error(
+            "reorder_point",
+            "발주 기준은 0 이상이어야 합니다.",
+        ));
+    }
+    if payload.reorder_multiple.is_some_and(|size| size <= 0.0) {
+        errors.push(field_error(
+            "reorder_multiple",
+            "발주 단위는 0보다 커야 합니다.",
+        ));
+    }
+    if payload.qty_step.is_some_and(|step| step <= 0.0) {
+        errors.push(field_error("qty_step", "수량 단위는 0보다 커야 합니다."));
+    }
+    if payload.tax_rate.is_some_and(|rate| !valid_tax_rate(rate)) {
+        errors.push(field_error("tax_rate", "세율은 0에서 100 사이여야 합니다."));
     }
+    check_text_len(errors, "name", Some(payload.name.trim()), MAX_NAME_LEN);
+    check_text_len(errors, "sku", payload.sku, MAX_SHORT_TEXT_LEN);
+    check_text_len(errors, "note", clean_text(payload.note), MAX_NOTE_LEN);
+}
+
+// a percentage; 0 marks zero-rated goods
+fn valid_tax_rate(rate: f64) -> bool {
+    (0.0..=100.0).contains(&rate)
+}
+
+const DEFAULT_LOW_STOCK_THRESHOLD: f64 = 5.0;
+
+fn resolve_low_stock_threshold(conn: &Connection, value: Option<f64>) -> Result<f64, AppError> {
+    match value {
+        Some(threshold) => Ok(threshold),
+        None => read_setting_f64(
+            conn,
+            "default_low_stock_threshold",
+            DEFAULT_LOW_STOCK_THRESHOLD,
+        ),
+    }
+}
+
+#[tauri::command]
+fn create_product(state: State<DbState>, payload: ProductForm) -> CommandResult<AppData> {
+    let note = clean_text(payload.note.as_deref());
+    validate_product_form(&payload).map_err(map_app_err)?;
 
     let mut conn = state.open().map_err(map_app_err)?;
     let tx = conn.transaction().map_err(map_sql_err)?;
+    let sku = ensure_sku_available(&tx, payload.sku.as_deref(), None).map_err(map_app_err)?;
+    let low_stock_threshold =
+        resolve_low_stock_threshold(&tx, payload.low_stock_threshold).map_err(map_app_err)?;
+    if let Some(supplier_id) = payload.preferred_supplier_id {
+        supplier_name(&tx, supplier_id).map_err(map_app_err)?;
+    }
 
     tx.execute(
-        "INSERT INTO products (name, sku, unit_price, qty, note, low_stock_threshold) VALUES (?, ?, ?, 0, ?, ?)",
+        "INSERT INTO products (name, sku, unit_price, qty, note, low_stock_threshold, cost_price, allow_negative_stock, reorder_multiple, qty_step, tax_rate, reorder_point, preferred_supplier_id) VALUES (?, ?, ?, 0, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         params![
             payload.name.trim(),
-            payload.sku.as_deref(),
+            sku,
             payload.unit_price,
-            payload.note.as_deref(),
-            payload.low_stock_threshold.unwrap_or(5.0)
+            note,
+            low_stock_threshold,
+            payload.cost_price,
+            if payload.allow_negative_stock.unwrap_or(false) { 1 } else { 0 },
+            payload.reorder_multiple,
+            payload.qty_step,
+            payload.tax_rate.unwrap_or(0.0),
+            payload.reorder_point,
+            payload.preferred_supplier_id
         ],
     )
-    .map_err(map_sql_err)?;
+    .map_err(map_unique_err)?;
 
     let product_id = tx.last_insert_rowid();
     if let Some(initial_qty) = payload.initial_qty {
@@ -405,885 +1619,6651 @@ fn create_product(state: State<DbState>, payload: ProductForm) -> CommandResult<
     load_app_data(&state).map_err(Into::into)
 }
 
-#[derive(Debug, Deserialize)]
-struct ProductUpdateForm {
-    id: i64,
-    name: String,
-    sku: Option<String>,
-    unit_price: f64,
-    note: Option<String>,
-    low_stock_threshold: Option<f64>,
-}
-
+// copies the pricing and stock settings of an existing product; stock, lots and history stay behind
 #[tauri::command]
-fn update_product(state: State<DbState>, payload: ProductUpdateForm) -> CommandResult<AppData> {
-    if payload.name.trim().is_empty() {
+fn clone_product(
+    state: State<DbState>,
+    source_id: i64,
+    new_name: String,
+) -> CommandResult<AppData> {
+    let name = new_name.trim();
+    if name.is_empty() {
         return Err(AppError::Validation("품명을 입력해주세요.".into()).into());
     }
-    if payload.unit_price < 0.0 {
-        return Err(AppError::Validation("단가는 0 이상이어야 합니다.".into()).into());
+    ensure_text_len("name", Some(name), MAX_NAME_LEN).map_err(map_app_err)?;
+
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    let source = fetch_product(&tx, source_id)
+        .map_err(map_app_err)?
+        .ok_or_else(|| AppError::Validation("존재하지 않는 품목입니다.".into()))?;
+
+    // names are unique across archived products too, so say which one is in the way
+    let existing = tx
+        .query_row(
+            "SELECT archived FROM products WHERE name = ?",
+            params![name],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+    match existing {
+        Some(0) => {
+            return Err(AppError::Validation(format!("'{name}' 품목이 이미 있습니다.")).into());
+        }
+        Some(_) => {
+            return Err(AppError::Validation(format!(
+                "보관된 품목 중에 '{name}'이(가) 있습니다. 복원하거나 다른 이름을 사용해주세요."
+            ))
+            .into());
+        }
+        None => {}
     }
 
-    let conn = state.open().map_err(map_app_err)?;
-    conn.execute(
-        "UPDATE products SET name = ?, sku = ?, unit_price = ?, note = ?, low_stock_threshold = ? WHERE id = ?",
+    let sku = match source.sku.as_deref() {
+        Some(sku) => Some(next_free_sku(&tx, sku).map_err(map_app_err)?),
+        None => None,
+    };
+    tx.execute(
+        "INSERT INTO products (name, sku, unit_price, qty, note, low_stock_threshold, cost_price, allow_negative_stock, reorder_multiple, qty_step, tax_rate, reorder_point, preferred_supplier_id) VALUES (?, ?, ?, 0, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         params![
-            payload.name.trim(),
-            payload.sku.as_deref(),
-            payload.unit_price,
-            payload.note.as_deref(),
-            payload.low_stock_threshold.unwrap_or(5.0),
-            payload.id
+            name,
+            sku,
+            source.unit_price,
+            source.note,
+            source.low_stock_threshold,
+            source.cost_price,
+            if source.allow_negative_stock { 1 } else { 0 },
+            source.reorder_multiple,
+            source.qty_step,
+            source.tax_rate,
+            source.reorder_point,
+            source.preferred_supplier_id
         ],
     )
-    .map_err(map_sql_err)?;
+    .map_err(map_unique_err)?;
 
+    tx.commit().map_err(map_sql_err)?;
     load_app_data(&state).map_err(Into::into)
 }
 
+// SKUs are unique among active products, so a copy gets the source SKU with the first free -N suffix
+fn next_free_sku(conn: &Connection, base: &str) -> Result<String, AppError> {
+    let mut suffix = 2;
+    loop {
+        let candidate = format!("{base}-{suffix}");
+        let taken = conn
+            .query_row(
+                "SELECT 1 FROM products WHERE sku = ? AND archived = 0",
+                params![candidate],
+                |_| Ok(()),
+            )
+            .optional()?;
+        if taken.is_none() {
+            return Ok(candidate);
+        }
+        suffix += 1;
+    }
+}
+
 #[tauri::command]
-fn delete_product(state: State<DbState>, product_id: i64) -> CommandResult<AppData> {
+fn get_product(state: State<DbState>, product_id: i64) -> CommandResult<Option<Product>> {
+    // includes archived products so edit dialogs can still inspect them
     let conn = state.open().map_err(map_app_err)?;
-    // Soft delete: archive the product so history remains intact
-    conn.execute(
-        "UPDATE products SET archived = 1 WHERE id = ?",
-        params![product_id],
-    )
-    .map_err(map_sql_err)?;
-    load_app_data(&state).map_err(Into::into)
+    fetch_product(&conn, product_id).map_err(Into::into)
 }
 
-#[derive(Debug, Deserialize)]
-struct CustomerForm {
-    name: String,
-    phone: String,
-    note: Option<String>,
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum ProductSort {
+    #[default]
+    NameAsc,
+    QtyAsc,
+    QtyDesc,
+    PriceAsc,
+    PriceDesc,
+    CreatedDesc,
 }
 
-#[derive(Debug, Deserialize)]
-struct CustomerUpdateForm {
-    id: i64,
-    name: String,
-    phone: String,
-    note: Option<String>,
+impl ProductSort {
+    // whitelisted ORDER BY clauses; user input never reaches the SQL text
+    fn order_by(&self) -> &'static str {
+        match self {
+            ProductSort::NameAsc => "name COLLATE NOCASE",
+            ProductSort::QtyAsc => "qty ASC, name COLLATE NOCASE",
+            ProductSort::QtyDesc => "qty DESC, name COLLATE NOCASE",
+            ProductSort::PriceAsc => "unit_price ASC, name COLLATE NOCASE",
+            ProductSort::PriceDesc => "unit_price DESC, name COLLATE NOCASE",
+            ProductSort::CreatedDesc => "created_at DESC, id DESC",
+        }
+    }
 }
 
 #[tauri::command]
-fn create_customer(state: State<DbState>, payload: CustomerForm) -> CommandResult<AppData> {
-    if payload.name.trim().is_empty() {
-        return Err(AppError::Validation("고객 이름을 입력해주세요.".into()).into());
-    }
-    let phone = payload.phone.trim();
-    if phone.is_empty() {
-        return Err(AppError::Validation("고객 연락처를 입력해주세요.".into()).into());
-    }
+fn list_products(state: State<DbState>, sort: Option<ProductSort>) -> CommandResult<Vec<Product>> {
     let conn = state.open().map_err(map_app_err)?;
-    conn.execute(
-        "INSERT INTO customers (name, phone, note) VALUES (?, ?, ?)",
-        params![payload.name.trim(), phone, payload.note.as_deref()],
-    )
-    .map_err(map_sql_err)?;
-
-    load_app_data(&state).map_err(Into::into)
+    fetch_products_sorted(&conn, sort.unwrap_or_default()).map_err(Into::into)
 }
 
+// the display warning only; what to buy comes from reorder_suggestions and the reorder point
 #[tauri::command]
-fn update_customer(state: State<DbState>, payload: CustomerUpdateForm) -> CommandResult<AppData> {
-    if payload.name.trim().is_empty() {
-        return Err(AppError::Validation("고객 이름을 입력해주세요.".into()).into());
-    }
-    let phone = payload.phone.trim();
-    if phone.is_empty() {
-        return Err(AppError::Validation("고객 연락처를 입력해주세요.".into()).into());
-    }
+fn low_stock_products(state: State<DbState>) -> CommandResult<Vec<Product>> {
     let conn = state.open().map_err(map_app_err)?;
-    conn.execute(
-        "UPDATE customers SET name = ?, phone = ?, note = ? WHERE id = ?",
-        params![
-            payload.name.trim(),
-            phone,
-            payload.note.as_deref(),
-            payload.id
-        ],
-    )
-    .map_err(map_sql_err)?;
+    let sql = format!(
+        "SELECT {PRODUCT_COLUMNS}
+         FROM products
+         WHERE archived = 0 AND qty <= low_stock_threshold
+         ORDER BY name COLLATE NOCASE"
+    );
+    let mut stmt = conn.prepare(&sql).map_err(map_sql_err)?;
+    let rows = stmt.query_map([], product_from_row).map_err(map_sql_err)?;
 
-    load_app_data(&state).map_err(Into::into)
+    let mut products = Vec::new();
+    for row in rows {
+        products.push(row.map_err(map_sql_err)?);
+    }
+    Ok(products)
+}
+
+#[derive(Debug, Serialize)]
+struct SimilarProductGroup {
+    normalized_name: String,
+    products: Vec<Product>,
 }
 
+// names that only differ in case or spacing; the owner picks which of each group to merge
 #[tauri::command]
-fn delete_customer(state: State<DbState>, customer_id: i64) -> CommandResult<AppData> {
+fn find_similar_products(state: State<DbState>) -> CommandResult<Vec<SimilarProductGroup>> {
     let conn = state.open().map_err(map_app_err)?;
-    // mark related sales as deleted-customer before FK nullify kicks in
-    conn.execute(
-        "UPDATE sales SET customer_deleted = 1 WHERE customer_id = ?",
-        params![customer_id],
-    )
-    .map_err(map_sql_err)?;
-    conn.execute("DELETE FROM customers WHERE id = ?", params![customer_id])
-        .map_err(|err| match err {
-            rusqlite::Error::SqliteFailure(_, _) => {
-                AppError::Validation("해당 고객은 거래 내역이 있어 삭제할 수 없습니다.".into())
-                    .to_string()
-            }
-            other => other.to_string(),
-        })?;
+    let mut groups: BTreeMap<String, Vec<Product>> = BTreeMap::new();
+    for product in fetch_products(&conn).map_err(map_app_err)? {
+        let key = product
+            .name
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        groups.entry(key).or_default().push(product);
+    }
+    Ok(groups
+        .into_iter()
+        .filter(|(_, products)| products.len() > 1)
+        .map(|(normalized_name, products)| SimilarProductGroup {
+            normalized_name,
+            products,
+        })
+        .collect())
+}
 
-    load_app_data(&state).map_err(Into::into)
+// active products whose preferred supplier is this one, for assembling a purchase order
+#[tauri::command]
+fn products_by_supplier(state: State<DbState>, supplier_id: i64) -> CommandResult<Vec<Product>> {
+    let conn = state.open().map_err(map_app_err)?;
+    supplier_name(&conn, supplier_id).map_err(map_app_err)?;
+    let sql = format!(
+        "SELECT {PRODUCT_COLUMNS}
+         FROM products
+         WHERE archived = 0 AND preferred_supplier_id = ?
+         ORDER BY name COLLATE NOCASE"
+    );
+    let mut stmt = conn.prepare(&sql).map_err(map_sql_err)?;
+    let rows = stmt
+        .query_map(params![supplier_id], product_from_row)
+        .map_err(map_sql_err)?;
+
+    let mut products = Vec::new();
+    for row in rows {
+        products.push(row.map_err(map_sql_err)?);
+    }
+    Ok(products)
 }
 
 #[derive(Debug, Deserialize)]
-struct StockEntryPayload {
-    product_id: i64,
-    qty: f64,
-    kind: Option<TransactionKind>,
-    unit_price: Option<f64>,
-    counterparty: Option<String>,
-    customer_id: Option<i64>,
+struct ProductUpdateForm {
+    id: i64,
+    name: String,
+    sku: Option<String>,
+    unit_price: f64,
     note: Option<String>,
+    low_stock_threshold: Option<f64>,
+    // for the clearable columns below, a missing key keeps the stored value and null clears it
+    #[serde(default, deserialize_with = "deserialize_some")]
+    cost_price: Option<Option<f64>>,
+    version: i64,
+    allow_negative_stock: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    reorder_multiple: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    qty_step: Option<Option<f64>>,
+    tax_rate: Option<f64>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    reorder_point: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    preferred_supplier_id: Option<Option<i64>>,
+}
+
+// wraps a present value in Some, so a field defaulting to None can tell a missing key from null
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
 }
 
 #[tauri::command]
-fn record_stock_entry(state: State<DbState>, payload: StockEntryPayload) -> CommandResult<AppData> {
-    if payload.qty <= 0.0 {
-        return Err(AppError::Validation("미터은 0보다 커야 합니다.".into()).into());
-    }
-    let kind = payload.kind.unwrap_or(TransactionKind::In);
+fn update_product(state: State<DbState>, payload: ProductUpdateForm) -> CommandResult<AppData> {
+    let note = clean_text(payload.note.as_deref());
+    let mut errors = Vec::new();
+    check_product_fields(&mut errors, (&payload).into());
+    check_fields(errors).map_err(map_app_err)?;
 
     let mut conn = state.open().map_err(map_app_err)?;
     let tx = conn.transaction().map_err(map_sql_err)?;
-
-    let product = tx
+    let sku =
+        ensure_sku_available(&tx, payload.sku.as_deref(), Some(payload.id)).map_err(map_app_err)?;
+    let low_stock_threshold =
+        resolve_low_stock_threshold(&tx, payload.low_stock_threshold).map_err(map_app_err)?;
+    if let Some(Some(supplier_id)) = payload.preferred_supplier_id {
+        supplier_name(&tx, supplier_id).map_err(map_app_err)?;
+    }
+    let previous_price = tx
         .query_row(
-            "SELECT qty, unit_price FROM products WHERE id = ?",
-            params![payload.product_id],
-            |row| Ok((row.get::<_, f64>(0)?, row.get::<_, f64>(1)?)),
+            "SELECT unit_price FROM products WHERE id = ?",
+            params![payload.id],
+            |row| row.get::<_, f64>(0),
         )
         .optional()
         .map_err(map_sql_err)?;
+    let updated = tx.execute(
+        "UPDATE products SET name = ?, sku = ?, unit_price = ?, note = ?, low_stock_threshold = ?, cost_price = CASE WHEN ? THEN ? ELSE cost_price END, allow_negative_stock = COALESCE(?, allow_negative_stock), reorder_multiple = CASE WHEN ? THEN ? ELSE reorder_multiple END, qty_step = CASE WHEN ? THEN ? ELSE qty_step END, tax_rate = COALESCE(?, tax_rate), reorder_point = CASE WHEN ? THEN ? ELSE reorder_point END, preferred_supplier_id = CASE WHEN ? THEN ? ELSE preferred_supplier_id END, version = version + 1 WHERE id = ? AND version = ?",
+        params![
+            payload.name.trim(),
+            sku,
+            payload.unit_price,
+            note,
+            low_stock_threshold,
+            payload.cost_price.is_some(),
+            payload.cost_price.flatten(),
+            payload
+                .allow_negative_stock
+                .map(|allow| if allow { 1 } else { 0 }),
+            payload.reorder_multiple.is_some(),
+            payload.reorder_multiple.flatten(),
+            payload.qty_step.is_some(),
+            payload.qty_step.flatten(),
+            payload.tax_rate,
+            payload.reorder_point.is_some(),
+            payload.reorder_point.flatten(),
+            payload.preferred_supplier_id.is_some(),
+            payload.preferred_supplier_id.flatten(),
+            payload.id,
+            payload.version
+        ],
+    )
+    .map_err(map_unique_err)?;
+    if updated == 0 {
+        return Err(map_app_err(stale_record_error()));
+    }
+    if let Some(old_price) = previous_price {
+        record_price_change(&tx, payload.id, old_price, payload.unit_price, None)
+            .map_err(map_app_err)?;
+    }
+    tx.commit().map_err(map_sql_err)?;
 
-    let (current_qty, default_price) = product
-        .ok_or_else(|| AppError::Validation("존재하지 않는 품명입니다.".into()).to_string())?;
+    load_app_data(&state).map_err(Into::into)
+}
 
-    let qty_delta = match kind {
-        TransactionKind::In => payload.qty,
-        TransactionKind::Out => -payload.qty,
-        TransactionKind::Return => {
-            return Err(
-                AppError::Validation("반품 입력은 반품 등록 기능을 사용해주세요.".into()).into(),
-            );
-        }
-    };
-    let new_qty = current_qty + qty_delta;
-    if new_qty < 0.0 {
-        return Err(AppError::Validation("재고가 부족합니다.".into()).into());
-    }
-    tx.execute(
-        "UPDATE products SET qty = qty + ? WHERE id = ?",
-        params![qty_delta, payload.product_id],
-    )
-    .map_err(map_sql_err)?;
+#[tauri::command]
+fn set_product_sku(
+    state: State<DbState>,
+    product_id: i64,
+    sku: Option<String>,
+) -> CommandResult<AppData> {
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    let sku = ensure_sku_available(&tx, sku.as_deref(), Some(product_id)).map_err(map_app_err)?;
+    let updated = tx
+        .execute(
+            "UPDATE products SET sku = ?, version = version + 1 WHERE id = ?",
+            params![sku, product_id],
+        )
+        .map_err(map_unique_err)?;
+    if updated == 0 {
+        return Err(AppError::Validation("존재하지 않는 품명입니다.".into()).into());
+    }
+    tx.commit().map_err(map_sql_err)?;
 
-    let ts = now_iso();
-    let unit_price = payload.unit_price.or(Some(default_price));
-    let total_amount = unit_price.map(|price| price * payload.qty);
+    load_app_data(&state).map_err(Into::into)
+}
 
-    tx.execute(
-        "INSERT INTO transactions (ts, kind, product_id, qty, unit_price, total_amount, counterparty, customer_id, note) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
-        params![
-            ts,
-            kind.as_str(),
-            payload.product_id,
-            payload.qty,
-            unit_price,
-            total_amount,
-            payload.counterparty.as_deref(),
-            payload.customer_id,
-            payload.note.as_deref()
-        ],
-    )
-    .map_err(map_sql_err)?;
+// blank SKUs are stored as NULL; a SKU may only be used by one active product
+fn ensure_sku_available(
+    conn: &Connection,
+    sku: Option<&str>,
+    product_id: Option<i64>,
+) -> Result<Option<String>, AppError> {
+    let sku = match sku.map(str::trim).filter(|sku| !sku.is_empty()) {
+        Some(sku) => sku,
+        None => return Ok(None),
+    };
+    let conflict = conn
+        .query_row(
+            "SELECT name FROM products
+             WHERE sku = ?1 AND archived = 0 AND (?2 IS NULL OR id <> ?2)
+             LIMIT 1",
+            params![sku, product_id],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?;
+    if let Some(name) = conflict {
+        return Err(AppError::Validation(format!(
+            "SKU '{sku}'는 이미 '{name}' 품목에서 사용 중입니다."
+        )));
+    }
+    Ok(Some(sku.to_string()))
+}
 
+#[derive(Debug, Serialize)]
+struct BulkArchiveResult {
+    archived: usize,
+    skipped_ids: Vec<i64>,
+    data: AppData,
+}
+
+// unknown ids are reported back instead of failing the whole batch
+#[tauri::command]
+fn bulk_archive_products(
+    state: State<DbState>,
+    product_ids: Vec<i64>,
+) -> CommandResult<BulkArchiveResult> {
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    let mut archived = 0;
+    let mut skipped_ids = Vec::new();
+    for product_id in product_ids {
+        let updated = tx
+            .execute(
+                "UPDATE products SET archived = 1 WHERE id = ?",
+                params![product_id],
+            )
+            .map_err(map_sql_err)?;
+        if updated == 0 {
+            skipped_ids.push(product_id);
+        } else {
+            archived += 1;
+        }
+    }
     tx.commit().map_err(map_sql_err)?;
-    load_app_data(&state).map_err(Into::into)
+
+    Ok(BulkArchiveResult {
+        archived,
+        skipped_ids,
+        data: load_app_data(&state).map_err(map_app_err)?,
+    })
 }
 
 #[derive(Debug, Deserialize)]
-struct SalePayload {
-    product_id: i64,
-    qty: f64,
-    unit_price: Option<f64>,
-    customer_id: Option<i64>,
-    note: Option<String>,
-    is_credit: bool,
+struct ArchiveCriteria {
+    idle_days: i64,
 }
 
-#[derive(Debug, Deserialize)]
-struct SaleUpdatePayload {
-    id: i64,
-    qty: f64,
-    unit_price: f64,
-    customer_id: Option<i64>,
-    note: Option<String>,
-    is_credit: bool,
+// archives active products that are out of stock and have not sold within idle_days
+#[tauri::command]
+fn archive_idle_products(
+    state: State<DbState>,
+    criteria: ArchiveCriteria,
+) -> CommandResult<BulkArchiveResult> {
+    if criteria.idle_days < 0 {
+        return Err(AppError::Validation("기간은 0일 이상이어야 합니다.".into()).into());
+    }
+    let cutoff = chrono::TimeDelta::try_days(criteria.idle_days)
+        .and_then(|span| Utc::now().checked_sub_signed(span))
+        .ok_or_else(|| AppError::Validation("기간이 너무 깁니다.".into()).to_string())?
+        .to_rfc3339();
+
+    let conn = state.open().map_err(map_app_err)?;
+    let archived = conn
+        .execute(
+            "UPDATE products SET archived = 1
+             WHERE archived = 0
+               AND qty = 0
+               AND NOT EXISTS (
+                    SELECT 1 FROM sales s
+                    WHERE s.product_id = products.id AND s.is_return = 0 AND s.ts >= ?
+               )",
+            params![cutoff],
+        )
+        .map_err(map_sql_err)?;
+
+    Ok(BulkArchiveResult {
+        archived,
+        skipped_ids: Vec::new(),
+        data: load_app_data(&state).map_err(map_app_err)?,
+    })
 }
 
-#[derive(Debug, Deserialize)]
-struct ReturnPayload {
-    product_id: i64,
-    customer_id: Option<i64>,
-    qty: f64,
-    note: Option<String>,
-    override_amount: Option<f64>,
+#[derive(Debug, Serialize)]
+struct PurgeResult {
+    products: usize,
+    sales: usize,
+    transactions: usize,
 }
 
+// hard-deletes archived products idle since the cutoff, with their sales and stock history.
+// products whose sales are tied to credit ledger rows or invoices are kept, because removing
+// those sales would change customer balances. customers have no archiving yet, so only products are purged
 #[tauri::command]
-fn record_sale(state: State<DbState>, payload: SalePayload) -> CommandResult<AppData> {
-    if payload.qty <= 0.0 {
-        return Err(AppError::Validation("미터은 0보다 커야 합니다.".into()).into());
-    }
-    if payload.is_credit && payload.customer_id.is_none() {
-        return Err(AppError::Validation("외상 거래에는 고객을 선택해야 합니다.".into()).into());
+fn purge_archived(state: State<DbState>, older_than_days: i64) -> CommandResult<PurgeResult> {
+    if older_than_days < 0 {
+        return Err(AppError::Validation("보관 기간은 0일 이상이어야 합니다.".into()).into());
     }
+    let cutoff = chrono::TimeDelta::try_days(older_than_days)
+        .and_then(|span| Utc::now().checked_sub_signed(span))
+        .ok_or_else(|| AppError::Validation("보관 기간이 너무 깁니다.".into()).to_string())?
+        .to_rfc3339();
 
     let mut conn = state.open().map_err(map_app_err)?;
     let tx = conn.transaction().map_err(map_sql_err)?;
 
-    let product = tx
-        .query_row(
-            "SELECT qty, unit_price FROM products WHERE id = ?",
-            params![payload.product_id],
-            |row| Ok((row.get::<_, f64>(0)?, row.get::<_, f64>(1)?)),
-        )
-        .optional()
-        .map_err(map_sql_err)?;
-    let (current_qty, default_price) = product
-        .ok_or_else(|| AppError::Validation("존재하지 않는 품명입니다.".into()).to_string())?;
+    let product_ids = {
+        let mut stmt = tx
+            .prepare(
+                "SELECT p.id
+                 FROM products p
+                 WHERE p.archived = 1
+                   AND NOT EXISTS (SELECT 1 FROM sales s WHERE s.product_id = p.id AND s.ts >= ?1)
+                   AND NOT EXISTS (SELECT 1 FROM transactions t WHERE t.product_id = p.id AND t.ts >= ?1)
+                   AND NOT EXISTS (
+                        SELECT 1 FROM sales s
+                        WHERE s.product_id = p.id
+                          AND (
+                                s.invoice_id IS NOT NULL
+                                OR EXISTS (SELECT 1 FROM credits cr WHERE cr.sale_id = s.id OR cr.return_id = s.id)
+                              )
+                   )
+                   AND NOT EXISTS (
+                        SELECT 1 FROM transactions t2
+                        JOIN transactions t1 ON t2.conversion_of = t1.id
+                        WHERE t1.product_id = p.id AND t2.product_id != p.id
+                   )",
+            )
+            .map_err(map_sql_err)?;
+        let rows = stmt
+            .query_map(params![cutoff], |row| row.get::<_, i64>(0))
+            .map_err(map_sql_err)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(map_sql_err)?
+    };
 
-    if current_qty < payload.qty {
-        return Err(AppError::Validation("재고가 부족합니다.".into()).into());
+    let mut result = PurgeResult {
+        products: 0,
+        sales: 0,
+        transactions: 0,
+    };
+    for product_id in product_ids {
+        result.transactions += tx
+            .execute(
+                "DELETE FROM transactions WHERE product_id = ?",
+                params![product_id],
+            )
+            .map_err(map_sql_err)?;
+        // returns first so origin sales are no longer referenced
+        result.sales += tx
+            .execute(
+                "DELETE FROM sales WHERE product_id = ? AND is_return = 1",
+                params![product_id],
+            )
+            .map_err(map_sql_err)?;
+        result.sales += tx
+            .execute(
+                "DELETE FROM sales WHERE product_id = ?",
+                params![product_id],
+            )
+            .map_err(map_sql_err)?;
+        result.products += tx
+            .execute("DELETE FROM products WHERE id = ?", params![product_id])
+            .map_err(map_sql_err)?;
     }
 
-    let unit_price = payload.unit_price.unwrap_or(default_price);
-    let total_amount = unit_price * payload.qty;
-    let ts = now_iso();
+    tx.commit().map_err(map_sql_err)?;
+    Ok(result)
+}
 
-    tx.execute(
-        "UPDATE products SET qty = qty - ? WHERE id = ?",
-        params![payload.qty, payload.product_id],
-    )
-    .map_err(map_sql_err)?;
+#[derive(Debug, Serialize)]
+struct BulkUpdateResult {
+    updated: usize,
+    data: AppData,
+}
 
-    tx.execute(
-        "INSERT INTO sales (ts, product_id, qty, price_snapshot, total_amount, customer_id, note, is_credit) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
-        params![
-            ts,
-            payload.product_id,
-            payload.qty,
-            unit_price,
-            total_amount,
-            payload.customer_id,
-            payload.note.as_deref(),
-            if payload.is_credit { 1 } else { 0 }
-        ],
-    )
-    .map_err(map_sql_err)?;
+#[tauri::command]
+fn bulk_adjust_prices(
+    state: State<DbState>,
+    percent: f64,
+    product_ids: Option<Vec<i64>>,
+) -> CommandResult<BulkUpdateResult> {
+    if !percent.is_finite() {
+        return Err(AppError::Validation("조정 비율이 올바르지 않습니다.".into()).into());
+    }
+    let factor = 1.0 + percent / 100.0;
+    if factor < 0.0 {
+        return Err(AppError::Validation("조정 후 단가가 0보다 작아집니다.".into()).into());
+    }
 
-    let sale_id = tx.last_insert_rowid();
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
 
-    tx.execute(
-        "INSERT INTO transactions (ts, kind, product_id, qty, unit_price, total_amount, customer_id, note, sale_id) VALUES (?, 'OUT', ?, ?, ?, ?, ?, ?, ?)",
-        params![
-            ts,
-            payload.product_id,
-            payload.qty,
-            unit_price,
-            total_amount,
-            payload.customer_id,
-            payload.note.as_deref(),
-            sale_id
-        ],
-    )
-    .map_err(map_sql_err)?;
+    let mut targets: Vec<(i64, f64)> = Vec::new();
+    {
+        let mut stmt = tx
+            .prepare("SELECT id, unit_price FROM products WHERE archived = 0")
+            .map_err(map_sql_err)?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?)))
+            .map_err(map_sql_err)?;
+        for row in rows {
+            let (id, price) = row.map_err(map_sql_err)?;
+            let selected = match &product_ids {
+                Some(ids) => ids.contains(&id),
+                None => true,
+            };
+            if selected {
+                targets.push((id, price));
+            }
+        }
+    }
 
-    if payload.is_credit {
+    let note = format!("일괄 단가 조정 {percent:+}%");
+    let mut updated = 0;
+    for (id, old_price) in targets {
+        let new_price = (old_price * factor * 100.0).round() / 100.0;
         tx.execute(
-            "INSERT INTO credits (ts, customer_id, sale_id, amount, is_payment, note) VALUES (?, ?, ?, ?, 0, ?)",
-            params![
-                ts,
-                payload.customer_id,
-                sale_id,
-                total_amount,
-                payload.note.as_deref()
-            ],
+            "UPDATE products SET unit_price = ?, version = version + 1 WHERE id = ?",
+            params![new_price, id],
         )
         .map_err(map_sql_err)?;
+        record_price_change(&tx, id, old_price, new_price, Some(&note)).map_err(map_app_err)?;
+        updated += 1;
     }
 
     tx.commit().map_err(map_sql_err)?;
+    let data = load_app_data(&state).map_err(map_app_err)?;
+    Ok(BulkUpdateResult { updated, data })
+}
+
+fn record_price_change(
+    tx: &rusqlite::Transaction<'_>,
+    product_id: i64,
+    old_price: f64,
+    new_price: f64,
+    note: Option<&str>,
+) -> Result<(), AppError> {
+    if (old_price - new_price).abs() < f64::EPSILON {
+        return Ok(());
+    }
+    tx.execute(
+        "INSERT INTO price_history (ts, product_id, old_price, new_price, note) VALUES (?, ?, ?, ?, ?)",
+        params![now_iso(), product_id, old_price, new_price, note],
+    )?;
+    Ok(())
+}
+
+#[tauri::command]
+fn delete_product(state: State<DbState>, product_id: i64) -> CommandResult<AppData> {
+    let conn = state.open().map_err(map_app_err)?;
+    // Soft delete: archive the product so history remains intact
+    conn.execute(
+        "UPDATE products SET archived = 1 WHERE id = ?",
+        params![product_id],
+    )
+    .map_err(map_sql_err)?;
     load_app_data(&state).map_err(Into::into)
 }
 
+// signed effect of one transactions row (aliased t) on products.qty
+// ADJUST rows store a signed delta; to_damaged rows only move damaged_qty
+const TRANSACTION_QTY_DELTA_SQL: &str = "CASE t.kind
+        WHEN 'IN' THEN t.qty
+        WHEN 'OUT' THEN CASE WHEN t.to_damaged = 1 THEN 0 ELSE -t.qty END
+        WHEN 'RETURN' THEN CASE WHEN t.to_damaged = 1 THEN 0 ELSE t.qty END
+        WHEN 'ADJUST' THEN t.qty
+        WHEN 'SUPPLIER_RETURN' THEN -t.qty
+        ELSE 0
+    END";
+
+#[derive(Debug, Serialize)]
+struct ProductStateAt {
+    product_id: i64,
+    product_name: String,
+    at: String,
+    qty: f64,
+    unit_price: f64,
+    // when the price in effect was set; None if it never changed before `at`
+    price_since: Option<String>,
+}
+
+// replays the ledger up to `at` (exclusive); a bare date means the end of that shop day
 #[tauri::command]
-fn update_sale(state: State<DbState>, payload: SaleUpdatePayload) -> CommandResult<AppData> {
-    if payload.qty <= 0.0 {
-        return Err(AppError::Validation("미터은 0보다 커야 합니다.".into()).into());
+fn product_state_at(
+    state: State<DbState>,
+    product_id: i64,
+    at: String,
+) -> CommandResult<ProductStateAt> {
+    let conn = state.open().map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let at = normalize_range_bound(tz, at.trim(), true).map_err(map_app_err)?;
+    let product = fetch_product(&conn, product_id)
+        .map_err(map_app_err)?
+        .ok_or_else(|| AppError::Validation("존재하지 않는 품목입니다.".into()))?;
+
+    // created_at is SQLite's UTC datetime('now') text rather than RFC 3339
+    let created = NaiveDateTime::parse_from_str(&product.created_at, "%Y-%m-%d %H:%M:%S")
+        .map(|created| created.and_utc());
+    let requested = DateTime::parse_from_rfc3339(&at).map(|at| at.with_timezone(&Utc));
+    if let (Ok(created), Ok(requested)) = (created, requested) {
+        if requested <= created {
+            return Err(AppError::Validation(
+                "품목이 등록되기 전 시점은 조회할 수 없습니다.".into(),
+            )
+            .into());
+        }
     }
-    let mut conn = state.open().map_err(map_app_err)?;
-    let tx = conn.transaction().map_err(map_sql_err)?;
-    // Fetch sale
-    let sale_row = tx
+
+    let qty = conn
         .query_row(
-            "SELECT product_id, qty, price_snapshot, is_return FROM sales WHERE id = ?",
-            params![payload.id],
-            |row| {
-                Ok((
-                    row.get::<_, i64>(0)?,
-                    row.get::<_, f64>(1)?,
-                    row.get::<_, f64>(2)?,
-                    row.get::<_, i64>(3)? != 0,
-                ))
-            },
+            &format!(
+                "SELECT IFNULL(SUM({TRANSACTION_QTY_DELTA_SQL}), 0)
+                 FROM transactions t
+                 WHERE t.product_id = ? AND t.ts < ?"
+            ),
+            params![product_id, at],
+            |row| row.get::<_, f64>(0),
+        )
+        .map_err(map_sql_err)?;
+
+    // the last change before `at` set the price; failing that, the first later change says what it replaced
+    let last_change = conn
+        .query_row(
+            "SELECT new_price, ts FROM price_history
+             WHERE product_id = ? AND ts < ?
+             ORDER BY ts DESC, id DESC LIMIT 1",
+            params![product_id, at],
+            |row| Ok((row.get::<_, f64>(0)?, row.get::<_, String>(1)?)),
         )
         .optional()
         .map_err(map_sql_err)?;
-    let (product_id, prev_qty, _prev_price, is_return) = match sale_row {
-        Some(v) => v,
+    let (unit_price, price_since) = match last_change {
+        Some((price, ts)) => (price, Some(ts)),
         None => {
-            return Err(AppError::Validation("존재하지 않는 판매입니다.".into()).into());
+            let next_change = conn
+                .query_row(
+                    "SELECT old_price FROM price_history
+                     WHERE product_id = ? AND ts >= ?
+                     ORDER BY ts, id LIMIT 1",
+                    params![product_id, at],
+                    |row| row.get::<_, f64>(0),
+                )
+                .optional()
+                .map_err(map_sql_err)?;
+            (next_change.unwrap_or(product.unit_price), None)
         }
     };
-    if is_return {
-        return Err(AppError::Validation("반품 내역은 수정할 수 없습니다.".into()).into());
+
+    Ok(ProductStateAt {
+        product_id,
+        product_name: product.name,
+        at,
+        qty,
+        unit_price,
+        price_since,
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct QuantityDiscrepancy {
+    product_id: i64,
+    product_name: String,
+    stored_qty: f64,
+    computed_qty: f64,
+    difference: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct QuantityReconciliation {
+    discrepancies: Vec<QuantityDiscrepancy>,
+    fixed: bool,
+}
+
+#[tauri::command]
+fn recompute_quantities(state: State<DbState>, fix: bool) -> CommandResult<QuantityReconciliation> {
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+
+    let discrepancies = find_quantity_discrepancies(&tx).map_err(map_app_err)?;
+    if fix {
+        for item in &discrepancies {
+            tx.execute(
+                "UPDATE products SET qty = ? WHERE id = ?",
+                params![item.computed_qty, item.product_id],
+            )
+            .map_err(map_sql_err)?;
+        }
     }
-    // Block if returns exist
-    let has_return = tx
-        .query_row(
-            "SELECT EXISTS(SELECT 1 FROM sales WHERE origin_sale_id = ? AND is_return = 1)",
-            params![payload.id],
-            |row| row.get::<_, i64>(0),
-        )
-        .map_err(map_sql_err)?
+
+    tx.commit().map_err(map_sql_err)?;
+    Ok(QuantityReconciliation {
+        fixed: fix && !discrepancies.is_empty(),
+        discrepancies,
+    })
+}
+
+// single-product counterpart of recompute_quantities; returned even when the two agree
+#[tauri::command]
+fn product_computed_qty(
+    state: State<DbState>,
+    product_id: i64,
+) -> CommandResult<QuantityDiscrepancy> {
+    let conn = state.open().map_err(map_app_err)?;
+    conn.query_row(
+        &format!(
+            "SELECT p.id, p.name, p.qty, IFNULL(SUM({TRANSACTION_QTY_DELTA_SQL}), 0)
+             FROM products p
+             LEFT JOIN transactions t ON t.product_id = p.id
+             WHERE p.id = ?
+             GROUP BY p.id, p.name, p.qty"
+        ),
+        params![product_id],
+        |row| {
+            let stored_qty: f64 = row.get(2)?;
+            let computed_qty: f64 = row.get(3)?;
+            Ok(QuantityDiscrepancy {
+                product_id: row.get(0)?,
+                product_name: row.get(1)?,
+                stored_qty,
+                computed_qty,
+                difference: stored_qty - computed_qty,
+            })
+        },
+    )
+    .optional()
+    .map_err(map_sql_err)?
+    .ok_or_else(|| AppError::Validation("존재하지 않는 품목입니다.".into()).into())
+}
+
+#[derive(Debug, Serialize)]
+struct OversoldEvent {
+    movement: StockMovement,
+    // stock on hand right after the movement
+    running_qty: f64,
+}
+
+// replays each product's ledger in order and reports every movement that took stock from
+// zero or above to below zero; later movements while still negative are not repeated
+#[tauri::command]
+fn oversold_events(state: State<DbState>) -> CommandResult<Vec<OversoldEvent>> {
+    let conn = state.open().map_err(map_app_err)?;
+    let sql = format!(
+        "SELECT t.id, t.product_id, {TRANSACTION_QTY_DELTA_SQL}
+         FROM transactions t
+         ORDER BY t.product_id, t.ts, t.id"
+    );
+    let mut stmt = conn.prepare(&sql).map_err(map_sql_err)?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, f64>(2)?,
+            ))
+        })
+        .map_err(map_sql_err)?;
+
+    let mut flagged = Vec::new();
+    let mut current_product = None;
+    let mut running = 0.0;
+    for row in rows {
+        let (movement_id, product_id, delta) = row.map_err(map_sql_err)?;
+        if current_product != Some(product_id) {
+            current_product = Some(product_id);
+            running = 0.0;
+        }
+        let before = running;
+        running += delta;
+        if before >= -1e-9 && running < -1e-9 {
+            flagged.push((movement_id, running));
+        }
+    }
+
+    let sql = format!("{MOVEMENT_SELECT} WHERE t.id = ?");
+    let mut stmt = conn.prepare(&sql).map_err(map_sql_err)?;
+    let mut events = Vec::with_capacity(flagged.len());
+    for (movement_id, running_qty) in flagged {
+        let movement = stmt
+            .query_row(params![movement_id], movement_from_row)
+            .map_err(map_sql_err)?;
+        events.push(OversoldEvent {
+            movement,
+            running_qty,
+        });
+    }
+    Ok(events)
+}
+
+fn find_quantity_discrepancies(conn: &Connection) -> Result<Vec<QuantityDiscrepancy>, AppError> {
+    let sql = format!(
+        "SELECT p.id, p.name, p.qty, IFNULL(SUM({TRANSACTION_QTY_DELTA_SQL}), 0)
+         FROM products p
+         LEFT JOIN transactions t ON t.product_id = p.id
+         WHERE p.archived = 0
+         GROUP BY p.id, p.name, p.qty
+         ORDER BY p.name COLLATE NOCASE"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], |row| {
+        let stored_qty: f64 = row.get(2)?;
+        let computed_qty: f64 = row.get(3)?;
+        Ok(QuantityDiscrepancy {
+            product_id: row.get(0)?,
+            product_name: row.get(1)?,
+            stored_qty,
+            computed_qty,
+            difference: stored_qty - computed_qty,
+        })
+    })?;
+
+    let mut discrepancies = Vec::new();
+    for row in rows {
+        let item = row?;
+        if item.difference.abs() > 1e-9 {
+            discrepancies.push(item);
+        }
+    }
+    Ok(discrepancies)
+}
+
+#[derive(Debug, Deserialize)]
+struct CustomerForm {
+    name: String,
+    phone: String,
+    note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CustomerUpdateForm {
+    id: i64,
+    name: String,
+    phone: String,
+    note: Option<String>,
+    version: i64,
+}
+
+// returns the normalized phone once every field checks out
+fn validate_customer_fields(
+    name: &str,
+    phone: &str,
+    note: Option<&str>,
+) -> Result<String, AppError> {
+    let mut errors = Vec::new();
+    if name.trim().is_empty() {
+        errors.push(field_error("name", "고객 이름을 입력해주세요."));
+    }
+    check_text_len(&mut errors, "name", Some(name.trim()), MAX_NAME_LEN);
+    check_text_len(&mut errors, "note", note, MAX_NOTE_LEN);
+    let phone_normalized = match normalize_phone(phone) {
+        Ok(normalized) => Some(normalized),
+        Err(AppError::Validation(message)) => {
+            errors.push(field_error("phone", message));
+            None
+        }
+        Err(err) => return Err(err),
+    };
+    check_fields(errors)?;
+    Ok(phone_normalized.unwrap_or_default())
+}
+
+#[tauri::command]
+fn create_customer(state: State<DbState>, payload: CustomerForm) -> CommandResult<AppData> {
+    let note = clean_text(payload.note.as_deref());
+    let phone = payload.phone.trim();
+    let phone_normalized =
+        validate_customer_fields(&payload.name, phone, note).map_err(map_app_err)?;
+    let conn = state.open().map_err(map_app_err)?;
+    conn.execute(
+        "INSERT INTO customers (name, phone, phone_normalized, note) VALUES (?, ?, ?, ?)",
+        params![payload.name.trim(), phone, phone_normalized, note],
+    )
+    .map_err(map_unique_err)?;
+
+    load_app_data(&state).map_err(Into::into)
+}
+
+#[tauri::command]
+fn update_customer(state: State<DbState>, payload: CustomerUpdateForm) -> CommandResult<AppData> {
+    let note = clean_text(payload.note.as_deref());
+    let phone = payload.phone.trim();
+    let phone_normalized =
+        validate_customer_fields(&payload.name, phone, note).map_err(map_app_err)?;
+    let conn = state.open().map_err(map_app_err)?;
+    let updated = conn
+        .execute(
+            "UPDATE customers SET name = ?, phone = ?, phone_normalized = ?, note = ?, version = version + 1 WHERE id = ? AND version = ?",
+            params![
+                payload.name.trim(),
+                phone,
+                phone_normalized,
+                note,
+                payload.id,
+                payload.version
+            ],
+        )
+        .map_err(map_unique_err)?;
+    if updated == 0 {
+        return Err(map_app_err(stale_record_error()));
+    }
+
+    load_app_data(&state).map_err(Into::into)
+}
+
+#[derive(Debug, Serialize)]
+struct ImportLineError {
+    line: usize,
+    errors: Vec<FieldError>,
+}
+
+#[derive(Debug, Serialize)]
+struct CustomerImportResult {
+    imported: usize,
+    errors: Vec<ImportLineError>,
+    data: AppData,
+}
+
+// column order import_customers_csv reads; import_template writes the same header
+const CUSTOMER_IMPORT_HEADER: [&str; 3] = ["이름", "연락처", "메모"];
+const CUSTOMER_IMPORT_EXAMPLE: [&str; 3] = ["홍길동", "010-1234-5678", "단골"];
+
+// header plus one example row, so the file opens in Excel with the expected columns
+#[tauri::command]
+fn import_template(kind: String) -> CommandResult<String> {
+    let (header, example) = match kind.trim() {
+        "customers" => (CUSTOMER_IMPORT_HEADER, CUSTOMER_IMPORT_EXAMPLE),
+        other => {
+            return Err(
+                AppError::Validation(format!("알 수 없는 가져오기 형식입니다: {other}")).into(),
+            );
+        }
+    };
+    let mut content = String::from(CSV_BOM);
+    push_csv_row(&mut content, &header);
+    push_csv_row(&mut content, &example);
+    Ok(content)
+}
+
+// rows are name,phone,note with an optional header row; valid rows are saved even when others fail,
+// and a phone already on file or repeated earlier in the file is reported as a duplicate
+#[tauri::command]
+fn import_customers_csv(
+    state: State<DbState>,
+    content: String,
+) -> CommandResult<CustomerImportResult> {
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+
+    let mut imported = 0;
+    let mut errors = Vec::new();
+    let mut seen_phones = HashSet::new();
+    for (index, (line, fields)) in parse_csv(&content).into_iter().enumerate() {
+        let field = |i: usize| fields.get(i).map(|value| value.trim()).unwrap_or("");
+        let (name, phone, note) = (field(0), field(1), field(2));
+        if fields.iter().all(|value| value.trim().is_empty()) {
+            continue;
+        }
+        let label = name.to_lowercase();
+        let is_header =
+            label == CUSTOMER_IMPORT_HEADER[0] || matches!(label.as_str(), "name" | "고객명");
+        if index == 0 && is_header {
+            continue;
+        }
+        let note = Some(note).filter(|note| !note.is_empty());
+
+        let phone_normalized = match validate_customer_fields(name, phone, note) {
+            Ok(normalized) => normalized,
+            Err(AppError::Fields(line_errors)) => {
+                errors.push(ImportLineError {
+                    line,
+                    errors: line_errors,
+                });
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let on_file = tx
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM customers WHERE phone_normalized = ?)",
+                params![phone_normalized],
+                |row| row.get::<_, i64>(0),
+            )
+            .map_err(map_sql_err)?
+            != 0;
+        if on_file || !seen_phones.insert(phone_normalized.clone()) {
+            errors.push(ImportLineError {
+                line,
+                errors: vec![field_error("phone", "이미 같은 연락처의 고객이 있습니다.")],
+            });
+            continue;
+        }
+
+        tx.execute(
+            "INSERT INTO customers (name, phone, phone_normalized, note) VALUES (?, ?, ?, ?)",
+            params![name, phone, phone_normalized, note],
+        )
+        .map_err(map_unique_err)?;
+        imported += 1;
+    }
+
+    tx.commit().map_err(map_sql_err)?;
+    Ok(CustomerImportResult {
+        imported,
+        errors,
+        data: load_app_data(&state).map_err(map_app_err)?,
+    })
+}
+
+#[tauri::command]
+fn delete_customer(state: State<DbState>, customer_id: i64) -> CommandResult<AppData> {
+    let conn = state.open().map_err(map_app_err)?;
+    // mark related sales as deleted-customer before FK nullify kicks in
+    conn.execute(
+        "UPDATE sales SET customer_deleted = 1 WHERE customer_id = ?",
+        params![customer_id],
+    )
+    .map_err(map_sql_err)?;
+    conn.execute("DELETE FROM customers WHERE id = ?", params![customer_id])
+        .map_err(|err| match err {
+            rusqlite::Error::SqliteFailure(_, _) => {
+                AppError::Validation("해당 고객은 거래 내역이 있어 삭제할 수 없습니다.".into())
+                    .to_string()
+            }
+            other => other.to_string(),
+        })?;
+
+    load_app_data(&state).map_err(Into::into)
+}
+
+// folds a duplicate customer into the one being kept: every row pointing at the source is
+// repointed, so the balance triggers carry its outstanding amount over, then the source is deleted
+#[tauri::command]
+fn merge_customers(
+    state: State<DbState>,
+    source_id: i64,
+    target_id: i64,
+) -> CommandResult<AppData> {
+    if source_id == target_id {
+        return Err(AppError::Validation("같은 고객끼리는 병합할 수 없습니다.".into()).into());
+    }
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    for customer_id in [source_id, target_id] {
+        let exists = tx
+            .query_row(
+                "SELECT 1 FROM customers WHERE id = ?",
+                params![customer_id],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(map_sql_err)?;
+        if exists.is_none() {
+            return Err(AppError::Validation("존재하지 않는 고객입니다.".into()).into());
+        }
+    }
+
+    // transfers between the two would become self-transfers; both legs land on the target and
+    // cancel out, so they are dropped along with their credit rows
+    for sql in [
+        "DELETE FROM credits WHERE transfer_id IN (
+            SELECT id FROM balance_transfers
+            WHERE (from_customer_id = ?1 AND to_customer_id = ?2)
+               OR (from_customer_id = ?2 AND to_customer_id = ?1)
+        )",
+        "DELETE FROM balance_transfers
+         WHERE (from_customer_id = ?1 AND to_customer_id = ?2)
+            OR (from_customer_id = ?2 AND to_customer_id = ?1)",
+        "UPDATE sales SET customer_id = ?1 WHERE customer_id = ?2",
+        "UPDATE transactions SET customer_id = ?1 WHERE customer_id = ?2",
+        "UPDATE credits SET customer_id = ?1 WHERE customer_id = ?2",
+        "UPDATE invoices SET customer_id = ?1 WHERE customer_id = ?2",
+        "UPDATE balance_transfers SET from_customer_id = ?1 WHERE from_customer_id = ?2",
+        "UPDATE balance_transfers SET to_customer_id = ?1 WHERE to_customer_id = ?2",
+    ] {
+        tx.execute(sql, params![target_id, source_id])
+            .map_err(map_sql_err)?;
+    }
+    tx.execute(
+        &format!("{SALE_CUSTOMER_SNAPSHOT_SQL} WHERE customer_id = ?"),
+        params![target_id],
+    )
+    .map_err(map_sql_err)?;
+    // a hold on either record stays on the merged one
+    tx.execute(
+        "UPDATE customers SET
+            on_hold = MAX(on_hold, (SELECT on_hold FROM customers WHERE id = ?2)),
+            hold_reason = CASE
+                WHEN on_hold = 0 AND (SELECT on_hold FROM customers WHERE id = ?2) = 1
+                THEN (SELECT hold_reason FROM customers WHERE id = ?2)
+                ELSE hold_reason
+            END,
+            version = version + 1
+         WHERE id = ?1",
+        params![target_id, source_id],
+    )
+    .map_err(map_sql_err)?;
+    tx.execute("DELETE FROM customers WHERE id = ?", params![source_id])
+        .map_err(map_sql_err)?;
+
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+// a customer on hold can still buy for cash and pay down what they owe
+#[tauri::command]
+fn set_customer_hold(
+    state: State<DbState>,
+    customer_id: i64,
+    on_hold: bool,
+    reason: Option<String>,
+) -> CommandResult<AppData> {
+    let reason = clean_text(reason.as_deref()).filter(|_| on_hold);
+    ensure_text_len("reason", reason, MAX_NOTE_LEN).map_err(map_app_err)?;
+    let conn = state.open().map_err(map_app_err)?;
+    let updated = conn
+        .execute(
+            "UPDATE customers SET on_hold = ?, hold_reason = ?, version = version + 1 WHERE id = ?",
+            params![if on_hold { 1 } else { 0 }, reason, customer_id],
+        )
+        .map_err(map_sql_err)?;
+    if updated == 0 {
+        return Err(AppError::Validation("존재하지 않는 고객입니다.".into()).into());
+    }
+    load_app_data(&state).map_err(Into::into)
+}
+
+fn ensure_credit_allowed(conn: &Connection, customer_id: i64) -> Result<(), AppError> {
+    let hold = conn
+        .query_row(
+            "SELECT hold_reason FROM customers WHERE id = ? AND on_hold = 1",
+            params![customer_id],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .optional()?;
+    match hold {
+        None => Ok(()),
+        Some(Some(reason)) => Err(AppError::Validation(format!(
+            "외상 거래가 중지된 고객입니다: {reason}"
+        ))),
+        Some(None) => Err(AppError::Validation(
+            "외상 거래가 중지된 고객입니다.".into(),
+        )),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SupplierForm {
+    name: String,
+    phone: Option<String>,
+    note: Option<String>,
+}
+
+#[tauri::command]
+fn create_supplier(state: State<DbState>, payload: SupplierForm) -> CommandResult<AppData> {
+    if payload.name.trim().is_empty() {
+        return Err(AppError::Validation("거래처 이름을 입력해주세요.".into()).into());
+    }
+    let conn = state.open().map_err(map_app_err)?;
+    conn.execute(
+        "INSERT INTO suppliers (name, phone, note) VALUES (?, ?, ?)",
+        params![
+            payload.name.trim(),
+            payload.phone.as_deref().map(str::trim),
+            payload.note.as_deref()
+        ],
+    )
+    .map_err(map_sql_err)?;
+
+    load_app_data(&state).map_err(Into::into)
+}
+
+fn supplier_name(conn: &Connection, supplier_id: i64) -> Result<String, AppError> {
+    conn.query_row(
+        "SELECT name FROM suppliers WHERE id = ?",
+        params![supplier_id],
+        |row| row.get::<_, String>(0),
+    )
+    .optional()?
+    .ok_or_else(|| AppError::Validation("존재하지 않는 거래처입니다.".into()))
+}
+
+#[derive(Debug, Deserialize)]
+struct StockEntryPayload {
+    product_id: i64,
+    qty: f64,
+    kind: Option<TransactionKind>,
+    unit_price: Option<f64>,
+    counterparty: Option<String>,
+    customer_id: Option<i64>,
+    note: Option<String>,
+    lot_code: Option<String>,
+    expiry_date: Option<String>,
+}
+
+#[tauri::command]
+fn record_stock_entry(state: State<DbState>, payload: StockEntryPayload) -> CommandResult<AppData> {
+    let counterparty = clean_text(payload.counterparty.as_deref());
+    let note = clean_text(payload.note.as_deref());
+    if payload.qty <= 0.0 {
+        return Err(AppError::Validation("미터은 0보다 커야 합니다.".into()).into());
+    }
+    ensure_text_len("counterparty", counterparty, MAX_NAME_LEN).map_err(map_app_err)?;
+    ensure_text_len("note", note, MAX_NOTE_LEN).map_err(map_app_err)?;
+    let kind = payload.kind.unwrap_or(TransactionKind::In);
+    let (lot_code, expiry_date) = stock_entry_lot(&payload).map_err(map_app_err)?;
+    if (lot_code.is_some() || expiry_date.is_some()) && !matches!(kind, TransactionKind::In) {
+        return Err(
+            AppError::Validation("로트 정보는 입고에만 입력할 수 있습니다.".into()).into(),
+        );
+    }
+
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+
+    let product = tx
+        .query_row(
+            "SELECT qty, unit_price FROM products WHERE id = ?",
+            params![payload.product_id],
+            |row| Ok((row.get::<_, f64>(0)?, row.get::<_, f64>(1)?)),
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+
+    let (current_qty, default_price) = product
+        .ok_or_else(|| AppError::Validation("존재하지 않는 품명입니다.".into()).to_string())?;
+    ensure_qty_step(&tx, payload.product_id, payload.qty).map_err(map_app_err)?;
+
+    let qty_delta = match kind {
+        TransactionKind::In => payload.qty,
+        TransactionKind::Out => -payload.qty,
+        TransactionKind::Return => {
+            return Err(
+                AppError::Validation("반품 입력은 반품 등록 기능을 사용해주세요.".into()).into(),
+            );
+        }
+        TransactionKind::Adjust => {
+            return Err(
+                AppError::Validation("재고 조정은 재고 실사 기능을 사용해주세요.".into()).into(),
+            );
+        }
+        TransactionKind::SupplierReturn => {
+            return Err(AppError::Validation(
+                "거래처 반품은 거래처 반품 등록 기능을 사용해주세요.".into(),
+            )
+            .into());
+        }
+    };
+    if qty_delta < 0.0 {
+        ensure_stock_available(&tx, payload.product_id, current_qty, -qty_delta)
+            .map_err(map_app_err)?;
+    }
+    tx.execute(
+        "UPDATE products SET qty = qty + ? WHERE id = ?",
+        params![qty_delta, payload.product_id],
+    )
+    .map_err(map_sql_err)?;
+    if qty_delta < 0.0 {
+        consume_lots(&tx, payload.product_id, -qty_delta).map_err(map_app_err)?;
+    }
+
+    let ts = now_iso();
+    let unit_price = payload.unit_price.or(Some(default_price));
+    let decimals = amount_decimals(&tx).map_err(map_app_err)?;
+    let total_amount = unit_price.map(|price| round_amount(price * payload.qty, decimals));
+
+    tx.execute(
+        "INSERT INTO transactions (ts, kind, product_id, qty, unit_price, total_amount, counterparty, customer_id, note) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            ts,
+            kind.as_str(),
+            payload.product_id,
+            payload.qty,
+            unit_price,
+            total_amount,
+            counterparty,
+            payload.customer_id,
+            note
+        ],
+    )
+    .map_err(map_sql_err)?;
+
+    if qty_delta > 0.0 && (lot_code.is_some() || expiry_date.is_some()) {
+        let transaction_id = tx.last_insert_rowid();
+        insert_lot(
+            &tx,
+            payload.product_id,
+            payload.qty,
+            (lot_code, expiry_date),
+            &ts,
+            transaction_id,
+        )
+        .map_err(map_sql_err)?;
+    }
+
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+// trimmed lot code and validated expiry date of an entry; blanks count as absent
+fn stock_entry_lot(payload: &StockEntryPayload) -> Result<(Option<&str>, Option<&str>), AppError> {
+    let lot_code = payload
+        .lot_code
+        .as_deref()
+        .map(str::trim)
+        .filter(|code| !code.is_empty());
+    let expiry_date = payload
+        .expiry_date
+        .as_deref()
+        .map(str::trim)
+        .filter(|date| !date.is_empty());
+    if let Some(date) = expiry_date {
+        if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+            return Err(AppError::Validation(
+                "유통기한은 YYYY-MM-DD 형식으로 입력해주세요.".into(),
+            ));
+        }
+    }
+    Ok((lot_code, expiry_date))
+}
+
+fn insert_lot(
+    conn: &Connection,
+    product_id: i64,
+    qty: f64,
+    (lot_code, expiry_date): (Option<&str>, Option<&str>),
+    ts: &str,
+    transaction_id: i64,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO product_lots (product_id, lot_code, qty, received_qty, expiry_date, received_at, transaction_id) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        params![product_id, lot_code, qty, qty, expiry_date, ts, transaction_id],
+    )?;
+    Ok(())
+}
+
+// products with a qty_step only move in whole multiples of it, e.g. 1 for goods sold by the piece
+fn ensure_qty_step(conn: &Connection, product_id: i64, qty: f64) -> Result<(), AppError> {
+    let step = conn
+        .query_row(
+            "SELECT qty_step FROM products WHERE id = ?",
+            params![product_id],
+            |row| row.get::<_, Option<f64>>(0),
+        )
+        .optional()?
+        .flatten();
+    if let Some(step) = step.filter(|step| *step > 0.0) {
+        let units = qty / step;
+        if (units - units.round()).abs() > 1e-6 {
+            return Err(AppError::Validation(format!(
+                "수량은 {step} 단위로 입력해주세요."
+            )));
+        }
+    }
+    Ok(())
+}
+
+// products flagged allow_negative_stock may be oversold (backordered); everything else stays strict
+fn ensure_stock_available(
+    conn: &Connection,
+    product_id: i64,
+    available: f64,
+    requested: f64,
+) -> Result<(), AppError> {
+    if available >= requested {
+        return Ok(());
+    }
+    let allow_negative = conn
+        .query_row(
+            "SELECT allow_negative_stock FROM products WHERE id = ?",
+            params![product_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+        .unwrap_or(0)
+        != 0;
+    if allow_negative {
+        Ok(())
+    } else {
+        Err(AppError::Validation("재고가 부족합니다.".into()))
+    }
+}
+
+// lots are drawn down earliest expiry first; stock that was never received into a lot is untracked
+const LOT_FIFO_ORDER: &str =
+    "ORDER BY expiry_date IS NULL, expiry_date ASC, received_at ASC, id ASC";
+
+fn consume_lots(conn: &Connection, product_id: i64, qty: f64) -> Result<(), AppError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, qty FROM product_lots WHERE product_id = ? AND qty > 0 {LOT_FIFO_ORDER}"
+    ))?;
+    let lots = stmt
+        .query_map(params![product_id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+
+    let mut remaining = qty;
+    for (lot_id, lot_qty) in lots {
+        if remaining <= 0.0 {
+            break;
+        }
+        let portion = remaining.min(lot_qty);
+        conn.execute(
+            "UPDATE product_lots SET qty = qty - ? WHERE id = ?",
+            params![portion, lot_id],
+        )?;
+        remaining -= portion;
+    }
+    Ok(())
+}
+
+// returned stock refills drawn-down lots in the same order they were consumed
+fn restore_lots(conn: &Connection, product_id: i64, qty: f64) -> Result<(), AppError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, received_qty - qty FROM product_lots WHERE product_id = ? AND qty < received_qty {LOT_FIFO_ORDER}"
+    ))?;
+    let lots = stmt
+        .query_map(params![product_id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(stmt);
+
+    let mut remaining = qty;
+    for (lot_id, room) in lots {
+        if remaining <= 0.0 {
+            break;
+        }
+        let portion = remaining.min(room);
+        conn.execute(
+            "UPDATE product_lots SET qty = qty + ? WHERE id = ?",
+            params![portion, lot_id],
+        )?;
+        remaining -= portion;
+    }
+    Ok(())
+}
+
+// follows a signed change of sellable qty: negative draws lots down, positive puts back
+fn shift_lots(conn: &Connection, product_id: i64, qty_delta: f64) -> Result<(), AppError> {
+    if qty_delta < 0.0 {
+        consume_lots(conn, product_id, -qty_delta)
+    } else if qty_delta > 0.0 {
+        restore_lots(conn, product_id, qty_delta)
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ProductLot {
+    id: i64,
+    product_id: i64,
+    product_name: String,
+    lot_code: Option<String>,
+    qty: f64,
+    expiry_date: Option<String>,
+    received_at: String,
+}
+
+#[tauri::command]
+fn expiring_lots(state: State<DbState>, within_days: i64) -> CommandResult<Vec<ProductLot>> {
+    if within_days < 0 {
+        return Err(AppError::Validation("조회 기간은 0일 이상이어야 합니다.".into()).into());
+    }
+    let conn = state.open().map_err(map_app_err)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT l.id, l.product_id, p.name, l.lot_code, l.qty, l.expiry_date, l.received_at
+             FROM product_lots l
+             JOIN products p ON p.id = l.product_id
+             WHERE l.qty > 0
+               AND l.expiry_date IS NOT NULL
+               AND l.expiry_date <= date('now', 'localtime', printf('+%d days', ?))
+             ORDER BY l.expiry_date ASC, p.name COLLATE NOCASE",
+        )
+        .map_err(map_sql_err)?;
+    let rows = stmt
+        .query_map(params![within_days], |row| {
+            Ok(ProductLot {
+                id: row.get(0)?,
+                product_id: row.get(1)?,
+                product_name: row.get(2)?,
+                lot_code: row.get(3)?,
+                qty: row.get(4)?,
+                expiry_date: row.get(5)?,
+                received_at: row.get(6)?,
+            })
+        })
+        .map_err(map_sql_err)?;
+
+    let mut lots = Vec::new();
+    for row in rows {
+        lots.push(row.map_err(map_sql_err)?);
+    }
+    Ok(lots)
+}
+
+#[tauri::command]
+fn reverse_stock_entry(state: State<DbState>, transaction_id: i64) -> CommandResult<AppData> {
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+
+    let row = tx
+        .query_row(
+            "SELECT kind, product_id, qty, unit_price, total_amount, counterparty, customer_id, sale_id, reverses_id, to_damaged,
+                    conversion_of IS NOT NULL
+                    OR EXISTS(SELECT 1 FROM transactions c WHERE c.conversion_of = transactions.id)
+             FROM transactions WHERE id = ?",
+            params![transaction_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, Option<f64>>(3)?,
+                    row.get::<_, Option<f64>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                    row.get::<_, Option<i64>>(6)?,
+                    row.get::<_, Option<i64>>(7)?,
+                    row.get::<_, Option<i64>>(8)?,
+                    row.get::<_, i64>(9)? != 0,
+                    row.get::<_, i64>(10)? != 0,
+                ))
+            },
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+    let (
+        kind,
+        product_id,
+        qty,
+        unit_price,
+        total_amount,
+        counterparty,
+        customer_id,
+        sale_id,
+        reverses_id,
+        to_damaged,
+        is_conversion,
+    ) = match row {
+        Some(v) => v,
+        None => return Err(AppError::Validation("존재하지 않는 입출고 기록입니다.".into()).into()),
+    };
+    // sale-linked OUT/RETURN rows must be corrected through the sale/return commands
+    if sale_id.is_some() {
+        return Err(AppError::Validation(
+            "판매·반품에 연결된 기록은 판매 삭제 또는 반품 수정으로 처리해주세요.".into(),
+        )
+        .into());
+    }
+    if reverses_id.is_some() {
+        return Err(AppError::Validation("취소 기록은 다시 취소할 수 없습니다.".into()).into());
+    }
+    // a damaged write-off never came out of sellable stock, so there is nothing to put back
+    if to_damaged {
+        return Err(
+            AppError::Validation("불량 재고 폐기 기록은 취소할 수 없습니다.".into()).into(),
+        );
+    }
+    // the two legs of a conversion move together; reversing one would leave the other dangling
+    if is_conversion {
+        return Err(
+            AppError::Validation("재고 전환 기록은 개별 취소할 수 없습니다.".into()).into(),
+        );
+    }
+    let already_reversed = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM transactions WHERE reverses_id = ?)",
+            params![transaction_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(map_sql_err)?
+        != 0;
+    if already_reversed {
+        return Err(AppError::Validation("이미 취소된 기록입니다.".into()).into());
+    }
+
+    let (reverse_kind, qty_delta) = match TransactionKind::from_db(&kind) {
+        Some(TransactionKind::In) => (TransactionKind::Out, -qty),
+        Some(TransactionKind::Out) => (TransactionKind::In, qty),
+        _ => {
+            return Err(AppError::Validation("취소할 수 없는 기록입니다.".into()).into());
+        }
+    };
+
+    let current_qty: f64 = tx
+        .query_row(
+            "SELECT qty FROM products WHERE id = ?",
+            params![product_id],
+            |row| row.get(0),
+        )
+        .map_err(map_sql_err)?;
+    if current_qty + qty_delta < 0.0 {
+        return Err(AppError::Validation("재고가 부족하여 취소할 수 없습니다.".into()).into());
+    }
+    tx.execute(
+        "UPDATE products SET qty = qty + ? WHERE id = ?",
+        params![qty_delta, product_id],
+    )
+    .map_err(map_sql_err)?;
+    // a cancelled receipt takes back what is left of its own lot before touching older ones
+    let own_lot = if qty_delta < 0.0 {
+        tx.query_row(
+            "SELECT id, qty FROM product_lots WHERE transaction_id = ?",
+            params![transaction_id],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?)),
+        )
+        .optional()
+        .map_err(map_sql_err)?
+    } else {
+        None
+    };
+    let mut lot_delta = qty_delta;
+    if let Some((lot_id, lot_qty)) = own_lot {
+        let portion = lot_qty.min(-qty_delta);
+        tx.execute(
+            "UPDATE product_lots SET qty = qty - ? WHERE id = ?",
+            params![portion, lot_id],
+        )
+        .map_err(map_sql_err)?;
+        lot_delta += portion;
+    }
+    shift_lots(&tx, product_id, lot_delta).map_err(map_app_err)?;
+
+    tx.execute(
+        "INSERT INTO transactions (ts, kind, product_id, qty, unit_price, total_amount, counterparty, customer_id, note, reverses_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            now_iso(),
+            reverse_kind.as_str(),
+            product_id,
+            qty,
+            unit_price,
+            total_amount,
+            counterparty,
+            customer_id,
+            Some(format!("#{transaction_id} 입출고 취소")),
+            transaction_id
+        ],
+    )
+    .map_err(map_sql_err)?;
+
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+// books an opposite ADJUST that points back through reverses_id; only the product's latest
+// movement can be undone, otherwise the later movements would have been based on the wrong count
+#[tauri::command]
+fn undo_stocktake(state: State<DbState>, transaction_id: i64) -> CommandResult<AppData> {
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+
+    let row = tx
+        .query_row(
+            "SELECT is_stocktake, product_id, qty, unit_price, total_amount, reverses_id
+             FROM transactions WHERE id = ?",
+            params![transaction_id],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)? != 0,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, Option<f64>>(3)?,
+                    row.get::<_, Option<f64>>(4)?,
+                    row.get::<_, Option<i64>>(5)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+    let (is_stocktake, product_id, delta, unit_price, total_amount, reverses_id) = match row {
+        Some(v) => v,
+        None => return Err(AppError::Validation("존재하지 않는 입출고 기록입니다.".into()).into()),
+    };
+    if !is_stocktake {
+        return Err(AppError::Validation("재고 실사 기록만 되돌릴 수 있습니다.".into()).into());
+    }
+    if reverses_id.is_some() {
+        return Err(AppError::Validation("취소 기록은 다시 취소할 수 없습니다.".into()).into());
+    }
+    let already_reversed = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM transactions WHERE reverses_id = ?)",
+            params![transaction_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(map_sql_err)?
+        != 0;
+    if already_reversed {
+        return Err(AppError::Validation("이미 취소된 기록입니다.".into()).into());
+    }
+    let has_later_movements = tx
+        .query_row(
+            "SELECT EXISTS(
+                SELECT 1 FROM transactions
+                WHERE product_id = ?1 AND id > ?2
+            )",
+            params![product_id, transaction_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(map_sql_err)?
+        != 0;
+    if has_later_movements {
+        return Err(AppError::Validation(
+            "이후 입출고가 있어 재고 실사를 되돌릴 수 없습니다.".into(),
+        )
+        .into());
+    }
+
+    tx.execute(
+        "UPDATE products SET qty = qty - ? WHERE id = ?",
+        params![delta, product_id],
+    )
+    .map_err(map_sql_err)?;
+    shift_lots(&tx, product_id, -delta).map_err(map_app_err)?;
+    tx.execute(
+        "INSERT INTO transactions (ts, kind, product_id, qty, unit_price, total_amount, note, reverses_id) VALUES (?, 'ADJUST', ?, ?, ?, ?, ?, ?)",
+        params![
+            now_iso(),
+            product_id,
+            -delta,
+            unit_price,
+            total_amount.map(|amount| -amount),
+            Some(format!("#{transaction_id} 재고 실사 취소")),
+            transaction_id
+        ],
+    )
+    .map_err(map_sql_err)?;
+
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+const DEFAULT_REORDER_LOOKBACK_DAYS: i64 = 30;
+const DEFAULT_REORDER_COVER_DAYS: i64 = 14;
+
+#[derive(Debug, Serialize)]
+struct StockoutForecast {
+    product_id: i64,
+    product_name: String,
+    qty: f64,
+    window_days: i64,
+    average_daily_sales: f64,
+    // None when nothing sold in the window, so no stockout is in sight
+    days_left: Option<f64>,
+    stockout_date: Option<String>,
+}
+
+// single-product counterpart of reorder_suggestions, using the same net sales rate
+#[tauri::command]
+fn stockout_forecast(
+    state: State<DbState>,
+    product_id: i64,
+    window_days: Option<i64>,
+) -> CommandResult<StockoutForecast> {
+    let window_days = window_days.unwrap_or(DEFAULT_REORDER_LOOKBACK_DAYS).max(1);
+    let since = chrono::TimeDelta::try_days(window_days)
+        .and_then(|span| Utc::now().checked_sub_signed(span))
+        .ok_or_else(|| AppError::Validation("조회 기간이 올바르지 않습니다.".into()))
+        .map_err(map_app_err)?
+        .to_rfc3339();
+
+    let conn = state.open().map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let (product_name, qty, sold) = conn
+        .query_row(
+            "SELECT p.name, p.qty,
+                    IFNULL(SUM(CASE WHEN s.is_return = 1 THEN -s.qty ELSE s.qty END), 0)
+             FROM products p
+             LEFT JOIN sales s ON s.product_id = p.id AND s.ts >= ?
+             WHERE p.id = ?
+             GROUP BY p.id",
+            params![since, product_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, f64>(2)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(map_sql_err)?
+        .ok_or_else(|| AppError::Validation("존재하지 않는 품목입니다.".into()))
+        .map_err(map_app_err)?;
+
+    let average_daily_sales = sold.max(0.0) / window_days as f64;
+    let days_left = (average_daily_sales > 0.0).then(|| (qty / average_daily_sales).max(0.0));
+    // far enough out to overflow the calendar is as good as never
+    let stockout_date = days_left
+        .and_then(|days| chrono::TimeDelta::try_days(days.ceil() as i64))
+        .and_then(|span| tz.today().checked_add_signed(span))
+        .map(|date| date.format("%Y-%m-%d").to_string());
+
+    Ok(StockoutForecast {
+        product_id,
+        product_name,
+        qty,
+        window_days,
+        average_daily_sales,
+        days_left,
+        stockout_date,
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct ReorderSuggestion {
+    product_id: i64,
+    product_name: String,
+    sku: Option<String>,
+    qty: f64,
+    reorder_point: f64,
+    average_daily_sales: f64,
+    target_qty: f64,
+    reorder_multiple: Option<f64>,
+    suggested_qty: f64,
+}
+
+// products at or below their reorder point, restocked to cover `cover_days` of the recent sales rate
+// (never less than the reorder point itself) and rounded up to the product's pack size
+#[tauri::command]
+fn reorder_suggestions(
+    state: State<DbState>,
+    lookback_days: Option<i64>,
+    cover_days: Option<i64>,
+) -> CommandResult<Vec<ReorderSuggestion>> {
+    let lookback_days = lookback_days
+        .unwrap_or(DEFAULT_REORDER_LOOKBACK_DAYS)
+        .max(1);
+    let cover_days = cover_days.unwrap_or(DEFAULT_REORDER_COVER_DAYS).max(0);
+    let since = chrono::TimeDelta::try_days(lookback_days)
+        .and_then(|span| Utc::now().checked_sub_signed(span))
+        .ok_or_else(|| AppError::Validation("조회 기간이 올바르지 않습니다.".into()))
+        .map_err(map_app_err)?
+        .to_rfc3339();
+
+    let conn = state.open().map_err(map_app_err)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT p.id, p.name, p.sku, p.qty, IFNULL(p.reorder_point, p.low_stock_threshold), p.reorder_multiple,
+                    IFNULL(SUM(CASE WHEN s.is_return = 1 THEN -s.qty ELSE s.qty END), 0)
+             FROM products p
+             LEFT JOIN sales s ON s.product_id = p.id AND s.ts >= ?
+             WHERE p.archived = 0 AND p.qty <= IFNULL(p.reorder_point, p.low_stock_threshold)
+             GROUP BY p.id
+             ORDER BY p.name COLLATE NOCASE",
+        )
+        .map_err(map_sql_err)?;
+    let rows = stmt
+        .query_map(params![since], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, f64>(3)?,
+                row.get::<_, f64>(4)?,
+                row.get::<_, Option<f64>>(5)?,
+                row.get::<_, f64>(6)?,
+            ))
+        })
+        .map_err(map_sql_err)?;
+
+    let mut suggestions = Vec::new();
+    for row in rows {
+        let (product_id, product_name, sku, qty, reorder_point, reorder_multiple, sold) =
+            row.map_err(map_sql_err)?;
+        let average_daily_sales = sold.max(0.0) / lookback_days as f64;
+        let target_qty = (average_daily_sales * cover_days as f64).max(reorder_point);
+        let needed = target_qty - qty;
+        if needed <= 0.0 {
+            continue;
+        }
+        let suggested_qty = match reorder_multiple.filter(|multiple| *multiple > 0.0) {
+            Some(multiple) => (needed / multiple).ceil() * multiple,
+            None => needed.ceil(),
+        };
+        suggestions.push(ReorderSuggestion {
+            product_id,
+            product_name,
+            sku,
+            qty,
+            reorder_point,
+            average_daily_sales,
+            target_qty,
+            reorder_multiple,
+            suggested_qty,
+        });
+    }
+    Ok(suggestions)
+}
+
+#[derive(Debug, Deserialize)]
+struct PurchaseLinePayload {
+    product_id: i64,
+    qty: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct PurchaseEstimateLine {
+    product_id: i64,
+    product_name: String,
+    sku: Option<String>,
+    qty: f64,
+    cost_price: f64,
+    line_total: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct PurchaseOrderEstimate {
+    lines: Vec<PurchaseEstimateLine>,
+    total: f64,
+}
+
+// prices a purchase order at current cost, e.g. the quantities from reorder_suggestions
+#[tauri::command]
+fn purchase_order_estimate(
+    state: State<DbState>,
+    lines: Vec<PurchaseLinePayload>,
+) -> CommandResult<PurchaseOrderEstimate> {
+    if lines.is_empty() {
+        return Err(AppError::Validation("발주 품목을 추가해주세요.".into()).into());
+    }
+    if lines.iter().any(|line| line.qty <= 0.0) {
+        return Err(AppError::Validation("미터은 0보다 커야 합니다.".into()).into());
+    }
+
+    let conn = state.open().map_err(map_app_err)?;
+    let decimals = amount_decimals(&conn).map_err(map_app_err)?;
+    let mut estimate_lines = Vec::new();
+    let mut total = 0.0;
+    for line in lines {
+        let product = fetch_product(&conn, line.product_id)
+            .map_err(map_app_err)?
+            .ok_or_else(|| AppError::Validation("존재하지 않는 품목입니다.".into()))?;
+        let cost_price = product.cost_price.ok_or_else(|| {
+            AppError::Validation(format!(
+                "'{}' 품목의 원가가 입력되지 않았습니다.",
+                product.name
+            ))
+        })?;
+        let line_total = round_amount(cost_price * line.qty, decimals);
+        total += line_total;
+        estimate_lines.push(PurchaseEstimateLine {
+            product_id: product.id,
+            product_name: product.name,
+            sku: product.sku,
+            qty: line.qty,
+            cost_price,
+            line_total,
+        });
+    }
+
+    Ok(PurchaseOrderEstimate {
+        lines: estimate_lines,
+        total: round_amount(total, decimals),
+    })
+}
+
+#[tauri::command]
+fn movements_for_product(
+    state: State<DbState>,
+    product_id: i64,
+    kind: Option<TransactionKind>,
+    from: Option<String>,
+    to: Option<String>,
+    tag: Option<String>,
+) -> CommandResult<Vec<StockMovement>> {
+    let conn = state.open().map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let (from, to) = resolve_date_range(tz, from.as_deref(), to.as_deref()).map_err(map_app_err)?;
+    let tag = tag.as_deref().map(str::trim).filter(|tag| !tag.is_empty());
+    let sql = format!(
+        "{MOVEMENT_SELECT}
+         WHERE t.product_id = ?1
+           AND (?2 IS NULL OR t.kind = ?2)
+           AND (?3 IS NULL OR t.ts >= ?3)
+           AND (?4 IS NULL OR t.ts < ?4)
+           AND (?5 IS NULL OR t.tag = ?5)
+         ORDER BY t.ts DESC"
+    );
+    let mut stmt = conn.prepare(&sql).map_err(map_sql_err)?;
+    let rows = stmt
+        .query_map(
+            params![product_id, kind.map(|kind| kind.as_str()), from, to, tag],
+            movement_from_row,
+        )
+        .map_err(map_sql_err)?;
+
+    let mut movements = Vec::new();
+    for row in rows {
+        movements.push(row.map_err(map_sql_err)?);
+    }
+    Ok(movements)
+}
+
+// stock receipts and issues ranked by value, for spotting typos or missing stock at a glance
+#[tauri::command]
+fn largest_transactions(
+    state: State<DbState>,
+    from: Option<String>,
+    to: Option<String>,
+    limit: i64,
+) -> CommandResult<Vec<StockMovement>> {
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::Validation(format!(
+            "조회 개수는 1에서 {MAX_PAGE_SIZE} 사이여야 합니다."
+        ))
+        .into());
+    }
+    let conn = state.open().map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let (from, to) = resolve_date_range(tz, from.as_deref(), to.as_deref()).map_err(map_app_err)?;
+    let sql = format!(
+        "{MOVEMENT_SELECT}
+         WHERE t.kind IN ('IN', 'OUT')
+           AND t.total_amount IS NOT NULL
+           AND (?1 IS NULL OR t.ts >= ?1)
+           AND (?2 IS NULL OR t.ts < ?2)
+         ORDER BY ABS(t.total_amount) DESC, t.ts DESC
+         LIMIT ?3"
+    );
+    let mut stmt = conn.prepare(&sql).map_err(map_sql_err)?;
+    let rows = stmt
+        .query_map(params![from, to, limit], movement_from_row)
+        .map_err(map_sql_err)?;
+
+    let mut movements = Vec::new();
+    for row in rows {
+        movements.push(row.map_err(map_sql_err)?);
+    }
+    Ok(movements)
+}
+
+// corrects when a late-typed entry happened; stored back in UTC like every other ts, and the
+// linked sale moves with it so sales and movement reports agree
+#[tauri::command]
+fn set_transaction_ts(
+    state: State<DbState>,
+    transaction_id: i64,
+    new_ts: String,
+) -> CommandResult<AppData> {
+    let ts = DateTime::parse_from_rfc3339(new_ts.trim())
+        .map_err(|_| AppError::Validation("일시 형식이 올바르지 않습니다.".into()))
+        .map_err(map_app_err)?
+        .with_timezone(&Utc);
+    if ts > Utc::now() {
+        return Err(AppError::Validation("미래 일시로는 변경할 수 없습니다.".into()).into());
+    }
+    let ts = ts.to_rfc3339();
+
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    let sale = tx
+        .query_row(
+            "SELECT t.sale_id, s.invoice_id
+             FROM transactions t
+             LEFT JOIN sales s ON s.id = t.sale_id
+             WHERE t.id = ?",
+            params![transaction_id],
+            |row| Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, Option<i64>>(1)?)),
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+    let (sale_id, invoice_id) = match sale {
+        Some(v) => v,
+        None => {
+            return Err(AppError::Validation(format!(
+                "존재하지 않는 입출고 기록입니다: #{transaction_id}"
+            ))
+            .into());
+        }
+    };
+    if invoice_id.is_some() {
+        return Err(
+            AppError::Validation("묶음 판매의 품목은 개별 수정할 수 없습니다.".into()).into(),
+        );
+    }
+
+    tx.execute(
+        "UPDATE transactions SET ts = ? WHERE id = ?",
+        params![ts, transaction_id],
+    )
+    .map_err(map_sql_err)?;
+    if let Some(sale_id) = sale_id {
+        tx.execute("UPDATE sales SET ts = ? WHERE id = ?", params![ts, sale_id])
+            .map_err(map_sql_err)?;
+        // the credit charge (or a return's refund) is dated with the sale for aging and statements
+        tx.execute(
+            "UPDATE credits SET ts = ?1 WHERE (sale_id = ?2 AND is_payment = 0) OR return_id = ?2",
+            params![ts, sale_id],
+        )
+        .map_err(map_sql_err)?;
+    }
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+// tag on both halves of a convert_stock pair; conversion_of is what identifies the pair
+const STOCK_CONVERSION_TAG: &str = "재고 전환";
+
+// repackaging: the OUT leg is valued at the source's cost and the IN leg carries the same
+// total, so the value moved between the two products is unchanged
+#[tauri::command]
+fn convert_stock(
+    state: State<DbState>,
+    from_product_id: i64,
+    from_qty: f64,
+    to_product_id: i64,
+    to_qty: f64,
+    note: Option<String>,
+) -> CommandResult<AppData> {
+    let note = clean_text(note.as_deref());
+    let mut errors = Vec::new();
+    if from_qty <= 0.0 {
+        errors.push(field_error("from_qty", "수량은 0보다 커야 합니다."));
+    }
+    if to_qty <= 0.0 {
+        errors.push(field_error("to_qty", "수량은 0보다 커야 합니다."));
+    }
+    if from_product_id == to_product_id {
+        errors.push(field_error(
+            "to_product_id",
+            "같은 품목으로는 전환할 수 없습니다.",
+        ));
+    }
+    check_text_len(&mut errors, "note", note, MAX_NOTE_LEN);
+    check_fields(errors).map_err(map_app_err)?;
+
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    let product = |product_id: i64| {
+        tx.query_row(
+            "SELECT qty, IFNULL(cost_price, unit_price) FROM products WHERE id = ?",
+            params![product_id],
+            |row| Ok((row.get::<_, f64>(0)?, row.get::<_, f64>(1)?)),
+        )
+        .optional()
+        .map_err(map_sql_err)?
+        .ok_or_else(|| AppError::Validation("존재하지 않는 품명입니다.".into()).to_string())
+    };
+    let (source_qty, source_cost) = product(from_product_id)?;
+    product(to_product_id)?;
+
+    ensure_qty_step(&tx, from_product_id, from_qty).map_err(map_app_err)?;
+    ensure_qty_step(&tx, to_product_id, to_qty).map_err(map_app_err)?;
+    ensure_stock_available(&tx, from_product_id, source_qty, from_qty).map_err(map_app_err)?;
+
+    let decimals = amount_decimals(&tx).map_err(map_app_err)?;
+    let total_amount = round_amount(source_cost * from_qty, decimals);
+    let ts = now_iso();
+
+    tx.execute(
+        "UPDATE products SET qty = qty - ? WHERE id = ?",
+        params![from_qty, from_product_id],
+    )
+    .map_err(map_sql_err)?;
+    consume_lots(&tx, from_product_id, from_qty).map_err(map_app_err)?;
+    tx.execute(
+        "INSERT INTO transactions (ts, kind, product_id, qty, unit_price, total_amount, note, tag) VALUES (?, 'OUT', ?, ?, ?, ?, ?, ?)",
+        params![
+            ts,
+            from_product_id,
+            from_qty,
+            source_cost,
+            total_amount,
+            note,
+            STOCK_CONVERSION_TAG
+        ],
+    )
+    .map_err(map_sql_err)?;
+    let out_id = tx.last_insert_rowid();
+
+    tx.execute(
+        "UPDATE products SET qty = qty + ? WHERE id = ?",
+        params![to_qty, to_product_id],
+    )
+    .map_err(map_sql_err)?;
+    tx.execute(
+        "INSERT INTO transactions (ts, kind, product_id, qty, unit_price, total_amount, note, tag, conversion_of) VALUES (?, 'IN', ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            ts,
+            to_product_id,
+            to_qty,
+            total_amount / to_qty,
+            total_amount,
+            note,
+            STOCK_CONVERSION_TAG,
+            out_id
+        ],
+    )
+    .map_err(map_sql_err)?;
+
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+// an empty tag clears it; any unknown id rolls the whole batch back
+#[tauri::command]
+fn tag_transactions(
+    state: State<DbState>,
+    transaction_ids: Vec<i64>,
+    tag: Option<String>,
+) -> CommandResult<AppData> {
+    let tag = tag.as_deref().map(str::trim).filter(|tag| !tag.is_empty());
+    ensure_text_len("tag", tag, MAX_SHORT_TEXT_LEN).map_err(map_app_err)?;
+
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    for transaction_id in transaction_ids {
+        let updated = tx
+            .execute(
+                "UPDATE transactions SET tag = ? WHERE id = ?",
+                params![tag, transaction_id],
+            )
+            .map_err(map_sql_err)?;
+        if updated == 0 {
+            return Err(AppError::Validation(format!(
+                "존재하지 않는 입출고 기록입니다: #{transaction_id}"
+            ))
+            .into());
+        }
+    }
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+// net (the default) includes return rows (is_return = true) so the history nets out to what was kept;
+// gross lists only the original sales
+#[tauri::command]
+fn sales_for_product(
+    state: State<DbState>,
+    product_id: i64,
+    from: Option<String>,
+    to: Option<String>,
+    net: Option<bool>,
+) -> CommandResult<Vec<SaleRecord>> {
+    let conn = state.open().map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let (from, to) = resolve_date_range(tz, from.as_deref(), to.as_deref()).map_err(map_app_err)?;
+    fetch_product_sales(&conn, product_id, from, to, net.unwrap_or(true)).map_err(Into::into)
+}
+
+fn fetch_product_sales(
+    conn: &Connection,
+    product_id: i64,
+    from: Option<String>,
+    to: Option<String>,
+    net: bool,
+) -> Result<Vec<SaleRecord>, AppError> {
+    let sql = format!(
+        "{SALE_SELECT}
+         WHERE s.product_id = ?1
+           AND (?2 IS NULL OR s.ts >= ?2)
+           AND (?3 IS NULL OR s.ts < ?3)
+           AND (?4 = 1 OR s.is_return = 0)
+         ORDER BY s.ts DESC"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![product_id, from, to, net], sale_from_row)?;
+
+    let mut sales = Vec::new();
+    for row in rows {
+        sales.push(row?);
+    }
+    Ok(sales)
+}
+
+#[derive(Debug, Serialize)]
+struct TopProduct {
+    product_id: i64,
+    product_name: String,
+    qty_sold: f64,
+    revenue: f64,
+}
+
+// net subtracts returns from both qty and revenue; gross counts only the original sales.
+// products whose returns cancel every sale drop out in net mode
+#[tauri::command]
+fn top_products(
+    state: State<DbState>,
+    from: Option<String>,
+    to: Option<String>,
+    limit: i64,
+    net: bool,
+) -> CommandResult<Vec<TopProduct>> {
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::Validation(format!(
+            "조회 개수는 1에서 {MAX_PAGE_SIZE} 사이여야 합니다."
+        ))
+        .into());
+    }
+    let conn = state.open().map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let (from, to) = resolve_date_range(tz, from.as_deref(), to.as_deref()).map_err(map_app_err)?;
+    fetch_top_products(&conn, from, to, limit, net).map_err(Into::into)
+}
+
+fn fetch_top_products(
+    conn: &Connection,
+    from: Option<String>,
+    to: Option<String>,
+    limit: i64,
+    net: bool,
+) -> Result<Vec<TopProduct>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.name,
+                SUM(CASE WHEN s.is_return = 1 THEN -s.qty ELSE s.qty END) AS qty_sold,
+                SUM(CASE WHEN s.is_return = 1 THEN -s.total_amount ELSE s.total_amount END)
+         FROM sales s
+         JOIN products p ON p.id = s.product_id
+         WHERE (?1 IS NULL OR s.ts >= ?1)
+           AND (?2 IS NULL OR s.ts < ?2)
+           AND (?3 = 1 OR s.is_return = 0)
+         GROUP BY p.id
+         HAVING qty_sold > 0
+         ORDER BY qty_sold DESC, p.name COLLATE NOCASE
+         LIMIT ?4",
+    )?;
+    let rows = stmt.query_map(params![from, to, net, limit], |row| {
+        Ok(TopProduct {
+            product_id: row.get(0)?,
+            product_name: row.get(1)?,
+            qty_sold: row.get(2)?,
+            revenue: row.get(3)?,
+        })
+    })?;
+
+    let mut products = Vec::new();
+    for row in rows {
+        products.push(row?);
+    }
+    Ok(products)
+}
+
+// matches the note and the customer name, including the name snapshot kept after a customer is deleted
+#[tauri::command]
+fn search_sales(
+    state: State<DbState>,
+    query: String,
+    from: Option<String>,
+    to: Option<String>,
+) -> CommandResult<Vec<SaleRecord>> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Err(AppError::Validation("검색어를 입력해주세요.".into()).into());
+    }
+    let pattern = format!("%{}%", escape_like(query));
+
+    let conn = state.open().map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let (from, to) = resolve_date_range(tz, from.as_deref(), to.as_deref()).map_err(map_app_err)?;
+    let sql = format!(
+        "{SALE_SELECT}
+         WHERE (s.note LIKE ?1 ESCAPE '\\'
+                OR COALESCE(c.name, s.customer_name_snapshot) LIKE ?1 ESCAPE '\\')
+           AND (?2 IS NULL OR s.ts >= ?2)
+           AND (?3 IS NULL OR s.ts < ?3)
+         ORDER BY s.ts DESC
+         LIMIT ?4"
+    );
+    let mut stmt = conn.prepare(&sql).map_err(map_sql_err)?;
+    let rows = stmt
+        .query_map(params![pattern, from, to, MAX_PAGE_SIZE], sale_from_row)
+        .map_err(map_sql_err)?;
+
+    let mut sales = Vec::new();
+    for row in rows {
+        sales.push(row.map_err(map_sql_err)?);
+    }
+    Ok(sales)
+}
+
+// user text is matched literally; pair with ESCAPE '\' in the LIKE clause
+fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if matches!(ch, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+#[derive(Debug, Deserialize)]
+struct PurchasePayload {
+    product_id: i64,
+    supplier_id: i64,
+    qty: f64,
+    unit_cost: f64,
+    note: Option<String>,
+}
+
+#[tauri::command]
+fn record_purchase(state: State<DbState>, payload: PurchasePayload) -> CommandResult<AppData> {
+    let note = clean_text(payload.note.as_deref());
+    if payload.qty <= 0.0 {
+        return Err(AppError::Validation("미터은 0보다 커야 합니다.".into()).into());
+    }
+    if payload.unit_cost < 0.0 {
+        return Err(AppError::Validation("원가는 0 이상이어야 합니다.".into()).into());
+    }
+    ensure_text_len("note", note, MAX_NOTE_LEN).map_err(map_app_err)?;
+
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+
+    let supplier = supplier_name(&tx, payload.supplier_id).map_err(map_app_err)?;
+    let (current_qty, current_cost) = tx
+        .query_row(
+            "SELECT qty, cost_price FROM products WHERE id = ?",
+            params![payload.product_id],
+            |row| Ok((row.get::<_, f64>(0)?, row.get::<_, Option<f64>>(1)?)),
+        )
+        .optional()
+        .map_err(map_sql_err)?
+        .ok_or_else(|| AppError::Validation("존재하지 않는 품명입니다.".into()).to_string())?;
+    ensure_qty_step(&tx, payload.product_id, payload.qty).map_err(map_app_err)?;
+
+    let new_cost = weighted_average_cost(current_qty, current_cost, payload.qty, payload.unit_cost);
+
+    tx.execute(
+        "UPDATE products SET qty = qty + ?, cost_price = ? WHERE id = ?",
+        params![payload.qty, new_cost, payload.product_id],
+    )
+    .map_err(map_sql_err)?;
+
+    let decimals = amount_decimals(&tx).map_err(map_app_err)?;
+    tx.execute(
+        "INSERT INTO transactions (ts, kind, product_id, qty, unit_price, total_amount, counterparty, supplier_id, note) VALUES (?, 'IN', ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            now_iso(),
+            payload.product_id,
+            payload.qty,
+            payload.unit_cost,
+            round_amount(payload.unit_cost * payload.qty, decimals),
+            supplier,
+            payload.supplier_id,
+            note
+        ],
+    )
+    .map_err(map_sql_err)?;
+
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+// weighted average over stock on hand; oversold or uncosted stock contributes nothing
+fn weighted_average_cost(
+    current_qty: f64,
+    current_cost: Option<f64>,
+    received_qty: f64,
+    unit_cost: f64,
+) -> f64 {
+    match current_cost {
+        Some(cost) if current_qty > 0.0 => {
+            (current_qty * cost + received_qty * unit_cost) / (current_qty + received_qty)
+        }
+        _ => unit_cost,
+    }
+}
+
+// lines reuse StockEntryPayload: unit_price is the unit cost paid, and counterparty/customer_id
+// are ignored because the supplier comes from the receipt
+#[tauri::command]
+fn record_goods_receipt(
+    state: State<DbState>,
+    lines: Vec<StockEntryPayload>,
+    supplier_id: i64,
+    note: Option<String>,
+) -> CommandResult<AppData> {
+    if lines.is_empty() {
+        return Err(AppError::Validation("입고 품목을 추가해주세요.".into()).into());
+    }
+    ensure_text_len("note", note.as_deref(), MAX_NOTE_LEN).map_err(map_app_err)?;
+    for line in &lines {
+        let kind = line.kind.unwrap_or(TransactionKind::In);
+        if !matches!(kind, TransactionKind::In) {
+            return Err(
+                AppError::Validation("입고 명세에는 입고만 포함할 수 있습니다.".into()).into(),
+            );
+        }
+        if line.qty <= 0.0 {
+            return Err(AppError::Validation("미터은 0보다 커야 합니다.".into()).into());
+        }
+        if line.unit_price.is_some_and(|cost| cost < 0.0) {
+            return Err(AppError::Validation("원가는 0 이상이어야 합니다.".into()).into());
+        }
+        ensure_text_len("note", line.note.as_deref(), MAX_NOTE_LEN).map_err(map_app_err)?;
+        stock_entry_lot(line).map_err(map_app_err)?;
+    }
+
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    let supplier = supplier_name(&tx, supplier_id).map_err(map_app_err)?;
+    let ts = now_iso();
+    tx.execute(
+        "INSERT INTO goods_receipts (ts, supplier_id, note) VALUES (?, ?, ?)",
+        params![ts, supplier_id, note.as_deref()],
+    )
+    .map_err(map_sql_err)?;
+    let receipt_id = tx.last_insert_rowid();
+    let decimals = amount_decimals(&tx).map_err(map_app_err)?;
+
+    // any missing product returns before commit, so the whole receipt rolls back
+    for line in &lines {
+        let (current_qty, current_cost) = tx
+            .query_row(
+                "SELECT qty, cost_price FROM products WHERE id = ?",
+                params![line.product_id],
+                |row| Ok((row.get::<_, f64>(0)?, row.get::<_, Option<f64>>(1)?)),
+            )
+            .optional()
+            .map_err(map_sql_err)?
+            .ok_or_else(|| AppError::Validation("존재하지 않는 품명입니다.".into()).to_string())?;
+        ensure_qty_step(&tx, line.product_id, line.qty).map_err(map_app_err)?;
+        let new_cost = line
+            .unit_price
+            .map(|cost| weighted_average_cost(current_qty, current_cost, line.qty, cost))
+            .or(current_cost);
+        tx.execute(
+            "UPDATE products SET qty = qty + ?, cost_price = ? WHERE id = ?",
+            params![line.qty, new_cost, line.product_id],
+        )
+        .map_err(map_sql_err)?;
+
+        let unit_cost = line.unit_price.or(current_cost);
+        tx.execute(
+            "INSERT INTO transactions (ts, kind, product_id, qty, unit_price, total_amount, counterparty, supplier_id, note, receipt_id) VALUES (?, 'IN', ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                ts,
+                line.product_id,
+                line.qty,
+                unit_cost,
+                unit_cost.map(|cost| round_amount(cost * line.qty, decimals)),
+                supplier,
+                supplier_id,
+                line.note.as_deref(),
+                receipt_id
+            ],
+        )
+        .map_err(map_sql_err)?;
+
+        let lot = stock_entry_lot(line).map_err(map_app_err)?;
+        if lot.0.is_some() || lot.1.is_some() {
+            let transaction_id = tx.last_insert_rowid();
+            insert_lot(&tx, line.product_id, line.qty, lot, &ts, transaction_id)
+                .map_err(map_sql_err)?;
+        }
+    }
+
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+#[derive(Debug, Deserialize)]
+struct PurchaseReturnPayload {
+    product_id: i64,
+    supplier_id: i64,
+    qty: f64,
+    refund_amount: Option<f64>,
+    note: Option<String>,
+}
+
+#[tauri::command]
+fn record_purchase_return(
+    state: State<DbState>,
+    payload: PurchaseReturnPayload,
+) -> CommandResult<AppData> {
+    let note = clean_text(payload.note.as_deref());
+    if payload.qty <= 0.0 {
+        return Err(AppError::Validation("반품 수량은 0보다 커야 합니다.".into()).into());
+    }
+    if payload.refund_amount.is_some_and(|amount| amount < 0.0) {
+        return Err(AppError::Validation("환불 금액은 0 이상이어야 합니다.".into()).into());
+    }
+    ensure_text_len("note", note, MAX_NOTE_LEN).map_err(map_app_err)?;
+
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+
+    let supplier = supplier_name(&tx, payload.supplier_id).map_err(map_app_err)?;
+    let current_qty = tx
+        .query_row(
+            "SELECT qty FROM products WHERE id = ?",
+            params![payload.product_id],
+            |row| row.get::<_, f64>(0),
+        )
+        .optional()
+        .map_err(map_sql_err)?
+        .ok_or_else(|| AppError::Validation("존재하지 않는 품명입니다.".into()).to_string())?;
+    ensure_qty_step(&tx, payload.product_id, payload.qty).map_err(map_app_err)?;
+    ensure_stock_available(&tx, payload.product_id, current_qty, payload.qty)
+        .map_err(map_app_err)?;
+
+    tx.execute(
+        "UPDATE products SET qty = qty - ? WHERE id = ?",
+        params![payload.qty, payload.product_id],
+    )
+    .map_err(map_sql_err)?;
+    consume_lots(&tx, payload.product_id, payload.qty).map_err(map_app_err)?;
+
+    let unit_price = payload.refund_amount.map(|amount| amount / payload.qty);
+    tx.execute(
+        "INSERT INTO transactions (ts, kind, product_id, qty, unit_price, total_amount, counterparty, supplier_id, note) VALUES (?, 'SUPPLIER_RETURN', ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            now_iso(),
+            payload.product_id,
+            payload.qty,
+            unit_price,
+            payload.refund_amount,
+            supplier,
+            payload.supplier_id,
+            note
+        ],
+    )
+    .map_err(map_sql_err)?;
+
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+#[derive(Debug, Deserialize)]
+struct StocktakePayload {
+    product_id: i64,
+    counted_qty: f64,
+    note: Option<String>,
+}
+
+#[tauri::command]
+fn record_stocktake(state: State<DbState>, payload: StocktakePayload) -> CommandResult<AppData> {
+    if payload.counted_qty < 0.0 {
+        return Err(AppError::Validation("실사 수량은 0 이상이어야 합니다.".into()).into());
+    }
+    if !has_text(payload.note.as_deref()) {
+        return Err(
+            AppError::Validation("재고 조정에는 사유(메모)를 입력해야 합니다.".into()).into(),
+        );
+    }
+
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+
+    let product = tx
+        .query_row(
+            "SELECT qty, unit_price FROM products WHERE id = ? AND archived = 0",
+            params![payload.product_id],
+            |row| Ok((row.get::<_, f64>(0)?, row.get::<_, f64>(1)?)),
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+    let (current_qty, unit_price) = product
+        .ok_or_else(|| AppError::Validation("존재하지 않는 품명입니다.".into()).to_string())?;
+
+    let delta = payload.counted_qty - current_qty;
+    if delta.abs() > f64::EPSILON {
+        tx.execute(
+            "UPDATE products SET qty = ? WHERE id = ?",
+            params![payload.counted_qty, payload.product_id],
+        )
+        .map_err(map_sql_err)?;
+        shift_lots(&tx, payload.product_id, delta).map_err(map_app_err)?;
+        tx.execute(
+            "INSERT INTO transactions (ts, kind, product_id, qty, unit_price, total_amount, note, is_stocktake) VALUES (?, 'ADJUST', ?, ?, ?, ?, ?, 1)",
+            params![
+                now_iso(),
+                payload.product_id,
+                delta,
+                unit_price,
+                delta * unit_price,
+                payload.note.as_deref().map(str::trim)
+            ],
+        )
+        .map_err(map_sql_err)?;
+    }
+
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+#[derive(Debug, Deserialize)]
+struct SalePayload {
+    product_id: i64,
+    qty: f64,
+    unit_price: Option<f64>,
+    customer_id: Option<i64>,
+    note: Option<String>,
+    is_credit: bool,
+    client_token: Option<String>,
+    // layaway: paid up front on a credit sale, leaving the rest outstanding
+    deposit: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SaleUpdatePayload {
+    id: i64,
+    qty: f64,
+    unit_price: f64,
+    customer_id: Option<i64>,
+    note: Option<String>,
+    is_credit: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReturnPayload {
+    product_id: i64,
+    customer_id: Option<i64>,
+    qty: f64,
+    note: Option<String>,
+    override_amount: Option<f64>,
+    // false sends the returned goods to damaged_qty instead of sellable stock
+    restock: Option<bool>,
+}
+
+fn is_client_token_conflict(err: &rusqlite::Error) -> bool {
+    matches!(
+        err,
+        rusqlite::Error::SqliteFailure(failure, Some(detail))
+            if failure.code == rusqlite::ErrorCode::ConstraintViolation
+                && detail.contains("sales.client_token")
+    )
+}
+
+#[tauri::command]
+fn record_sale(
+    app: tauri::AppHandle,
+    state: State<DbState>,
+    payload: SalePayload,
+) -> CommandResult<AppData> {
+    let note = clean_text(payload.note.as_deref());
+    let mut errors = Vec::new();
+    if payload.qty <= 0.0 {
+        errors.push(field_error("qty", "미터은 0보다 커야 합니다."));
+    }
+    if payload.is_credit && payload.customer_id.is_none() {
+        errors.push(field_error(
+            "customer_id",
+            "외상 거래에는 고객을 선택해야 합니다.",
+        ));
+    }
+    if payload.unit_price.is_some_and(|price| price < 0.0) {
+        errors.push(field_error("unit_price", "단가는 0 이상이어야 합니다."));
+    }
+    if let Some(deposit) = payload.deposit {
+        if !payload.is_credit {
+            errors.push(field_error(
+                "deposit",
+                "선금은 외상 판매에만 입력할 수 있습니다.",
+            ));
+        } else if deposit <= 0.0 {
+            errors.push(field_error("deposit", "선금은 0보다 커야 합니다."));
+        }
+    }
+    check_text_len(&mut errors, "note", note, MAX_NOTE_LEN);
+    check_fields(errors).map_err(map_app_err)?;
+
+    let client_token = payload
+        .client_token
+        .as_deref()
+        .map(str::trim)
+        .filter(|token| !token.is_empty());
+
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+
+    // a retried submission with the same token returns the current data without a new sale
+    if let Some(token) = client_token {
+        let already_recorded = tx
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM sales WHERE client_token = ?)",
+                params![token],
+                |row| row.get::<_, i64>(0),
+            )
+            .map_err(map_sql_err)?
+            != 0;
+        if already_recorded {
+            drop(tx);
+            return load_app_data(&state).map_err(Into::into);
+        }
+    }
+
+    let product = tx
+        .query_row(
+            "SELECT qty, unit_price, cost_price FROM products WHERE id = ?",
+            params![payload.product_id],
+            |row| {
+                Ok((
+                    row.get::<_, f64>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, Option<f64>>(2)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+    let (current_qty, default_price, cost_price) = product
+        .ok_or_else(|| AppError::Validation("존재하지 않는 품명입니다.".into()).to_string())?;
+    if let Some(customer_id) = payload.customer_id.filter(|_| payload.is_credit) {
+        ensure_credit_allowed(&tx, customer_id).map_err(map_app_err)?;
+    }
+
+    ensure_qty_step(&tx, payload.product_id, payload.qty).map_err(map_app_err)?;
+    ensure_stock_available(&tx, payload.product_id, current_qty, payload.qty)
+        .map_err(map_app_err)?;
+
+    if let Some(price) = payload.unit_price {
+        validate_price_override(&tx, price, default_price, cost_price).map_err(map_app_err)?;
+    }
+    let unit_price = payload.unit_price.unwrap_or(default_price);
+    let decimals = amount_decimals(&tx).map_err(map_app_err)?;
+    let line_amount = round_amount(unit_price * payload.qty, decimals);
+    let (total_amount, rounding_adjustment) =
+        charged_amount(&tx, line_amount, payload.is_credit).map_err(map_app_err)?;
+    if payload.deposit.is_some_and(|amount| amount > total_amount) {
+        return Err(AppError::Fields(vec![field_error(
+            "deposit",
+            "선금은 판매 금액보다 클 수 없습니다.",
+        )])
+        .into());
+    }
+    let ts = now_iso();
+
+    tx.execute(
+        "UPDATE products SET qty = qty - ? WHERE id = ?",
+        params![payload.qty, payload.product_id],
+    )
+    .map_err(map_sql_err)?;
+    consume_lots(&tx, payload.product_id, payload.qty).map_err(map_app_err)?;
+
+    let inserted = tx.execute(
+        "INSERT INTO sales (ts, product_id, qty, price_snapshot, total_amount, customer_id, note, is_credit, client_token, cost_snapshot, tax_rate, rounding_adjustment)
+         VALUES (?, ?2, ?, ?, ?, ?, ?, ?, ?, ?, (SELECT tax_rate FROM products WHERE id = ?2), ?)",
+        params![
+            ts,
+            payload.product_id,
+            payload.qty,
+            unit_price,
+            total_amount,
+            payload.customer_id,
+            note,
+            if payload.is_credit { 1 } else { 0 },
+            client_token,
+            cost_price,
+            rounding_adjustment
+        ],
+    );
+    // two submissions of one token can both pass the check above; the unique index lets only
+    // one commit, and the other rolls back its stock change and answers like a retry
+    match inserted {
+        Err(err) if is_client_token_conflict(&err) => {
+            drop(tx);
+            return load_app_data(&state).map_err(Into::into);
+        }
+        other => other.map_err(map_sql_err)?,
+    };
+
+    let sale_id = tx.last_insert_rowid();
+    snapshot_sale_customer(&tx, sale_id).map_err(map_app_err)?;
+
+    tx.execute(
+        "INSERT INTO transactions (ts, kind, product_id, qty, unit_price, total_amount, customer_id, note, sale_id) VALUES (?, 'OUT', ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            ts,
+            payload.product_id,
+            payload.qty,
+            unit_price,
+            total_amount,
+            payload.customer_id,
+            note,
+            sale_id
+        ],
+    )
+    .map_err(map_sql_err)?;
+
+    if payload.is_credit {
+        tx.execute(
+            "INSERT INTO credits (ts, customer_id, sale_id, amount, is_payment, note) VALUES (?, ?, ?, ?, 0, ?)",
+            params![
+                ts,
+                payload.customer_id,
+                sale_id,
+                total_amount,
+                note
+            ],
+        )
+        .map_err(map_sql_err)?;
+    }
+    // tied to the sale like a settlement, so the remainder shows as what is still owed on it
+    if let Some(deposit) = payload.deposit {
+        tx.execute(
+            "INSERT INTO credits (ts, customer_id, sale_id, amount, is_payment, note) VALUES (?, ?, ?, ?, 1, ?)",
+            params![ts, payload.customer_id, sale_id, deposit, "선금"],
+        )
+        .map_err(map_sql_err)?;
+    }
+
+    tx.commit().map_err(map_sql_err)?;
+    // emitted only after commit so dashboard listeners never see rolled-back rows;
+    // the payload is the committed SaleRecord and a failed emit does not fail the sale
+    if let Ok(Some(sale)) = fetch_sale(&conn, sale_id) {
+        let _ = app.emit("sale-recorded", sale);
+    }
+    load_app_data(&state).map_err(Into::into)
+}
+
+#[derive(Debug, Deserialize)]
+struct InvoiceLinePayload {
+    product_id: i64,
+    qty: f64,
+    unit_price: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InvoicePayload {
+    customer_id: Option<i64>,
+    note: Option<String>,
+    is_credit: bool,
+    lines: Vec<InvoiceLinePayload>,
+}
+
+#[tauri::command]
+fn record_invoice(state: State<DbState>, payload: InvoicePayload) -> CommandResult<AppData> {
+    let note = clean_text(payload.note.as_deref());
+    if payload.lines.is_empty() {
+        return Err(AppError::Validation("판매할 품목을 추가해주세요.".into()).into());
+    }
+    if payload.lines.iter().any(|line| line.qty <= 0.0) {
+        return Err(AppError::Validation("미터은 0보다 커야 합니다.".into()).into());
+    }
+    if payload.is_credit && payload.customer_id.is_none() {
+        return Err(AppError::Validation("외상 거래에는 고객을 선택해야 합니다.".into()).into());
+    }
+    ensure_text_len("note", note, MAX_NOTE_LEN).map_err(map_app_err)?;
+
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    if let Some(customer_id) = payload.customer_id.filter(|_| payload.is_credit) {
+        ensure_credit_allowed(&tx, customer_id).map_err(map_app_err)?;
+    }
+    let ts = now_iso();
+
+    tx.execute(
+        "INSERT INTO invoices (ts, customer_id, is_credit, note) VALUES (?, ?, ?, ?)",
+        params![
+            ts,
+            payload.customer_id,
+            if payload.is_credit { 1 } else { 0 },
+            note
+        ],
+    )
+    .map_err(map_sql_err)?;
+    let invoice_id = tx.last_insert_rowid();
+
+    let decimals = amount_decimals(&tx).map_err(map_app_err)?;
+    let mut invoice_total = 0.0;
+    let mut last_sale_id = 0;
+    for line in &payload.lines {
+        let product = tx
+            .query_row(
+                "SELECT qty, unit_price, cost_price FROM products WHERE id = ?",
+                params![line.product_id],
+                |row| {
+                    Ok((
+                        row.get::<_, f64>(0)?,
+                        row.get::<_, f64>(1)?,
+                        row.get::<_, Option<f64>>(2)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(map_sql_err)?;
+        // stock is read per line so repeated products see the earlier decrements
+        let (current_qty, default_price, cost_price) = product
+            .ok_or_else(|| AppError::Validation("존재하지 않는 품명입니다.".into()).to_string())?;
+        ensure_qty_step(&tx, line.product_id, line.qty).map_err(map_app_err)?;
+        ensure_stock_available(&tx, line.product_id, current_qty, line.qty).map_err(map_app_err)?;
+        if let Some(price) = line.unit_price {
+            validate_price_override(&tx, price, default_price, cost_price).map_err(map_app_err)?;
+        }
+
+        let unit_price = line.unit_price.unwrap_or(default_price);
+        let total_amount = round_amount(unit_price * line.qty, decimals);
+        invoice_total += total_amount;
+
+        tx.execute(
+            "UPDATE products SET qty = qty - ? WHERE id = ?",
+            params![line.qty, line.product_id],
+        )
+        .map_err(map_sql_err)?;
+        consume_lots(&tx, line.product_id, line.qty).map_err(map_app_err)?;
+        tx.execute(
+            "INSERT INTO sales (ts, product_id, qty, price_snapshot, total_amount, customer_id, note, is_credit, invoice_id, cost_snapshot, tax_rate)
+             VALUES (?, ?2, ?, ?, ?, ?, ?, ?, ?, ?, (SELECT tax_rate FROM products WHERE id = ?2))",
+            params![
+                ts,
+                line.product_id,
+                line.qty,
+                unit_price,
+                total_amount,
+                payload.customer_id,
+                note,
+                if payload.is_credit { 1 } else { 0 },
+                invoice_id,
+                cost_price
+            ],
+        )
+        .map_err(map_sql_err)?;
+        let sale_id = tx.last_insert_rowid();
+        last_sale_id = sale_id;
+        snapshot_sale_customer(&tx, sale_id).map_err(map_app_err)?;
+        tx.execute(
+            "INSERT INTO transactions (ts, kind, product_id, qty, unit_price, total_amount, customer_id, note, sale_id) VALUES (?, 'OUT', ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                ts,
+                line.product_id,
+                line.qty,
+                unit_price,
+                total_amount,
+                payload.customer_id,
+                note,
+                sale_id
+            ],
+        )
+        .map_err(map_sql_err)?;
+    }
+
+    // a cash invoice is paid as one amount, so its rounding lands on the last line
+    let lines_total = round_amount(invoice_total, decimals);
+    let (_, rounding_adjustment) =
+        charged_amount(&tx, lines_total, payload.is_credit).map_err(map_app_err)?;
+    if rounding_adjustment != 0.0 {
+        tx.execute(
+            "UPDATE sales SET total_amount = total_amount + ?1, rounding_adjustment = ?1 WHERE id = ?2",
+            params![rounding_adjustment, last_sale_id],
+        )
+        .map_err(map_sql_err)?;
+        tx.execute(
+            "UPDATE transactions SET total_amount = total_amount + ? WHERE sale_id = ? AND kind = 'OUT'",
+            params![rounding_adjustment, last_sale_id],
+        )
+        .map_err(map_sql_err)?;
+    }
+
+    // one credit row covers the whole invoice
+    if payload.is_credit {
+        tx.execute(
+            "INSERT INTO credits (ts, customer_id, sale_id, invoice_id, amount, is_payment, note) VALUES (?, ?, NULL, ?, ?, 0, ?)",
+            params![
+                ts,
+                payload.customer_id,
+                invoice_id,
+                invoice_total,
+                note
+            ],
+        )
+        .map_err(map_sql_err)?;
+    }
+
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+#[derive(Debug, Serialize)]
+struct InvoiceView {
+    id: i64,
+    ts: String,
+    customer_id: Option<i64>,
+    customer_name: Option<String>,
+    customer_phone: Option<String>,
+    is_credit: bool,
+    note: Option<String>,
+    total_amount: f64,
+    credit_amount: Option<f64>,
+    reconciled: bool,
+    lines: Vec<SaleRecord>,
+}
+
+#[tauri::command]
+fn fetch_invoice(state: State<DbState>, invoice_id: i64) -> CommandResult<InvoiceView> {
+    let conn = state.open().map_err(map_app_err)?;
+    fetch_invoice_view(&conn, invoice_id).map_err(Into::into)
+}
+
+fn fetch_invoice_view(conn: &Connection, invoice_id: i64) -> Result<InvoiceView, AppError> {
+    let header = conn
+        .query_row(
+            "SELECT i.ts, i.customer_id, c.name, c.phone, i.is_credit, i.note
+             FROM invoices i
+             LEFT JOIN customers c ON c.id = i.customer_id
+             WHERE i.id = ?",
+            params![invoice_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<i64>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, i64>(4)? != 0,
+                    row.get::<_, Option<String>>(5)?,
+                ))
+            },
+        )
+        .optional()?;
+    let (ts, customer_id, customer_name, customer_phone, is_credit, note) =
+        header.ok_or_else(|| AppError::Validation("존재하지 않는 묶음 판매입니다.".into()))?;
+
+    let sql = format!("{SALE_SELECT} WHERE s.invoice_id = ? AND s.is_return = 0 ORDER BY s.id");
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![invoice_id], sale_from_row)?;
+    let mut lines = Vec::new();
+    for row in rows {
+        lines.push(row?);
+    }
+    let total_amount: f64 = lines.iter().map(|line| line.total_amount).sum();
+
+    let credit_amount = conn
+        .query_row(
+            "SELECT amount FROM credits WHERE invoice_id = ? AND is_payment = 0",
+            params![invoice_id],
+            |row| row.get::<_, f64>(0),
+        )
+        .optional()?;
+    let reconciled = match credit_amount {
+        Some(amount) => is_credit && (amount - total_amount).abs() < 0.005,
+        None => !is_credit,
+    };
+
+    Ok(InvoiceView {
+        id: invoice_id,
+        ts,
+        customer_id,
+        customer_name,
+        customer_phone,
+        is_credit,
+        note,
+        total_amount,
+        credit_amount,
+        reconciled,
+        lines,
+    })
+}
+
+// columns on a 58mm thermal printer
+const RECEIPT_WIDTH: usize = 32;
+
+// plain text for a single sale or a whole invoice, laid out in fixed columns; prices include tax,
+// so the tax line only shows the share of the total, and discounts come from grant_discount
+#[tauri::command]
+fn generate_receipt(
+    state: State<DbState>,
+    sale_id: Option<i64>,
+    invoice_id: Option<i64>,
+) -> CommandResult<String> {
+    let conn = state.open().map_err(map_app_err)?;
+    let (ts, customer_name, lines) = match (sale_id, invoice_id) {
+        (Some(sale_id), None) => {
+            let sale = fetch_sale(&conn, sale_id)
+                .map_err(map_app_err)?
+                .ok_or_else(|| AppError::Validation("존재하지 않는 판매입니다.".into()))?;
+            if sale.is_return {
+                return Err(AppError::Validation(
+                    "반품 내역은 영수증을 발행할 수 없습니다.".into(),
+                )
+                .into());
+            }
+            (sale.ts.clone(), sale.customer_name.clone(), vec![sale])
+        }
+        (None, Some(invoice_id)) => {
+            let invoice = fetch_invoice_view(&conn, invoice_id).map_err(map_app_err)?;
+            (invoice.ts, invoice.customer_name, invoice.lines)
+        }
+        _ => {
+            return Err(
+                AppError::Validation("판매 또는 묶음 판매 중 하나를 지정해주세요.".into()).into(),
+            );
+        }
+    };
+
+    let (tax, discount) = conn
+        .query_row(
+            "SELECT
+                IFNULL(SUM(s.total_amount * s.tax_rate / (100 + s.tax_rate)), 0),
+                IFNULL((
+                    SELECT SUM(cr.amount)
+                    FROM credits cr
+                    JOIN sales d ON d.id = cr.sale_id
+                    WHERE cr.is_discount = 1 AND d.is_return = 0 AND (d.id = ?1 OR d.invoice_id = ?2)
+                ), 0)
+             FROM sales s
+             WHERE s.is_return = 0 AND (s.id = ?1 OR s.invoice_id = ?2)",
+            params![sale_id, invoice_id],
+            |row| Ok((row.get::<_, f64>(0)?, row.get::<_, f64>(1)?)),
+        )
+        .map_err(map_sql_err)?;
+    let settings = fetch_settings(&conn).map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let money = |amount: f64| {
+        format_currency(
+            amount,
+            &settings.currency_symbol,
+            settings.currency_decimals,
+        )
+    };
+    let divider = "-".repeat(RECEIPT_WIDTH);
+
+    let mut receipt = String::new();
+    if let Some(shop_name) = settings.shop_name.as_deref() {
+        let padding = RECEIPT_WIDTH.saturating_sub(receipt_width(shop_name)) / 2;
+        receipt.push_str(&" ".repeat(padding));
+        receipt.push_str(shop_name);
+        receipt.push('\n');
+    }
+    receipt.push_str(&divider);
+    receipt.push('\n');
+    let date = tz
+        .local_datetime(&ts)
+        .map(|local| local.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or(ts);
+    push_receipt_row(&mut receipt, "일시", &date);
+    if let Some(customer_name) = customer_name.as_deref() {
+        push_receipt_row(&mut receipt, "고객", customer_name);
+    }
+    receipt.push_str(&divider);
+    receipt.push('\n');
+
+    let mut subtotal = 0.0;
+    for line in &lines {
+        subtotal += line.total_amount;
+        receipt.push_str(&line.product_name);
+        receipt.push('\n');
+        push_receipt_row(
+            &mut receipt,
+            &format!("  {} x {}", line.qty, money(line.unit_price)),
+            &money(line.total_amount),
+        );
+    }
+    receipt.push_str(&divider);
+    receipt.push('\n');
+
+    push_receipt_row(&mut receipt, "소계", &money(subtotal));
+    if discount > 0.0 {
+        push_receipt_row(&mut receipt, "할인", &money(-discount));
+    }
+    push_receipt_row(&mut receipt, "합계", &money(subtotal - discount));
+    if tax > 0.0 {
+        push_receipt_row(
+            &mut receipt,
+            "부가세 포함",
+            &money(round_amount(tax, settings.currency_decimals)),
+        );
+    }
+    Ok(receipt)
+}
+
+// thermal printers give Hangul and other East Asian wide characters two columns
+fn receipt_width(text: &str) -> usize {
+    text.chars()
+        .map(|ch| match ch {
+            '\u{1100}'..='\u{115F}'
+            | '\u{2E80}'..='\u{A4CF}'
+            | '\u{AC00}'..='\u{D7A3}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FF00}'..='\u{FF60}'
+            | '\u{FFE0}'..='\u{FFE6}' => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+// left text and right-aligned value on one line, keeping at least one space between them
+fn push_receipt_row(receipt: &mut String, left: &str, right: &str) {
+    let gap = RECEIPT_WIDTH
+        .saturating_sub(receipt_width(left) + receipt_width(right))
+        .max(1);
+    receipt.push_str(left);
+    receipt.push_str(&" ".repeat(gap));
+    receipt.push_str(right);
+    receipt.push('\n');
+}
+
+// Guards manual unit_price overrides against selling below cost and fat-finger typos.
+fn validate_price_override(
+    conn: &Connection,
+    unit_price: f64,
+    list_price: f64,
+    cost_price: Option<f64>,
+) -> Result<(), AppError> {
+    if unit_price < 0.0 {
+        return Err(AppError::Validation("단가는 0 이상이어야 합니다.".into()));
+    }
+    let max_multiplier = read_setting_f64(conn, "price_override_max_multiplier", 10.0)?;
+    if max_multiplier > 0.0 && list_price > 0.0 && unit_price > list_price * max_multiplier {
+        return Err(AppError::Validation(format!(
+            "입력한 단가가 기본 단가의 {max_multiplier}배를 초과합니다."
+        )));
+    }
+    if let Some(cost) = cost_price {
+        if unit_price < cost && !read_setting_bool(conn, "allow_below_cost_sales", false)? {
+            return Err(AppError::Validation("입력한 단가가 원가보다 낮습니다.".into()));
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn update_sale(state: State<DbState>, payload: SaleUpdatePayload) -> CommandResult<AppData> {
+    let note = clean_text(payload.note.as_deref());
+    if payload.qty <= 0.0 {
+        return Err(AppError::Validation("미터은 0보다 커야 합니다.".into()).into());
+    }
+    ensure_text_len("note", note, MAX_NOTE_LEN).map_err(map_app_err)?;
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    // Fetch sale
+    let row = tx
+        .query_row(
+            "SELECT product_id, qty, is_return, invoice_id, is_credit, customer_id FROM sales WHERE id = ?",
+            params![payload.id],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, i64>(2)? != 0,
+                    row.get::<_, Option<i64>>(3)?,
+                    row.get::<_, i64>(4)? != 0,
+                    row.get::<_, Option<i64>>(5)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+    let (product_id, prev_qty, is_return, invoice_id, was_credit, prev_customer_id) = match row {
+        Some(v) => v,
+        None => {
+            return Err(AppError::Validation("존재하지 않는 판매입니다.".into()).into());
+        }
+    };
+    if is_return {
+        return Err(AppError::Validation("반품 내역은 수정할 수 없습니다.".into()).into());
+    }
+    if invoice_id.is_some() {
+        return Err(
+            AppError::Validation("묶음 판매의 품목은 개별 수정할 수 없습니다.".into()).into(),
+        );
+    }
+    // Block if returns exist
+    let has_return = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM sales WHERE origin_sale_id = ? AND is_return = 1)",
+            params![payload.id],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(map_sql_err)?
+        != 0;
+    if has_return {
+        return Err(AppError::Validation("반품이 등록된 판매는 수정할 수 없습니다.".into()).into());
+    }
+    // a hold stops new credit only, so an existing credit sale stays editable
+    if payload.is_credit {
+        match payload.customer_id {
+            None => {
+                return Err(
+                    AppError::Validation("외상 거래에는 고객을 선택해야 합니다.".into()).into(),
+                );
+            }
+            Some(customer_id) if !was_credit || prev_customer_id != Some(customer_id) => {
+                ensure_credit_allowed(&tx, customer_id).map_err(map_app_err)?;
+            }
+            Some(_) => {}
+        }
+    }
+    // Adjust stock
+    let qty_delta = payload.qty - prev_qty;
+    if qty_delta > 0.0 {
+        // need more stock available
+        let available: f64 = tx
+            .query_row(
+                "SELECT qty FROM products WHERE id = ?",
+                params![product_id],
+                |row| row.get(0),
+            )
+            .map_err(map_sql_err)?;
+        ensure_stock_available(&tx, product_id, available, qty_delta).map_err(map_app_err)?;
+    }
+    tx.execute(
+        "UPDATE products SET qty = qty - ? WHERE id = ?",
+        params![qty_delta, product_id],
+    )
+    .map_err(map_sql_err)?;
+    shift_lots(&tx, product_id, -qty_delta).map_err(map_app_err)?;
+    let decimals = amount_decimals(&tx).map_err(map_app_err)?;
+    let line_amount = round_amount(payload.unit_price * payload.qty, decimals);
+    let (total_amount, rounding_adjustment) =
+        charged_amount(&tx, line_amount, payload.is_credit).map_err(map_app_err)?;
+    let ts = now_iso();
+    // Update sale
+    tx.execute(
+        "UPDATE sales SET qty = ?, price_snapshot = ?, total_amount = ?, rounding_adjustment = ?, customer_id = ?, note = ?, is_credit = ? WHERE id = ?",
+        params![
+            payload.qty,
+            payload.unit_price,
+            total_amount,
+            rounding_adjustment,
+            payload.customer_id,
+            note,
+            if payload.is_credit { 1 } else { 0 },
+            payload.id
+        ],
+    )
+    .map_err(map_sql_err)?;
+    snapshot_sale_customer(&tx, payload.id).map_err(map_app_err)?;
+    // Update transaction row
+    tx.execute(
+        "UPDATE transactions SET qty = ?, unit_price = ?, total_amount = ?, customer_id = ?, note = ? WHERE sale_id = ? AND kind = 'OUT'",
+        params![
+            payload.qty,
+            payload.unit_price,
+            total_amount,
+            payload.customer_id,
+            note,
+            payload.id
+        ],
+    )
+    .map_err(map_sql_err)?;
+    // Update credit
+    let credit_exists: bool = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM credits WHERE sale_id = ? AND is_payment = 0)",
+            params![payload.id],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(map_sql_err)?
+        != 0;
+    if payload.is_credit {
+        if credit_exists {
+            tx.execute(
+                "UPDATE credits SET customer_id = ?, amount = ?, note = ? WHERE sale_id = ? AND is_payment = 0",
+                params![
+                    payload.customer_id,
+                    total_amount,
+                    note,
+                    payload.id
+                ],
+            )
+            .map_err(map_sql_err)?;
+            // payments already taken against this sale follow it to the new customer
+            tx.execute(
+                "UPDATE credits SET customer_id = ? WHERE sale_id = ? AND is_payment = 1 AND return_id IS NULL",
+                params![payload.customer_id, payload.id],
+            )
+            .map_err(map_sql_err)?;
+        } else if payload.customer_id.is_some() {
+            tx.execute(
+                "INSERT INTO credits (ts, customer_id, sale_id, amount, is_payment, note) VALUES (?, ?, ?, ?, 0, ?)",
+                params![ts, payload.customer_id, payload.id, total_amount, note],
+            )
+            .map_err(map_sql_err)?;
+        }
+    } else if credit_exists {
+        // the charge and its discounts go; money already taken stays on the customer as a
+        // standalone payment
+        tx.execute(
+            "DELETE FROM credits WHERE sale_id = ? AND (is_payment = 0 OR is_discount = 1)",
+            params![payload.id],
+        )
+        .map_err(map_sql_err)?;
+        tx.execute(
+            "UPDATE credits SET sale_id = NULL WHERE sale_id = ? AND return_id IS NULL",
+            params![payload.id],
+        )
+        .map_err(map_sql_err)?;
+    }
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+// fixes a mistyped price after the goods have moved; stock is left untouched
+#[tauri::command]
+fn correct_sale_price(
+    state: State<DbState>,
+    sale_id: i64,
+    new_unit_price: f64,
+) -> CommandResult<AppData> {
+    if new_unit_price < 0.0 {
+        return Err(AppError::Validation("단가는 0 이상이어야 합니다.".into()).into());
+    }
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    let row = tx
+        .query_row(
+            "SELECT s.qty, s.is_return, s.invoice_id, p.unit_price, p.cost_price, s.is_credit
+             FROM sales s
+             JOIN products p ON p.id = s.product_id
+             WHERE s.id = ?",
+            params![sale_id],
+            |row| {
+                Ok((
+                    row.get::<_, f64>(0)?,
+                    row.get::<_, i64>(1)? != 0,
+                    row.get::<_, Option<i64>>(2)?,
+                    row.get::<_, f64>(3)?,
+                    row.get::<_, Option<f64>>(4)?,
+                    row.get::<_, i64>(5)? != 0,
+                ))
+            },
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+    let (qty, is_return, invoice_id, list_price, cost_price, is_credit) = match row {
+        Some(v) => v,
+        None => {
+            return Err(AppError::Validation("존재하지 않는 판매입니다.".into()).into());
+        }
+    };
+    if is_return {
+        return Err(AppError::Validation("반품 내역은 수정할 수 없습니다.".into()).into());
+    }
+    if invoice_id.is_some() {
+        return Err(
+            AppError::Validation("묶음 판매의 품목은 개별 수정할 수 없습니다.".into()).into(),
+        );
+    }
+    let has_return = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM sales WHERE origin_sale_id = ? AND is_return = 1)",
+            params![sale_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(map_sql_err)?
+        != 0;
+    if has_return {
+        return Err(AppError::Validation("반품이 등록된 판매는 수정할 수 없습니다.".into()).into());
+    }
+    validate_price_override(&tx, new_unit_price, list_price, cost_price).map_err(map_app_err)?;
+
+    let decimals = amount_decimals(&tx).map_err(map_app_err)?;
+    let line_amount = round_amount(new_unit_price * qty, decimals);
+    let (total_amount, rounding_adjustment) =
+        charged_amount(&tx, line_amount, is_credit).map_err(map_app_err)?;
+    tx.execute(
+        "UPDATE sales SET price_snapshot = ?, total_amount = ?, rounding_adjustment = ? WHERE id = ?",
+        params![new_unit_price, total_amount, rounding_adjustment, sale_id],
+    )
+    .map_err(map_sql_err)?;
+    tx.execute(
+        "UPDATE transactions SET unit_price = ?, total_amount = ? WHERE sale_id = ? AND kind = 'OUT'",
+        params![new_unit_price, total_amount, sale_id],
+    )
+    .map_err(map_sql_err)?;
+    tx.execute(
+        "UPDATE credits SET amount = ? WHERE sale_id = ? AND is_payment = 0",
+        params![total_amount, sale_id],
+    )
+    .map_err(map_sql_err)?;
+
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+#[tauri::command]
+fn delete_sale(state: State<DbState>, sale_id: i64) -> CommandResult<AppData> {
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    // fetch sale
+    let row = tx
+        .query_row(
+            "SELECT product_id, qty, is_return, invoice_id FROM sales WHERE id = ?",
+            params![sale_id],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, i64>(2)? != 0,
+                    row.get::<_, Option<i64>>(3)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+    let (product_id, qty, is_return, invoice_id) = match row {
+        Some(v) => v,
+        None => {
+            return Err(AppError::Validation("존재하지 않는 판매입니다.".into()).into());
+        }
+    };
+    if is_return {
+        return Err(AppError::Validation("반품 내역은 삭제할 수 없습니다.".into()).into());
+    }
+    if invoice_id.is_some() {
+        return Err(
+            AppError::Validation("묶음 판매의 품목은 개별 삭제할 수 없습니다.".into()).into(),
+        );
+    }
+    let has_return = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM sales WHERE origin_sale_id = ? AND is_return = 1)",
+            params![sale_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(map_sql_err)?
+        != 0;
+    if has_return {
+        return Err(AppError::Validation("반품이 등록된 판매는 삭제할 수 없습니다.".into()).into());
+    }
+    // revert stock
+    tx.execute(
+        "UPDATE products SET qty = qty + ? WHERE id = ?",
+        params![qty, product_id],
+    )
+    .map_err(map_sql_err)?;
+    restore_lots(&tx, product_id, qty).map_err(map_app_err)?;
+    // delete movement
+    tx.execute(
+        "DELETE FROM transactions WHERE sale_id = ?",
+        params![sale_id],
+    )
+    .map_err(map_sql_err)?;
+    // delete the charge and its discounts; payments taken against the sale were real money, so
+    // the sale_id foreign key detaches them into standalone payments
+    tx.execute(
+        "DELETE FROM credits WHERE sale_id = ? AND (is_payment = 0 OR is_discount = 1)",
+        params![sale_id],
+    )
+    .map_err(map_sql_err)?;
+    // delete sale
+    tx.execute("DELETE FROM sales WHERE id = ?", params![sale_id])
+        .map_err(map_sql_err)?;
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+// the invoice counterpart of delete_sale: every line, its movement and the invoice's charge go together
+#[tauri::command]
+fn void_invoice(state: State<DbState>, invoice_id: i64) -> CommandResult<AppData> {
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    let exists = tx
+        .query_row(
+            "SELECT 1 FROM invoices WHERE id = ?",
+            params![invoice_id],
+            |_| Ok(()),
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+    if exists.is_none() {
+        return Err(AppError::Validation("존재하지 않는 묶음 판매입니다.".into()).into());
+    }
+    let has_return = tx
+        .query_row(
+            "SELECT EXISTS(
+                SELECT 1 FROM sales r
+                JOIN sales s ON s.id = r.origin_sale_id
+                WHERE s.invoice_id = ? AND r.is_return = 1
+            )",
+            params![invoice_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(map_sql_err)?
+        != 0;
+    if has_return {
+        return Err(
+            AppError::Validation("반품이 등록된 묶음 판매는 취소할 수 없습니다.".into()).into(),
+        );
+    }
+
+    let lines = {
+        let mut stmt = tx
+            .prepare("SELECT id, product_id, qty FROM sales WHERE invoice_id = ? AND is_return = 0")
+            .map_err(map_sql_err)?;
+        let rows = stmt
+            .query_map(params![invoice_id], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, f64>(2)?,
+                ))
+            })
+            .map_err(map_sql_err)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(map_sql_err)?
+    };
+    for (sale_id, product_id, qty) in lines {
+        tx.execute(
+            "UPDATE products SET qty = qty + ? WHERE id = ?",
+            params![qty, product_id],
+        )
+        .map_err(map_sql_err)?;
+        restore_lots(&tx, product_id, qty).map_err(map_app_err)?;
+        tx.execute(
+            "DELETE FROM transactions WHERE sale_id = ?",
+            params![sale_id],
+        )
+        .map_err(map_sql_err)?;
+        tx.execute("DELETE FROM sales WHERE id = ?", params![sale_id])
+            .map_err(map_sql_err)?;
+    }
+    // the charge and its discounts go; payments already taken stay on the customer as
+    // standalone payments
+    tx.execute(
+        "DELETE FROM credits WHERE invoice_id = ? AND (is_payment = 0 OR is_discount = 1)",
+        params![invoice_id],
+    )
+    .map_err(map_sql_err)?;
+    tx.execute(
+        "UPDATE credits SET invoice_id = NULL WHERE invoice_id = ?",
+        params![invoice_id],
+    )
+    .map_err(map_sql_err)?;
+    tx.execute("DELETE FROM invoices WHERE id = ?", params![invoice_id])
+        .map_err(map_sql_err)?;
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+#[derive(Debug, Serialize)]
+struct ReturnOrigin {
+    sale_id: i64,
+    ts: String,
+    qty: f64,
+    unit_price: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct ReturnRecord {
+    return_sale: SaleRecord,
+    // None when the original sale has since been deleted
+    origin: Option<ReturnOrigin>,
+}
+
+// the returns ledger alone, newest first, each with what was originally sold
+#[tauri::command]
+fn fetch_returns(
+    state: State<DbState>,
+    from: Option<String>,
+    to: Option<String>,
+) -> CommandResult<Vec<ReturnRecord>> {
+    let conn = state.open().map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let (from, to) = resolve_date_range(tz, from.as_deref(), to.as_deref()).map_err(map_app_err)?;
+    let sql = format!(
+        "{SALE_SELECT}
+         WHERE s.is_return = 1
+           AND (?1 IS NULL OR s.ts >= ?1)
+           AND (?2 IS NULL OR s.ts < ?2)
+         ORDER BY s.ts DESC, s.id DESC"
+    );
+    let mut stmt = conn.prepare(&sql).map_err(map_sql_err)?;
+    let rows = stmt
+        .query_map(params![from, to], sale_from_row)
+        .map_err(map_sql_err)?;
+    let mut origin_stmt = conn
+        .prepare("SELECT id, ts, qty, price_snapshot FROM sales WHERE id = ?")
+        .map_err(map_sql_err)?;
+
+    let mut returns = Vec::new();
+    for row in rows {
+        let return_sale = row.map_err(map_sql_err)?;
+        let origin = match return_sale.origin_sale_id {
+            Some(origin_id) => origin_stmt
+                .query_row(params![origin_id], |row| {
+                    Ok(ReturnOrigin {
+                        sale_id: row.get(0)?,
+                        ts: row.get(1)?,
+                        qty: row.get(2)?,
+                        unit_price: row.get(3)?,
+                    })
+                })
+                .optional()
+                .map_err(map_sql_err)?,
+            None => None,
+        };
+        returns.push(ReturnRecord {
+            return_sale,
+            origin,
+        });
+    }
+    Ok(returns)
+}
+
+#[derive(Debug, Deserialize)]
+struct ReturnUpdatePayload {
+    id: i64, // return sale id
+    qty: f64,
+    note: Option<String>,
+    override_amount: Option<f64>,
+}
+
+#[tauri::command]
+fn update_return(state: State<DbState>, payload: ReturnUpdatePayload) -> CommandResult<AppData> {
+    if payload.qty <= 0.0 {
+        return Err(AppError::Validation("반품 수량은 0보다 커야 합니다.".into()).into());
+    }
+    ensure_text_len("note", payload.note.as_deref(), MAX_NOTE_LEN).map_err(map_app_err)?;
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    // fetch return sale
+    let row = tx
+        .query_row(
+            "SELECT product_id, qty, price_snapshot, customer_id, is_credit FROM sales WHERE id = ? AND is_return = 1",
+            params![payload.id],
+            |row| Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, f64>(1)?,
+                row.get::<_, f64>(2)?,
+                row.get::<_, Option<i64>>(3)?,
+                row.get::<_, i64>(4)? != 0,
+            )),
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+    let (product_id, prev_qty, prev_unit, customer_id, was_credit) = match row {
+        Some(v) => v,
+        None => return Err(AppError::Validation("존재하지 않는 반품입니다.".into()).into()),
+    };
+    let qty_delta = payload.qty - prev_qty;
+    let pool = return_stock_pool(&tx, payload.id).map_err(map_app_err)?;
+    if qty_delta < 0.0 {
+        ensure_pool_available(&tx, product_id, pool, -qty_delta).map_err(map_app_err)?;
+    }
+    tx.execute(
+        &format!("UPDATE products SET {pool} = {pool} + ? WHERE id = ?"),
+        params![qty_delta, product_id],
+    )
+    .map_err(map_sql_err)?;
+    if pool == "qty" {
+        shift_lots(&tx, product_id, qty_delta).map_err(map_app_err)?;
+    }
+    let unit = payload
+        .override_amount
+        .map(|v| v / payload.qty)
+        .unwrap_or(prev_unit);
+    let decimals = amount_decimals(&tx).map_err(map_app_err)?;
+    let total = round_amount(unit * payload.qty, decimals);
+    ensure_return_note(&tx, payload.qty, total, payload.note.as_deref()).map_err(map_app_err)?;
+    tx.execute(
+        "UPDATE sales SET qty = ?, price_snapshot = ?, total_amount = ?, note = ? WHERE id = ?",
+        params![payload.qty, unit, total, payload.note.as_deref(), payload.id],
+    )
+    .map_err(map_sql_err)?;
+    tx.execute(
+        "UPDATE transactions SET qty = ?, unit_price = ?, total_amount = ?, note = ? WHERE sale_id = ? AND kind = 'RETURN'",
+        params![payload.qty, unit, total, payload.note.as_deref(), payload.id],
+    )
+    .map_err(map_sql_err)?;
+    // credit update to reflect new return amount (update linked payment instead of diff row)
+    if was_credit {
+        // set credit amount for this specific return (if present)
+        tx.execute(
+            "UPDATE credits SET amount = ?, note = ? WHERE return_id = ?",
+            params![total, payload.note.as_deref(), payload.id],
+        )
+        .map_err(map_sql_err)?;
+    }
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+#[tauri::command]
+fn delete_return(state: State<DbState>, return_id: i64) -> CommandResult<AppData> {
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    let row = tx
+        .query_row(
+            "SELECT product_id, qty, total_amount, customer_id, is_credit FROM sales WHERE id = ? AND is_return = 1",
+            params![return_id],
+            |row| Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, f64>(1)?,
+                row.get::<_, f64>(2)?,
+                row.get::<_, Option<i64>>(3)?,
+                row.get::<_, i64>(4)? != 0,
+            )),
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+    let (product_id, qty, total_amount, customer_id, was_credit) = match row {
+        Some(v) => v,
+        None => return Err(AppError::Validation("존재하지 않는 반품입니다.".into()).into()),
+    };
+    let pool = return_stock_pool(&tx, return_id).map_err(map_app_err)?;
+    ensure_pool_available(&tx, product_id, pool, qty).map_err(map_app_err)?;
+    tx.execute(
+        &format!("UPDATE products SET {pool} = {pool} - ? WHERE id = ?"),
+        params![qty, product_id],
+    )
+    .map_err(map_sql_err)?;
+    if pool == "qty" {
+        consume_lots(&tx, product_id, qty).map_err(map_app_err)?;
+    }
+    tx.execute("DELETE FROM transactions WHERE sale_id = ?", params![return_id])
+        .map_err(map_sql_err)?;
+    tx.execute("DELETE FROM sales WHERE id = ?", params![return_id])
+        .map_err(map_sql_err)?;
+    if was_credit {
+        // remove the payment that was created for this return
+        tx.execute("DELETE FROM credits WHERE return_id = ?", params![return_id])
+            .map_err(map_sql_err)?;
+    }
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+// products column a return's quantity went into
+fn return_stock_pool(conn: &Connection, return_id: i64) -> Result<&'static str, AppError> {
+    let to_damaged = conn
+        .query_row(
+            "SELECT to_damaged FROM transactions WHERE sale_id = ? AND kind = 'RETURN' LIMIT 1",
+            params![return_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+        .unwrap_or(0)
+        != 0;
+    Ok(if to_damaged { "damaged_qty" } else { "qty" })
+}
+
+// a shrinking or deleted return takes its goods back out of the pool they went into; damaged
+// stock may already have been written off or repaired, so it can run short
+fn ensure_pool_available(
+    conn: &Connection,
+    product_id: i64,
+    pool: &str,
+    qty: f64,
+) -> Result<(), AppError> {
+    let available = conn.query_row(
+        &format!("SELECT {pool} FROM products WHERE id = ?"),
+        params![product_id],
+        |row| row.get::<_, f64>(0),
+    )?;
+    if pool == "qty" {
+        ensure_stock_available(conn, product_id, available, qty)
+    } else if available + f64::EPSILON < qty {
+        Err(AppError::Validation("불량 재고가 부족합니다.".into()))
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum DamagedStockAction {
+    WriteOff,
+    Repair,
+}
+
+// clears damaged stock: a write-off discards it through a to_damaged OUT, a repair moves it back
+// into sellable qty through an ADJUST movement so recompute_quantities still balances
+#[tauri::command]
+fn resolve_damaged_stock(
+    state: State<DbState>,
+    product_id: i64,
+    qty: f64,
+    action: DamagedStockAction,
+    note: Option<String>,
+) -> CommandResult<AppData> {
+    if qty <= 0.0 {
+        return Err(AppError::Validation("수량은 0보다 커야 합니다.".into()).into());
+    }
+    ensure_text_len("note", note.as_deref(), MAX_NOTE_LEN).map_err(map_app_err)?;
+
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    let damaged_qty = tx
+        .query_row(
+            "SELECT damaged_qty FROM products WHERE id = ?",
+            params![product_id],
+            |row| row.get::<_, f64>(0),
+        )
+        .optional()
+        .map_err(map_sql_err)?
+        .ok_or_else(|| AppError::Validation("존재하지 않는 품명입니다.".into()).to_string())?;
+    if damaged_qty + f64::EPSILON < qty {
+        return Err(AppError::Validation("불량 재고가 부족합니다.".into()).into());
+    }
+
+    tx.execute(
+        "UPDATE products SET damaged_qty = damaged_qty - ? WHERE id = ?",
+        params![qty, product_id],
+    )
+    .map_err(map_sql_err)?;
+    if matches!(action, DamagedStockAction::Repair) {
+        tx.execute(
+            "UPDATE products SET qty = qty + ? WHERE id = ?",
+            params![qty, product_id],
+        )
+        .map_err(map_sql_err)?;
+        restore_lots(&tx, product_id, qty).map_err(map_app_err)?;
+        tx.execute(
+            "INSERT INTO transactions (ts, kind, product_id, qty, note) VALUES (?, 'ADJUST', ?, ?, ?)",
+            params![
+                now_iso(),
+                product_id,
+                qty,
+                note.as_deref().unwrap_or("불량 반품 수리 후 재입고")
+            ],
+        )
+        .map_err(map_sql_err)?;
+    } else {
+        // flagged to_damaged so the ledger shows the disposal without touching sellable qty
+        tx.execute(
+            "INSERT INTO transactions (ts, kind, product_id, qty, note, to_damaged) VALUES (?, 'OUT', ?, ?, ?, 1)",
+            params![
+                now_iso(),
+                product_id,
+                qty,
+                note.as_deref().unwrap_or("불량 재고 폐기")
+            ],
+        )
+        .map_err(map_sql_err)?;
+    }
+
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+#[tauri::command]
+fn record_return(
+    app: tauri::AppHandle,
+    state: State<DbState>,
+    payload: ReturnPayload,
+) -> CommandResult<AppData> {
+    let note = clean_text(payload.note.as_deref());
+    let mut errors = Vec::new();
+    if payload.qty <= 0.0 {
+        errors.push(field_error("qty", "반품 수량은 0보다 커야 합니다."));
+    }
+    if payload.override_amount.is_some_and(|amount| amount < 0.0) {
+        errors.push(field_error(
+            "override_amount",
+            "반품 금액은 0 이상이어야 합니다.",
+        ));
+    }
+    check_text_len(&mut errors, "note", note, MAX_NOTE_LEN);
+    check_fields(errors).map_err(map_app_err)?;
+
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+
+    ensure_qty_step(&tx, payload.product_id, payload.qty).map_err(map_app_err)?;
+    let outstanding_sales = fetch_returnable_sales(&tx, payload.product_id, payload.customer_id)
+        .map_err(map_app_err)?;
+    let total_available: f64 = outstanding_sales
+        .iter()
+        .map(|entry| entry.returnable_qty)
+        .sum();
+
+    if outstanding_sales.is_empty() {
+        return Err(AppError::Validation("반품 가능한 판매 내역이 없습니다.".into()).into());
+    }
+
+    if total_available + f64::EPSILON < payload.qty {
+        return Err(AppError::Validation("반품 수량이 남은 수량을 초과했습니다.".into()).into());
+    }
+
+    let ts = now_iso();
+    let restock = payload.restock.unwrap_or(true);
+
+    if restock {
+        tx.execute(
+            "UPDATE products SET qty = qty + ? WHERE id = ?",
+            params![payload.qty, payload.product_id],
+        )
+        .map_err(map_sql_err)?;
+        restore_lots(&tx, payload.product_id, payload.qty).map_err(map_app_err)?;
+    } else {
+        tx.execute(
+            "UPDATE products SET damaged_qty = damaged_qty + ? WHERE id = ?",
+            params![payload.qty, payload.product_id],
+        )
+        .map_err(map_sql_err)?;
+    }
+
+    let mut remaining_qty = payload.qty;
+    let mut computed_total = 0.0;
+    let mut return_ids = Vec::new();
+    for entry in outstanding_sales {
+        if remaining_qty <= 0.0 {
+            break;
+        }
+        let portion = remaining_qty.min(entry.returnable_qty);
+        let (return_id, return_total) = insert_return_for_sale(
+            &tx,
+            &ts,
+            payload.product_id,
+            portion,
+            entry.price_snapshot,
+            entry.customer_id,
+            entry.is_credit,
+            entry.sale_id,
+            note,
+            restock,
+        )
+        .map_err(map_app_err)?;
+        return_ids.push(return_id);
+        computed_total += return_total;
+        remaining_qty -= portion;
+    }
+
+    ensure_return_note(
+        &tx,
+        payload.qty,
+        payload.override_amount.unwrap_or(computed_total),
+        note,
+    )
+    .map_err(map_app_err)?;
+
+    // If an override amount is provided and differs from computed_total, add an adjustment credit/payment
+    if let Some(override_amount) = payload.override_amount {
+        let decimals = amount_decimals(&tx).map_err(map_app_err)?;
+        let diff = round_amount(override_amount - computed_total, decimals);
+        if diff.abs() > f64::EPSILON {
+            if let Some(cid) = payload.customer_id {
+                tx.execute(
+                    "INSERT INTO credits (ts, customer_id, sale_id, amount, is_payment, note) VALUES (?, ?, NULL, ?, ?, ?)",
+                    params![
+                        ts,
+                        cid,
+                        diff.abs(),
+                        if diff > 0.0 { 0 } else { 1 },
+                        Some("반품 금액 조정")
+                    ],
+                )
+                .map_err(map_sql_err)?;
+            }
+        }
+    }
+
+    tx.commit().map_err(map_sql_err)?;
+    // one return can split across several original sales, so the payload is a SaleRecord list
+    let returns: Vec<SaleRecord> = return_ids
+        .iter()
+        .filter_map(|id| fetch_sale(&conn, *id).ok().flatten())
+        .collect();
+    let _ = app.emit("return-recorded", returns);
+    load_app_data(&state).map_err(Into::into)
+}
+
+#[derive(Debug, Serialize)]
+struct ReturnableSale {
+    sale_id: i64,
+    ts: String,
+    price_snapshot: f64,
+    customer_id: Option<i64>,
+    is_credit: bool,
+    returnable_qty: f64,
+}
+
+#[tauri::command]
+fn returnable_sales(
+    state: State<DbState>,
+    product_id: i64,
+    customer_id: Option<i64>,
+) -> CommandResult<Vec<ReturnableSale>> {
+    let conn = state.open().map_err(map_app_err)?;
+    fetch_returnable_sales(&conn, product_id, customer_id).map_err(Into::into)
+}
+
+// original sales that still have quantity left to return, oldest first (the order returns consume them)
+fn fetch_returnable_sales(
+    conn: &Connection,
+    product_id: i64,
+    customer_id: Option<i64>,
+) -> Result<Vec<ReturnableSale>, AppError> {
+    let sql = "
+        SELECT
+            s.id,
+            s.ts,
+            s.price_snapshot,
+            s.customer_id,
+            s.is_credit,
+            s.qty - IFNULL(SUM(r.qty), 0) AS returnable
+        FROM sales s
+        LEFT JOIN sales r ON r.origin_sale_id = s.id AND r.is_return = 1
+        WHERE s.product_id = ?1
+          AND (
+                (?2 IS NULL AND s.customer_id IS NULL) OR
+                (?2 IS NOT NULL AND s.customer_id = ?2)
+              )
+          AND s.is_return = 0
+        GROUP BY s.id, s.ts, s.qty, s.price_snapshot, s.is_credit, s.customer_id
+        HAVING s.qty - IFNULL(SUM(r.qty), 0) > 0
+        ORDER BY s.ts ASC
+    ";
+
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map(params![product_id, customer_id], |row| {
+        Ok(ReturnableSale {
+            sale_id: row.get(0)?,
+            ts: row.get(1)?,
+            price_snapshot: row.get(2)?,
+            customer_id: row.get(3)?,
+            is_credit: row.get::<_, i64>(4)? != 0,
+            returnable_qty: row.get(5)?,
+        })
+    })?;
+
+    let mut sales = Vec::new();
+    for row in rows {
+        sales.push(row?);
+    }
+    Ok(sales)
+}
+
+const DEFAULT_RETURN_NOTE_QTY_THRESHOLD: f64 = 10.0;
+const DEFAULT_RETURN_NOTE_AMOUNT_THRESHOLD: f64 = 100_000.0;
+
+// large returns must carry an explanation for the audit trail
+fn ensure_return_note(
+    conn: &Connection,
+    qty: f64,
+    amount: f64,
+    note: Option<&str>,
+) -> Result<(), AppError> {
+    if has_text(note) {
+        return Ok(());
+    }
+    let qty_threshold = read_setting_f64(
+        conn,
+        "return_note_qty_threshold",
+        DEFAULT_RETURN_NOTE_QTY_THRESHOLD,
+    )?;
+    let amount_threshold = read_setting_f64(
+        conn,
+        "return_note_amount_threshold",
+        DEFAULT_RETURN_NOTE_AMOUNT_THRESHOLD,
+    )?;
+    let is_large = (qty_threshold > 0.0 && qty >= qty_threshold)
+        || (amount_threshold > 0.0 && amount.abs() >= amount_threshold);
+    if is_large {
+        return Err(AppError::Validation(format!(
+            "수량 {qty_threshold} 이상 또는 금액 {amount_threshold} 이상인 반품은 사유(메모)를 입력해야 합니다."
+        )));
+    }
+    Ok(())
+}
+
+fn insert_return_for_sale(
+    tx: &rusqlite::Transaction<'_>,
+    ts: &str,
+    product_id: i64,
+    qty: f64,
+    price_snapshot: f64,
+    customer_id: Option<i64>,
+    was_credit: bool,
+    origin_sale_id: i64,
+    note: Option<&str>,
+    restock: bool,
+) -> Result<(i64, f64), AppError> {
+    let total_amount = round_amount(price_snapshot * qty, amount_decimals(tx)?);
+
+    tx.execute(
+        "INSERT INTO sales (ts, product_id, qty, price_snapshot, total_amount, customer_id, note, is_credit, is_return, origin_sale_id, cost_snapshot, tax_rate)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, 1, ?9, (SELECT cost_snapshot FROM sales WHERE id = ?9), (SELECT tax_rate FROM sales WHERE id = ?9))",
+        params![
+            ts,
+            product_id,
+            qty,
+            price_snapshot,
+            total_amount,
+            customer_id,
+            note,
+            if was_credit { 1 } else { 0 },
+            origin_sale_id
+        ],
+    )?;
+
+    let return_sale_id = tx.last_insert_rowid();
+    snapshot_sale_customer(tx, return_sale_id)?;
+
+    tx.execute(
+        "INSERT INTO transactions (ts, kind, product_id, qty, unit_price, total_amount, customer_id, note, sale_id, to_damaged)
+         VALUES (?, 'RETURN', ?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            ts,
+            product_id,
+            qty,
+            price_snapshot,
+            total_amount,
+            customer_id,
+            note,
+            return_sale_id,
+            if restock { 0 } else { 1 }
+        ],
+    )?;
+
+    if was_credit {
+        if let Some(cid) = customer_id {
+            let credit_note = note.unwrap_or("반품 정산");
+            tx.execute(
+                "INSERT INTO credits (ts, customer_id, sale_id, return_id, amount, is_payment, note)
+                 VALUES (?, ?, ?, ?, ?, 1, ?)",
+                params![ts, cid, origin_sale_id, return_sale_id, total_amount, credit_note],
+            )?;
+        }
+    }
+
+    Ok((return_sale_id, total_amount))
+}
+
+
+#[derive(Debug, Deserialize)]
+struct CreditPaymentPayload {
+    customer_id: i64,
+    amount: f64,
+    note: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct CreditPaymentResult {
+    requested_amount: f64,
+    // what was actually handed over after cash rounding
+    rounded_amount: f64,
+    data: AppData,
+}
+
+// the payment row holds the rounded cash; the difference is booked as an adjustment so the
+// balance still drops by exactly the requested amount
+#[tauri::command]
+fn record_credit_payment(
+    state: State<DbState>,
+    payload: CreditPaymentPayload,
+) -> CommandResult<CreditPaymentResult> {
+    let note = clean_text(payload.note.as_deref());
+    if payload.amount <= 0.0 {
+        return Err(AppError::Validation("결제 금액은 0보다 커야 합니다.".into()).into());
+    }
+    ensure_text_len("note", note, MAX_NOTE_LEN).map_err(map_app_err)?;
+
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+
+    let exists = tx
+        .query_row(
+            "SELECT 1 FROM customers WHERE id = ?",
+            params![payload.customer_id],
+            |_| Ok(()),
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+
+    if exists.is_none() {
+        return Err(AppError::Validation("존재하지 않는 고객입니다.".into()).into());
+    }
+
+    let decimals = amount_decimals(&tx).map_err(map_app_err)?;
+    let requested_amount = round_amount(payload.amount, decimals);
+    let rounded_amount = round_cash(&tx, requested_amount).map_err(map_app_err)?;
+    if rounded_amount <= 0.0 {
+        return Err(
+            AppError::Validation("현금 단수 처리 후 결제 금액이 0이 됩니다.".into()).into(),
+        );
+    }
+    let ts = now_iso();
+    tx.execute(
+        "INSERT INTO credits (ts, customer_id, sale_id, amount, is_payment, note) VALUES (?, ?, NULL, ?, 1, ?)",
+        params![
+            ts,
+            payload.customer_id,
+            rounded_amount,
+            note
+        ],
+    )
+    .map_err(map_sql_err)?;
+    // short of the request it is written off as a payment, over it is charged back
+    let difference = round_amount(requested_amount - rounded_amount, decimals);
+    if difference != 0.0 {
+        tx.execute(
+            "INSERT INTO credits (ts, customer_id, amount, is_payment, note, is_adjustment) VALUES (?, ?, ?, ?, ?, 1)",
+            params![
+                ts,
+                payload.customer_id,
+                difference.abs(),
+                if difference > 0.0 { 1 } else { 0 },
+                "현금 단수 조정"
+            ],
+        )
+        .map_err(map_sql_err)?;
+    }
+    tx.commit().map_err(map_sql_err)?;
+
+    Ok(CreditPaymentResult {
+        requested_amount,
+        rounded_amount,
+        data: load_app_data(&state).map_err(map_app_err)?,
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct SaleSettlement {
+    // exactly one of sale_id / invoice_id is set; an invoice is settled as a whole
+    sale_id: Option<i64>,
+    invoice_id: Option<i64>,
+    sale_ts: String,
+    amount: f64,
+    // still owed on the sale after this payment
+    remaining: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct CustomerSettlement {
+    customer_id: i64,
+    settled: Vec<SaleSettlement>,
+    // booked as a standalone payment once every credit sale is paid off
+    unapplied: f64,
+}
+
+// a lump payment applied oldest sale or invoice first, one payment row per item it reaches, so
+// per-item balances go down along with the customer total
+#[tauri::command]
+fn settle_customer(
+    state: State<DbState>,
+    customer_id: i64,
+    amount: f64,
+    note: Option<String>,
+) -> CommandResult<CustomerSettlement> {
+    if amount <= 0.0 {
+        return Err(AppError::Validation("결제 금액은 0보다 커야 합니다.".into()).into());
+    }
+    let note = clean_text(note.as_deref());
+    ensure_text_len("note", note, MAX_NOTE_LEN).map_err(map_app_err)?;
+
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    let customer_exists = tx
+        .query_row(
+            "SELECT 1 FROM customers WHERE id = ?",
+            params![customer_id],
+            |_| Ok(()),
+        )
+        .optional()
+        .map_err(map_sql_err)?
+        .is_some();
+    if !customer_exists {
+        return Err(AppError::Validation("존재하지 않는 고객입니다.".into()).into());
+    }
+
+    let decimals = amount_decimals(&tx).map_err(map_app_err)?;
+    // same remaining figure settle_sale pays off, plus credit invoices charged as one row
+    let outstanding = {
+        let mut stmt = tx
+            .prepare(
+                "SELECT s.id, NULL, s.ts,
+                        IFNULL(SUM(CASE WHEN cr.is_payment = 0 THEN cr.amount ELSE -cr.amount END), 0) AS owed
+                 FROM sales s
+                 JOIN credits cr ON cr.sale_id = s.id
+                 WHERE s.customer_id = ?1 AND s.is_credit = 1 AND s.is_return = 0
+                 GROUP BY s.id, s.ts
+                 HAVING owed > 0
+                 UNION ALL
+                 SELECT NULL, i.id, i.ts,
+                        IFNULL(SUM(CASE WHEN cr.is_payment = 0 THEN cr.amount ELSE -cr.amount END), 0) AS owed
+                 FROM invoices i
+                 JOIN credits cr ON cr.invoice_id = i.id
+                 WHERE cr.customer_id = ?1 AND i.is_credit = 1
+                 GROUP BY i.id, i.ts
+                 HAVING owed > 0
+                 ORDER BY 3, 1, 2",
+            )
+            .map_err(map_sql_err)?;
+        let rows = stmt
+            .query_map(params![customer_id], |row| {
+                Ok((
+                    row.get::<_, Option<i64>>(0)?,
+                    row.get::<_, Option<i64>>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, f64>(3)?,
+                ))
+            })
+            .map_err(map_sql_err)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(map_sql_err)?
+    };
+    // earlier standalone payments and adjustments already lowered the balance without naming a
+    // sale; they are treated as having paid off the oldest open items first
+    let balance = tx
+        .query_row(
+            "SELECT cached_credit - cached_paid FROM customers WHERE id = ?",
+            params![customer_id],
+            |row| row.get::<_, f64>(0),
+        )
+        .map_err(map_sql_err)?;
+    let open_total: f64 = outstanding.iter().map(|(_, _, _, owed)| owed).sum();
+    let mut covered = round_amount(open_total - balance.max(0.0), decimals);
+
+    let ts = now_iso();
+    let mut left = round_amount(amount, decimals);
+    let mut settled = Vec::new();
+    for (sale_id, invoice_id, sale_ts, owed) in outstanding {
+        if left <= 0.0 {
+            break;
+        }
+        let mut owed = round_amount(owed, decimals);
+        if covered > 0.0 {
+            let absorbed = owed.min(covered);
+            covered = round_amount(covered - absorbed, decimals);
+            owed = round_amount(owed - absorbed, decimals);
+        }
+        if owed <= 0.0 {
+            continue;
+        }
+        let paid = owed.min(left);
+        tx.execute(
+            "INSERT INTO credits (ts, customer_id, sale_id, invoice_id, amount, is_payment, note) VALUES (?, ?, ?, ?, ?, 1, ?)",
+            params![ts, customer_id, sale_id, invoice_id, paid, note],
+        )
+        .map_err(map_sql_err)?;
+        left = round_amount(left - paid, decimals);
+        settled.push(SaleSettlement {
+            sale_id,
+            invoice_id,
+            sale_ts,
+            amount: paid,
+            remaining: round_amount(owed - paid, decimals),
+        });
+    }
+    if left > 0.0 {
+        tx.execute(
+            "INSERT INTO credits (ts, customer_id, sale_id, amount, is_payment, note) VALUES (?, ?, NULL, ?, 1, ?)",
+            params![ts, customer_id, left, note],
+        )
+        .map_err(map_sql_err)?;
+    }
+
+    tx.commit().map_err(map_sql_err)?;
+    Ok(CustomerSettlement {
+        customer_id,
+        settled,
+        unapplied: left,
+    })
+}
+
+const CUSTOMER_DETAIL_RECENT_LIMIT: i64 = 50;
+
+#[derive(Debug, Serialize)]
+struct CustomerDetail {
+    customer: Customer,
+    balance: CustomerBalance,
+    recent_sales: Vec<SaleRecord>,
+    recent_credits: Vec<CreditEntry>,
+}
+
+#[tauri::command]
+fn customer_detail(state: State<DbState>, customer_id: i64) -> CommandResult<CustomerDetail> {
+    let conn = state.open().map_err(map_app_err)?;
+    fetch_customer_detail(&conn, customer_id).map_err(Into::into)
+}
+
+fn fetch_customer_detail(conn: &Connection, customer_id: i64) -> Result<CustomerDetail, AppError> {
+    let customer = conn
+        .query_row(
+            &format!("SELECT {CUSTOMER_COLUMNS} FROM customers WHERE id = ?"),
+            params![customer_id],
+            customer_from_row,
+        )
+        .optional()?
+        .ok_or_else(|| AppError::Validation("존재하지 않는 고객입니다.".into()))?;
+
+    let balance = conn.query_row(
+        &format!("{CUSTOMER_BALANCE_QUERY} WHERE c.id = ?"),
+        params![customer_id],
+        customer_balance_from_row,
+    )?;
+
+    let sql = format!("{SALE_SELECT} WHERE s.customer_id = ? ORDER BY s.ts DESC LIMIT ?");
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(
+        params![customer_id, CUSTOMER_DETAIL_RECENT_LIMIT],
+        sale_from_row,
+    )?;
+    let mut recent_sales = Vec::new();
+    for row in rows {
+        recent_sales.push(row?);
+    }
+
+    let sql = format!("{CREDIT_SELECT} WHERE cr.customer_id = ? ORDER BY cr.ts DESC LIMIT ?");
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(
+        params![customer_id, CUSTOMER_DETAIL_RECENT_LIMIT],
+        credit_from_row,
+    )?;
+    let mut recent_credits = Vec::new();
+    for row in rows {
+        recent_credits.push(row?);
+    }
+
+    Ok(CustomerDetail {
+        customer,
+        balance,
+        recent_sales,
+        recent_credits,
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct SaleLedger {
+    sale: SaleRecord,
+    transactions: Vec<StockMovement>,
+    returns: Vec<SaleRecord>,
+    credits: Vec<CreditEntry>,
+}
+
+#[tauri::command]
+fn sale_ledger(state: State<DbState>, sale_id: i64) -> CommandResult<SaleLedger> {
+    let conn = state.open().map_err(map_app_err)?;
+    fetch_sale_ledger(&conn, sale_id).map_err(Into::into)
+}
+
+// the sale plus everything hanging off it: stock movements and credits of the
+// sale itself and of every return issued against it
+fn fetch_sale_ledger(conn: &Connection, sale_id: i64) -> Result<SaleLedger, AppError> {
+    let sale = fetch_sale(conn, sale_id)?
+        .ok_or_else(|| AppError::Validation("존재하지 않는 판매입니다.".into()))?;
+
+    let sql = format!("{SALE_SELECT} WHERE s.origin_sale_id = ? AND s.is_return = 1 ORDER BY s.ts");
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![sale_id], sale_from_row)?;
+    let mut returns = Vec::new();
+    for row in rows {
+        returns.push(row?);
+    }
+
+    let sql = format!(
+        "{MOVEMENT_SELECT}
+         WHERE t.sale_id = ?1
+            OR t.sale_id IN (SELECT id FROM sales WHERE origin_sale_id = ?1 AND is_return = 1)
+         ORDER BY t.ts, t.id"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![sale_id], movement_from_row)?;
+    let mut transactions = Vec::new();
+    for row in rows {
+        transactions.push(row?);
+    }
+
+    let sql = format!(
+        "{CREDIT_SELECT}
+         WHERE cr.sale_id = ?1
+            OR cr.return_id IN (SELECT id FROM sales WHERE origin_sale_id = ?1 AND is_return = 1)
+         ORDER BY cr.ts, cr.id"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![sale_id], credit_from_row)?;
+    let mut credits = Vec::new();
+    for row in rows {
+        credits.push(row?);
+    }
+
+    Ok(SaleLedger {
+        sale,
+        transactions,
+        returns,
+        credits,
+    })
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum StatementLineKind {
+    Sale,
+    Invoice,
+    Return,
+    Payment,
+    Transfer,
+    Adjustment,
+    Discount,
+}
+
+impl StatementLineKind {
+    fn label(self) -> &'static str {
+        match self {
+            StatementLineKind::Sale => "판매",
+            StatementLineKind::Invoice => "명세서",
+            StatementLineKind::Return => "반품",
+            StatementLineKind::Payment => "결제",
+            StatementLineKind::Transfer => "잔액 이전",
+            StatementLineKind::Adjustment => "조정",
+            StatementLineKind::Discount => "할인",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct StatementLine {
+    credit_id: i64,
+    ts: String,
+    kind: StatementLineKind,
+    description: Option<String>,
+    charge: f64,
+    payment: f64,
+    balance: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct CustomerStatement {
+    customer: Customer,
+    from: Option<String>,
+    to: Option<String>,
+    opening_balance: f64,
+    lines: Vec<StatementLine>,
+    closing_balance: f64,
+}
+
+#[tauri::command]
+fn customer_statement(
+    state: State<DbState>,
+    customer_id: i64,
+    from: Option<String>,
+    to: Option<String>,
+) -> CommandResult<CustomerStatement> {
+    let conn = state.open().map_err(map_app_err)?;
+    fetch_customer_statement(&conn, customer_id, from.as_deref(), to.as_deref()).map_err(Into::into)
+}
+
+// only credits that move the balance appear, using the same charge rule as the balance cache,
+// so the closing balance of an open-ended statement equals the customer's outstanding amount
+fn fetch_customer_statement(
+    conn: &Connection,
+    customer_id: i64,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<CustomerStatement, AppError> {
+    let customer = conn
+        .query_row(
+            &format!("SELECT {CUSTOMER_COLUMNS} FROM customers WHERE id = ?"),
+            params![customer_id],
+            customer_from_row,
+        )
+        .optional()?
+        .ok_or_else(|| AppError::Validation("존재하지 않는 고객입니다.".into()))?;
+    let tz = BusinessTz::read(conn)?;
+    let (from, to) = resolve_date_range(tz, from, to)?;
+
+    let opening_balance = conn.query_row(
+        &format!(
+            "SELECT IFNULL(SUM(CASE
+                    WHEN is_payment = 1 THEN -amount
+                    WHEN {} THEN amount
+                    ELSE 0
+                END), 0)
+             FROM credits
+             WHERE customer_id = ?1 AND ?2 IS NOT NULL AND ts < ?2",
+            credit_charge_sql("credits")
+        ),
+        params![customer_id, from],
+        |row| row.get::<_, f64>(0),
+    )?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT cr.id, cr.ts, cr.amount, cr.is_payment, cr.sale_id, cr.invoice_id,
+                cr.return_id, cr.transfer_id, cr.note, p.name, cr.is_adjustment, cr.is_discount
+         FROM credits cr
+         LEFT JOIN sales s ON s.id = COALESCE(cr.return_id, cr.sale_id)
+         LEFT JOIN products p ON p.id = s.product_id
+         WHERE cr.customer_id = ?1
+           AND (cr.is_payment = 1 OR {})
+           AND (?2 IS NULL OR cr.ts >= ?2)
+           AND (?3 IS NULL OR cr.ts < ?3)
+         ORDER BY cr.ts, cr.id",
+        credit_charge_sql("cr")
+    ))?;
+    let rows = stmt.query_map(params![customer_id, from, to], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, f64>(2)?,
+            row.get::<_, i64>(3)? != 0,
+            row.get::<_, Option<i64>>(4)?,
+            row.get::<_, Option<i64>>(5)?,
+            row.get::<_, Option<i64>>(6)?,
+            row.get::<_, Option<i64>>(7)?,
+            row.get::<_, Option<String>>(8)?,
+            row.get::<_, Option<String>>(9)?,
+            row.get::<_, i64>(10)? != 0,
+            row.get::<_, i64>(11)? != 0,
+        ))
+    })?;
+
+    let mut balance = opening_balance;
+    let mut lines = Vec::new();
+    for row in rows {
+        let (
+            credit_id,
+            ts,
+            amount,
+            is_payment,
+            sale_id,
+            invoice_id,
+            return_id,
+            transfer_id,
+            note,
+            product,
+            is_adjustment,
+            is_discount,
+        ) = row?;
+        let kind = if is_adjustment {
+            StatementLineKind::Adjustment
+        } else if is_discount {
+            StatementLineKind::Discount
+        } else if is_payment {
+            if return_id.is_some() {
+                StatementLineKind::Return
+            } else if transfer_id.is_some() {
+                StatementLineKind::Transfer
+            } else {
+                StatementLineKind::Payment
+            }
+        } else if sale_id.is_some() {
+            StatementLineKind::Sale
+        } else if invoice_id.is_some() {
+            StatementLineKind::Invoice
+        } else {
+            StatementLineKind::Transfer
+        };
+        let (charge, payment) = if is_payment {
+            (0.0, amount)
+        } else {
+            (amount, 0.0)
+        };
+        balance += charge - payment;
+        let description = match (product, note) {
+            (Some(product), Some(note)) => Some(format!("{product} - {note}")),
+            (product, note) => product.or(note),
+        };
+        lines.push(StatementLine {
+            credit_id,
+            ts,
+            kind,
+            description,
+            charge,
+            payment,
+            balance,
+        });
+    }
+
+    Ok(CustomerStatement {
+        customer,
+        from,
+        to,
+        opening_balance,
+        lines,
+        closing_balance: balance,
+    })
+}
+
+#[tauri::command]
+fn delete_credit_payment(state: State<DbState>, credit_id: i64) -> CommandResult<AppData> {
+    let conn = state.open().map_err(map_app_err)?;
+    let row = conn
+        .query_row(
+            "SELECT is_payment, return_id, transfer_id FROM credits WHERE id = ?",
+            params![credit_id],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)? != 0,
+                    row.get::<_, Option<i64>>(1)?,
+                    row.get::<_, Option<i64>>(2)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+    let (is_payment, return_id, transfer_id) = match row {
+        Some(v) => v,
+        None => {
+            return Err(AppError::Validation("존재하지 않는 결제 내역입니다.".into()).into());
+        }
+    };
+    if !is_payment {
+        return Err(AppError::Validation("결제 내역만 삭제할 수 있습니다.".into()).into());
+    }
+    // settlements created by returns are removed together with the return
+    if return_id.is_some() {
+        return Err(AppError::Validation("반품 정산 내역은 삭제할 수 없습니다.".into()).into());
+    }
+    if transfer_id.is_some() {
+        return Err(AppError::Validation("잔액 이전 내역은 삭제할 수 없습니다.".into()).into());
+    }
+
+    conn.execute("DELETE FROM credits WHERE id = ?", params![credit_id])
+        .map_err(map_sql_err)?;
+
+    load_app_data(&state).map_err(Into::into)
+}
+
+#[derive(Debug, Deserialize)]
+struct BalanceTransferPayload {
+    from_customer_id: i64,
+    to_customer_id: i64,
+    amount: f64,
+    note: Option<String>,
+}
+
+// moves outstanding debt: a payment on the source and a matching charge on the destination
+#[tauri::command]
+fn transfer_balance(
+    state: State<DbState>,
+    payload: BalanceTransferPayload,
+) -> CommandResult<AppData> {
+    let note = clean_text(payload.note.as_deref());
+    if payload.amount <= 0.0 {
+        return Err(AppError::Validation("금액은 0보다 커야 합니다.".into()).into());
+    }
+    if payload.from_customer_id == payload.to_customer_id {
+        return Err(
+            AppError::Validation("같은 고객에게는 잔액을 이전할 수 없습니다.".into()).into(),
+        );
+    }
+    ensure_text_len("note", note, MAX_NOTE_LEN).map_err(map_app_err)?;
+
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+
+    let source = tx
+        .query_row(
+            &format!("{CUSTOMER_BALANCE_QUERY} WHERE c.id = ?"),
+            params![payload.from_customer_id],
+            customer_balance_from_row,
+        )
+        .optional()
+        .map_err(map_sql_err)?
+        .ok_or_else(|| AppError::Validation("존재하지 않는 고객입니다.".into()).to_string())?;
+    let destination = tx
+        .query_row(
+            "SELECT name FROM customers WHERE id = ?",
+            params![payload.to_customer_id],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(map_sql_err)?
+        .ok_or_else(|| AppError::Validation("존재하지 않는 고객입니다.".into()).to_string())?;
+    if source.outstanding + f64::EPSILON < payload.amount {
+        return Err(AppError::Validation("이전 금액이 미수금 잔액을 초과했습니다.".into()).into());
+    }
+
+    let ts = now_iso();
+    let tag = format!("잔액 이전: {} → {}", source.customer_name, destination);
+    let credit_note = match note {
+        Some(note) => format!("{tag} ({note})"),
+        None => tag,
+    };
+    tx.execute(
+        "INSERT INTO balance_transfers (ts, from_customer_id, to_customer_id, amount, note) VALUES (?, ?, ?, ?, ?)",
+        params![
+            ts,
+            payload.from_customer_id,
+            payload.to_customer_id,
+            payload.amount,
+            note
+        ],
+    )
+    .map_err(map_sql_err)?;
+    let transfer_id = tx.last_insert_rowid();
+    tx.execute(
+        "INSERT INTO credits (ts, customer_id, amount, is_payment, note, transfer_id) VALUES (?, ?, ?, 1, ?, ?)",
+        params![ts, payload.from_customer_id, payload.amount, credit_note, transfer_id],
+    )
+    .map_err(map_sql_err)?;
+    tx.execute(
+        "INSERT INTO credits (ts, customer_id, amount, is_payment, note, transfer_id) VALUES (?, ?, ?, 0, ?, ?)",
+        params![ts, payload.to_customer_id, payload.amount, credit_note, transfer_id],
+    )
+    .map_err(map_sql_err)?;
+
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+const MAX_PAGE_SIZE: i64 = 500;
+
+#[tauri::command]
+fn fetch_credits_page(
+    state: State<DbState>,
+    customer_id: Option<i64>,
+    limit: i64,
+    offset: i64,
+) -> CommandResult<Vec<CreditEntry>> {
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::Validation(format!(
+            "조회 개수는 1에서 {MAX_PAGE_SIZE} 사이여야 합니다."
+        ))
+        .into());
+    }
+    if offset < 0 {
+        return Err(AppError::Validation("조회 위치는 0 이상이어야 합니다.".into()).into());
+    }
+    let conn = state.open().map_err(map_app_err)?;
+    let sql = format!(
+        "{CREDIT_SELECT}
+         WHERE (?1 IS NULL OR cr.customer_id = ?1)
+         ORDER BY cr.ts DESC, cr.id DESC
+         LIMIT ?2 OFFSET ?3"
+    );
+    let mut stmt = conn.prepare(&sql).map_err(map_sql_err)?;
+    let rows = stmt
+        .query_map(params![customer_id, limit, offset], credit_from_row)
+        .map_err(map_sql_err)?;
+
+    let mut credits = Vec::new();
+    for row in rows {
+        credits.push(row.map_err(map_sql_err)?);
+    }
+    Ok(credits)
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum ActivityType {
+    Sale,
+    Return,
+    StockMovement,
+    CreditCharge,
+    CreditPayment,
+}
+
+#[derive(Debug, Serialize)]
+struct ActivityItem {
+    ts: String,
+    #[serde(rename = "type")]
+    activity_type: ActivityType,
+    ref_id: i64,
+    description: String,
+    amount: Option<f64>,
+}
+
+// sales, manual stock movements and credit entries newest first. movements created by a sale or
+// return, credit-sale charges and return settlements are left out so each event shows up once
+#[tauri::command]
+fn recent_activity(state: State<DbState>, limit: i64) -> CommandResult<Vec<ActivityItem>> {
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::Validation(format!(
+            "조회 개수는 1에서 {MAX_PAGE_SIZE} 사이여야 합니다."
+        ))
+        .into());
+    }
+    let conn = state.open().map_err(map_app_err)?;
+    fetch_recent_activity(&conn, limit).map_err(Into::into)
+}
+
+fn fetch_recent_activity(conn: &Connection, limit: i64) -> Result<Vec<ActivityItem>, AppError> {
+    let mut items = Vec::new();
+
+    let sql = format!("{SALE_SELECT} ORDER BY s.ts DESC, s.id DESC LIMIT ?");
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![limit], sale_from_row)?;
+    for row in rows {
+        let sale = row?;
+        let customer = sale
+            .customer_name
+            .as_deref()
+            .map(|name| format!(" · {name}"))
+            .unwrap_or_default();
+        let (activity_type, label) = if sale.is_return {
+            (ActivityType::Return, "반품")
+        } else if sale.is_credit {
+            (ActivityType::Sale, "외상 판매")
+        } else {
+            (ActivityType::Sale, "판매")
+        };
+        items.push(ActivityItem {
+            ts: sale.ts,
+            activity_type,
+            ref_id: sale.id,
+            description: format!("{label} {} {}{customer}", sale.product_name, sale.qty),
+            amount: Some(sale.total_amount),
+        });
+    }
+
+    let sql = format!(
+        "{MOVEMENT_SELECT}
+         WHERE t.sale_id IS NULL
+         ORDER BY t.ts DESC, t.id DESC
+         LIMIT ?"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![limit], movement_from_row)?;
+    for row in rows {
+        let movement = row?;
+        let counterparty = movement
+            .counterparty
+            .as_deref()
+            .map(|name| format!(" · {name}"))
+            .unwrap_or_default();
+        items.push(ActivityItem {
+            ts: movement.ts,
+            activity_type: ActivityType::StockMovement,
+            ref_id: movement.id,
+            description: format!(
+                "{} {} {}{counterparty}",
+                movement.kind.as_str(),
+                movement.product_name,
+                movement.qty
+            ),
+            amount: movement.total_amount,
+        });
+    }
+
+    let sql = format!(
+        "{CREDIT_SELECT}
+         WHERE cr.return_id IS NULL
+           AND NOT (cr.is_payment = 0 AND cr.sale_id IS NOT NULL)
+         ORDER BY cr.ts DESC, cr.id DESC
+         LIMIT ?"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![limit], credit_from_row)?;
+    for row in rows {
+        let credit = row?;
+        let (activity_type, label) = match (credit.is_payment, credit.is_adjustment) {
+            (true, _) if credit.is_discount => (ActivityType::CreditPayment, "외상 할인"),
+            (true, true) => (ActivityType::CreditPayment, "외상 탕감"),
+            (true, false) => (ActivityType::CreditPayment, "외상 결제"),
+            (false, true) => (ActivityType::CreditCharge, "외상 조정"),
+            (false, false) => (ActivityType::CreditCharge, "외상"),
+        };
+        let note = credit
+            .note
+            .as_deref()
+            .map(|note| format!(" · {note}"))
+            .unwrap_or_default();
+        items.push(ActivityItem {
+            ts: credit.ts,
+            activity_type,
+            ref_id: credit.id,
+            description: format!("{label} {}{note}", credit.customer_name),
+            amount: Some(credit.amount),
+        });
+    }
+
+    // each source is already capped at limit, so the merged head is exact
+    items.sort_by(|a, b| b.ts.cmp(&a.ts));
+    items.truncate(limit as usize);
+    Ok(items)
+}
+
+// attributes an already recorded sale to a customer. a credit sale takes its charge, payments
+// and discounts with it; one whose charge row is missing gets it recreated under the new customer
+#[tauri::command]
+fn assign_sale_customer(
+    state: State<DbState>,
+    sale_id: i64,
+    customer_id: i64,
+) -> CommandResult<AppData> {
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    let sale = tx
+        .query_row(
+            "SELECT ts, total_amount, note, is_credit, is_return, invoice_id FROM sales WHERE id = ?",
+            params![sale_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, i64>(3)? != 0,
+                    row.get::<_, i64>(4)? != 0,
+                    row.get::<_, Option<i64>>(5)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+    let (ts, total_amount, note, is_credit, is_return, invoice_id) = match sale {
+        Some(v) => v,
+        None => {
+            return Err(AppError::Validation("존재하지 않는 판매입니다.".into()).into());
+        }
+    };
+    if is_return {
+        return Err(AppError::Validation("반품 내역의 고객은 변경할 수 없습니다.".into()).into());
+    }
+    if invoice_id.is_some() {
+        return Err(
+            AppError::Validation("묶음 판매의 품목은 개별 수정할 수 없습니다.".into()).into(),
+        );
+    }
+    // a return's refund credits are booked to the original customer, so the two would split
+    let has_return = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM sales WHERE origin_sale_id = ? AND is_return = 1)",
+            params![sale_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(map_sql_err)?
         != 0;
     if has_return {
         return Err(AppError::Validation("반품이 등록된 판매는 수정할 수 없습니다.".into()).into());
     }
-    // Adjust stock
-    let qty_delta = payload.qty - prev_qty;
-    if qty_delta > 0.0 {
-        // need more stock available
-        let available: f64 = tx
+    let customer_exists = tx
+        .query_row(
+            "SELECT 1 FROM customers WHERE id = ?",
+            params![customer_id],
+            |_| Ok(()),
+        )
+        .optional()
+        .map_err(map_sql_err)?
+        .is_some();
+    if !customer_exists {
+        return Err(AppError::Validation("존재하지 않는 고객입니다.".into()).into());
+    }
+    if is_credit {
+        ensure_credit_allowed(&tx, customer_id).map_err(map_app_err)?;
+    }
+
+    tx.execute(
+        "UPDATE sales SET customer_id = ? WHERE id = ?",
+        params![customer_id, sale_id],
+    )
+    .map_err(map_sql_err)?;
+    snapshot_sale_customer(&tx, sale_id).map_err(map_app_err)?;
+    tx.execute(
+        "UPDATE transactions SET customer_id = ? WHERE sale_id = ?",
+        params![customer_id, sale_id],
+    )
+    .map_err(map_sql_err)?;
+
+    if is_credit {
+        // the balance triggers move the amounts between the two customers
+        tx.execute(
+            "UPDATE credits SET customer_id = ? WHERE sale_id = ?",
+            params![customer_id, sale_id],
+        )
+        .map_err(map_sql_err)?;
+        let has_charge = tx
             .query_row(
-                "SELECT qty FROM products WHERE id = ?",
-                params![product_id],
-                |row| row.get(0),
+                "SELECT EXISTS(SELECT 1 FROM credits WHERE sale_id = ? AND is_payment = 0)",
+                params![sale_id],
+                |row| row.get::<_, i64>(0),
+            )
+            .map_err(map_sql_err)?
+            != 0;
+        if !has_charge {
+            tx.execute(
+                "INSERT INTO credits (ts, customer_id, sale_id, amount, is_payment, note) VALUES (?, ?, ?, ?, 0, ?)",
+                params![ts, customer_id, sale_id, total_amount, note],
             )
             .map_err(map_sql_err)?;
-        if available < qty_delta {
-            return Err(AppError::Validation("재고가 부족합니다.".into()).into());
         }
     }
+
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+// pays off what is left on one credit sale; the sale keeps is_credit so its history
+// still shows it was sold on account
+#[tauri::command]
+fn settle_sale(state: State<DbState>, sale_id: i64) -> CommandResult<CustomerBalance> {
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    let sale = tx
+        .query_row(
+            "SELECT customer_id, is_credit, is_return FROM sales WHERE id = ?",
+            params![sale_id],
+            |row| {
+                Ok((
+                    row.get::<_, Option<i64>>(0)?,
+                    row.get::<_, i64>(1)? != 0,
+                    row.get::<_, i64>(2)? != 0,
+                ))
+            },
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+    let (customer_id, is_credit, is_return) = match sale {
+        Some(v) => v,
+        None => {
+            return Err(AppError::Validation("존재하지 않는 판매입니다.".into()).into());
+        }
+    };
+    let customer_id = match customer_id {
+        Some(id) if is_credit && !is_return => id,
+        _ => {
+            return Err(AppError::Validation("외상 판매만 정산할 수 있습니다.".into()).into());
+        }
+    };
+
+    // the charge on this sale less payments and return settlements already applied to it
+    let remaining = tx
+        .query_row(
+            "SELECT IFNULL(SUM(CASE WHEN is_payment = 0 THEN amount ELSE -amount END), 0)
+             FROM credits
+             WHERE sale_id = ?",
+            params![sale_id],
+            |row| row.get::<_, f64>(0),
+        )
+        .map_err(map_sql_err)?;
+    if remaining <= f64::EPSILON {
+        return Err(AppError::Validation("정산할 잔액이 없습니다.".into()).into());
+    }
+
     tx.execute(
-        "UPDATE products SET qty = qty - ? WHERE id = ?",
-        params![qty_delta, product_id],
+        "INSERT INTO credits (ts, customer_id, sale_id, amount, is_payment, note) VALUES (?, ?, ?, ?, 1, ?)",
+        params![now_iso(), customer_id, sale_id, remaining, "판매 건 정산"],
     )
     .map_err(map_sql_err)?;
-    let total_amount = payload.unit_price * payload.qty;
-    let ts = now_iso();
-    // Update sale
-    tx.execute(
-        "UPDATE sales SET qty = ?, price_snapshot = ?, total_amount = ?, customer_id = ?, note = ?, is_credit = ? WHERE id = ?",
+
+    let balance = tx
+        .query_row(
+            &format!("{CUSTOMER_BALANCE_QUERY} WHERE c.id = ?"),
+            params![customer_id],
+            customer_balance_from_row,
+        )
+        .map_err(map_sql_err)?;
+    tx.commit().map_err(map_sql_err)?;
+    Ok(balance)
+}
+
+#[tauri::command]
+fn customer_balances(state: State<DbState>) -> CommandResult<Vec<CustomerBalance>> {
+    let conn = state.open().map_err(map_app_err)?;
+    fetch_customer_balances(&conn).map_err(Into::into)
+}
+
+#[derive(Debug, Serialize)]
+struct CashEntry {
+    id: i64,
+    ts: String,
+    kind: CashEntryKind,
+    amount: f64,
+    category: Option<String>,
+    note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CashEntryPayload {
+    kind: CashEntryKind,
+    amount: f64,
+    category: Option<String>,
+    note: Option<String>,
+}
+
+#[tauri::command]
+fn record_cash_entry(state: State<DbState>, payload: CashEntryPayload) -> CommandResult<AppData> {
+    let note = clean_text(payload.note.as_deref());
+    if payload.amount <= 0.0 {
+        return Err(AppError::Validation("금액은 0보다 커야 합니다.".into()).into());
+    }
+    ensure_text_len("note", note, MAX_NOTE_LEN).map_err(map_app_err)?;
+    let conn = state.open().map_err(map_app_err)?;
+    let decimals = amount_decimals(&conn).map_err(map_app_err)?;
+    conn.execute(
+        "INSERT INTO cash_entries (ts, kind, amount, category, note) VALUES (?, ?, ?, ?, ?)",
         params![
-            payload.qty,
-            payload.unit_price,
-            total_amount,
-            payload.customer_id,
-            payload.note.as_deref(),
-            if payload.is_credit { 1 } else { 0 },
-            payload.id
+            now_iso(),
+            payload.kind.as_str(),
+            round_amount(payload.amount, decimals),
+            payload
+                .category
+                .as_deref()
+                .map(str::trim)
+                .filter(|category| !category.is_empty()),
+            note
         ],
     )
     .map_err(map_sql_err)?;
-    // Update transaction row
-    tx.execute(
-        "UPDATE transactions SET qty = ?, unit_price = ?, total_amount = ?, customer_id = ?, note = ? WHERE sale_id = ? AND kind = 'OUT'",
+
+    load_app_data(&state).map_err(Into::into)
+}
+
+#[tauri::command]
+fn fetch_cash_entries(
+    state: State<DbState>,
+    from: Option<String>,
+    to: Option<String>,
+) -> CommandResult<Vec<CashEntry>> {
+    let conn = state.open().map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let (from, to) = resolve_date_range(tz, from.as_deref(), to.as_deref()).map_err(map_app_err)?;
+    fetch_cash_entries_between(&conn, from.as_deref(), to.as_deref()).map_err(Into::into)
+}
+
+fn fetch_cash_entries_between(
+    conn: &Connection,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<Vec<CashEntry>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, ts, kind, amount, category, note
+         FROM cash_entries
+         WHERE (?1 IS NULL OR ts >= ?1)
+           AND (?2 IS NULL OR ts < ?2)
+         ORDER BY ts DESC",
+    )?;
+    let rows = stmt.query_map(params![from, to], |row| {
+        let kind_str: String = row.get(2)?;
+        Ok(CashEntry {
+            id: row.get(0)?,
+            ts: row.get(1)?,
+            kind: CashEntryKind::from_db(&kind_str).unwrap_or(CashEntryKind::Expense),
+            amount: row.get(3)?,
+            category: row.get(4)?,
+            note: row.get(5)?,
+        })
+    })?;
+
+    let mut entries = Vec::new();
+    for row in rows {
+        entries.push(row?);
+    }
+    Ok(entries)
+}
+
+fn sum_cash_entries(
+    conn: &Connection,
+    kind: CashEntryKind,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<f64, AppError> {
+    let total = conn.query_row(
+        "SELECT IFNULL(SUM(amount), 0)
+         FROM cash_entries
+         WHERE kind = ?1
+           AND (?2 IS NULL OR ts >= ?2)
+           AND (?3 IS NULL OR ts < ?3)",
+        params![kind.as_str(), from, to],
+        |row| row.get::<_, f64>(0),
+    )?;
+    Ok(total)
+}
+
+#[derive(Debug, Serialize)]
+struct DailySalesBreakdown {
+    date: String,
+    cash_sales: f64,
+    credit_sales: f64,
+    returns_total: f64,
+    cash_refunds: f64,
+    credit_payments: f64,
+    other_income: f64,
+    expenses: f64,
+    expected_cash: f64,
+}
+
+#[tauri::command]
+fn daily_sales_breakdown(
+    state: State<DbState>,
+    date: String,
+) -> CommandResult<DailySalesBreakdown> {
+    let conn = state.open().map_err(map_app_err)?;
+    fetch_daily_sales_breakdown(&conn, &date).map_err(Into::into)
+}
+
+fn fetch_daily_sales_breakdown(
+    conn: &Connection,
+    date: &str,
+) -> Result<DailySalesBreakdown, AppError> {
+    let date = date.trim();
+    if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+        return Err(AppError::Validation(format!(
+            "날짜 형식이 올바르지 않습니다: {date}"
+        )));
+    }
+    // the shop's calendar day mapped onto the UTC timestamps stored in ts
+    let tz = BusinessTz::read(conn)?;
+    let (from, to) = resolve_date_range(tz, Some(date), Some(date))?;
+    let (cash_sales, credit_sales, returns_total, cash_refunds) = conn
+        .query_row(
+            "SELECT
+                IFNULL(SUM(CASE WHEN is_return = 0 AND is_credit = 0 THEN total_amount ELSE 0 END), 0),
+                IFNULL(SUM(CASE WHEN is_return = 0 AND is_credit = 1 THEN total_amount ELSE 0 END), 0),
+                IFNULL(SUM(CASE WHEN is_return = 1 THEN total_amount ELSE 0 END), 0),
+                IFNULL(SUM(CASE WHEN is_return = 1 AND is_credit = 0 THEN total_amount ELSE 0 END), 0)
+             FROM sales
+             WHERE ts >= ? AND ts < ?",
+            params![from, to],
+            |row| {
+                Ok((
+                    row.get::<_, f64>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, f64>(3)?,
+                ))
+            },
+        )?;
+    // only real payments bring cash in; return settlements, transfers, write-offs and discounts just move balances
+    let credit_payments = conn.query_row(
+        "SELECT IFNULL(SUM(amount), 0)
+         FROM credits
+         WHERE is_payment = 1
+           AND return_id IS NULL
+           AND transfer_id IS NULL
+           AND is_adjustment = 0
+           AND is_discount = 0
+           AND ts >= ? AND ts < ?",
+        params![from, to],
+        |row| row.get::<_, f64>(0),
+    )?;
+    let other_income =
+        sum_cash_entries(conn, CashEntryKind::Income, from.as_deref(), to.as_deref())?;
+    let expenses = sum_cash_entries(conn, CashEntryKind::Expense, from.as_deref(), to.as_deref())?;
+
+    Ok(DailySalesBreakdown {
+        date: date.to_string(),
+        cash_sales,
+        credit_sales,
+        returns_total,
+        cash_refunds,
+        credit_payments,
+        other_income,
+        expenses,
+        expected_cash: cash_sales - cash_refunds + credit_payments + other_income - expenses,
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct DrawerReconciliation {
+    id: i64,
+    date: String,
+    expected_cash: f64,
+    counted_cash: f64,
+    // positive when the drawer is over, negative when short
+    variance: f64,
+}
+
+// expected cash is daily_sales_breakdown's figure for the same shop day
+#[tauri::command]
+fn reconcile_drawer(
+    state: State<DbState>,
+    date: String,
+    counted_cash: f64,
+    note: Option<String>,
+) -> CommandResult<DrawerReconciliation> {
+    if counted_cash < 0.0 {
+        return Err(AppError::Validation("실제 현금은 0 이상이어야 합니다.".into()).into());
+    }
+    let note = clean_text(note.as_deref());
+    ensure_text_len("note", note, MAX_NOTE_LEN).map_err(map_app_err)?;
+
+    let conn = state.open().map_err(map_app_err)?;
+    let breakdown = fetch_daily_sales_breakdown(&conn, &date).map_err(map_app_err)?;
+    let decimals = amount_decimals(&conn).map_err(map_app_err)?;
+    let expected_cash = round_amount(breakdown.expected_cash, decimals);
+    let counted_cash = round_amount(counted_cash, decimals);
+    let variance = round_amount(counted_cash - expected_cash, decimals);
+    conn.execute(
+        "INSERT INTO drawer_reconciliations (ts, business_date, expected_cash, counted_cash, variance, note) VALUES (?, ?, ?, ?, ?, ?)",
         params![
-            payload.qty,
-            payload.unit_price,
-            total_amount,
-            payload.customer_id,
-            payload.note.as_deref(),
-            payload.id
+            now_iso(),
+            breakdown.date,
+            expected_cash,
+            counted_cash,
+            variance,
+            note
         ],
     )
     .map_err(map_sql_err)?;
-    // Update credit
-    let credit_exists: bool = tx
+
+    Ok(DrawerReconciliation {
+        id: conn.last_insert_rowid(),
+        date: breakdown.date,
+        expected_cash,
+        counted_cash,
+        variance,
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct CustomerInsights {
+    customer_id: i64,
+    total_spend: f64,
+    purchase_count: i64,
+    average_order_value: f64,
+    first_purchase: Option<String>,
+    last_purchase: Option<String>,
+    favorite_product_id: Option<i64>,
+    favorite_product_name: Option<String>,
+    favorite_product_qty: Option<f64>,
+    // null when the customer never bought on credit
+    average_days_to_pay: Option<f64>,
+    open_credit_amount: f64,
+    open_credit_days: Option<i64>,
+}
+
+#[tauri::command]
+fn customer_insights(state: State<DbState>, customer_id: i64) -> CommandResult<CustomerInsights> {
+    let conn = state.open().map_err(map_app_err)?;
+    let exists = conn
         .query_row(
-            "SELECT EXISTS(SELECT 1 FROM credits WHERE sale_id = ? AND is_payment = 0)",
-            params![payload.id],
-            |row| row.get::<_, i64>(0),
-        )
-        .map_err(map_sql_err)?
-        != 0;
-    if payload.is_credit {
-        if credit_exists {
-            tx.execute(
-                "UPDATE credits SET customer_id = ?, amount = ?, note = ? WHERE sale_id = ? AND is_payment = 0",
-                params![
-                    payload.customer_id,
-                    total_amount,
-                    payload.note.as_deref(),
-                    payload.id
-                ],
-            )
-            .map_err(map_sql_err)?;
-        } else if payload.customer_id.is_some() {
-            tx.execute(
-                "INSERT INTO credits (ts, customer_id, sale_id, amount, is_payment, note) VALUES (?, ?, ?, ?, 0, ?)",
-                params![ts, payload.customer_id, payload.id, total_amount, payload.note.as_deref()],
-            )
-            .map_err(map_sql_err)?;
-        }
-    } else if credit_exists {
-        tx.execute(
-            "DELETE FROM credits WHERE sale_id = ? AND is_payment = 0",
-            params![payload.id],
+            "SELECT 1 FROM customers WHERE id = ?",
+            params![customer_id],
+            |_| Ok(()),
         )
+        .optional()
         .map_err(map_sql_err)?;
+    if exists.is_none() {
+        return Err(AppError::Validation("존재하지 않는 고객입니다.".into()).into());
     }
-    tx.commit().map_err(map_sql_err)?;
-    load_app_data(&state).map_err(Into::into)
-}
 
-#[tauri::command]
-fn delete_sale(state: State<DbState>, sale_id: i64) -> CommandResult<AppData> {
-    let mut conn = state.open().map_err(map_app_err)?;
-    let tx = conn.transaction().map_err(map_sql_err)?;
-    // fetch sale
-    let row = tx
+    // lines of one invoice count as a single purchase
+    let (total_spend, purchase_count, first_purchase, last_purchase) = conn
         .query_row(
-            "SELECT product_id, qty, is_return FROM sales WHERE id = ?",
-            params![sale_id],
-            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?, row.get::<_, i64>(2)? != 0)),
+            "SELECT
+                IFNULL(SUM(CASE WHEN is_return = 1 THEN -total_amount ELSE total_amount END), 0),
+                COUNT(DISTINCT CASE
+                    WHEN is_return = 1 THEN NULL
+                    WHEN invoice_id IS NOT NULL THEN 'i' || invoice_id
+                    ELSE 's' || id
+                END),
+                MIN(CASE WHEN is_return = 0 THEN ts END),
+                MAX(CASE WHEN is_return = 0 THEN ts END)
+             FROM sales
+             WHERE customer_id = ?",
+            params![customer_id],
+            |row| {
+                Ok((
+                    row.get::<_, f64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                ))
+            },
+        )
+        .map_err(map_sql_err)?;
+
+    let favorite = conn
+        .query_row(
+            "SELECT s.product_id, p.name,
+                    SUM(CASE WHEN s.is_return = 1 THEN -s.qty ELSE s.qty END) AS net_qty
+             FROM sales s
+             JOIN products p ON p.id = s.product_id
+             WHERE s.customer_id = ?
+             GROUP BY s.product_id, p.name
+             HAVING net_qty > 0
+             ORDER BY net_qty DESC, p.name COLLATE NOCASE
+             LIMIT 1",
+            params![customer_id],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, f64>(2)?,
+                ))
+            },
         )
         .optional()
         .map_err(map_sql_err)?;
-    let (product_id, qty, is_return) = match row {
-        Some(v) => v,
-        None => {
-            return Err(AppError::Validation("존재하지 않는 판매입니다.".into()).into());
+
+    let timing = fetch_payment_timing(&conn, customer_id).map_err(map_app_err)?;
+
+    Ok(CustomerInsights {
+        customer_id,
+        total_spend,
+        purchase_count,
+        average_order_value: if purchase_count > 0 {
+            total_spend / purchase_count as f64
+        } else {
+            0.0
+        },
+        first_purchase,
+        last_purchase,
+        favorite_product_id: favorite.as_ref().map(|(id, _, _)| *id),
+        favorite_product_name: favorite.as_ref().map(|(_, name, _)| name.clone()),
+        favorite_product_qty: favorite.map(|(_, _, qty)| qty),
+        average_days_to_pay: timing.average_days_to_pay,
+        open_credit_amount: timing.open_amount,
+        open_credit_days: timing.open_days,
+    })
+}
+
+struct PaymentTiming {
+    average_days_to_pay: Option<f64>,
+    open_amount: f64,
+    open_days: Option<i64>,
+}
+
+// payments settle the oldest charges first, as in fetch_customer_aging; the average is weighted by
+// the amount settled so a small late remainder does not count like a whole late sale.
+// open_days is the age of the oldest charge still unpaid
+fn fetch_payment_timing(conn: &Connection, customer_id: i64) -> Result<PaymentTiming, AppError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT ts, amount, is_payment
+         FROM credits
+         WHERE customer_id = ?
+           AND (is_payment = 1 OR {})
+         ORDER BY ts ASC, id ASC",
+        credit_charge_sql("credits")
+    ))?;
+    let mut rows = stmt.query(params![customer_id])?;
+
+    let mut open: std::collections::VecDeque<(DateTime<chrono::FixedOffset>, f64)> =
+        std::collections::VecDeque::new();
+    let mut prepaid: f64 = 0.0;
+    let mut settled_amount = 0.0;
+    let mut settled_day_amount = 0.0;
+    while let Some(row) = rows.next()? {
+        let ts: String = row.get(0)?;
+        let amount: f64 = row.get(1)?;
+        let is_payment = row.get::<_, i64>(2)? != 0;
+        let Ok(at) = DateTime::parse_from_rfc3339(&ts) else {
+            continue;
+        };
+        if !is_payment {
+            // money paid ahead of the charge settles it immediately
+            let applied = prepaid.min(amount);
+            prepaid -= applied;
+            settled_amount += applied;
+            if amount - applied > f64::EPSILON {
+                open.push_back((at, amount - applied));
+            }
+            continue;
+        }
+        let mut remaining = amount;
+        while remaining > f64::EPSILON {
+            let Some((charged, due)) = open.front_mut() else {
+                prepaid += remaining;
+                break;
+            };
+            let applied = remaining.min(*due);
+            let days = at.signed_duration_since(*charged).num_days().max(0) as f64;
+            settled_amount += applied;
+            settled_day_amount += applied * days;
+            remaining -= applied;
+            *due -= applied;
+            if *due <= f64::EPSILON {
+                open.pop_front();
+            }
         }
-    };
-    if is_return {
-        return Err(AppError::Validation("반품 내역은 삭제할 수 없습니다.".into()).into());
     }
-    let has_return = tx
+
+    let now = Utc::now();
+    Ok(PaymentTiming {
+        average_days_to_pay: (settled_amount > f64::EPSILON)
+            .then(|| settled_day_amount / settled_amount),
+        open_amount: open.iter().map(|(_, due)| due).sum(),
+        open_days: open
+            .front()
+            .map(|(charged, _)| now.signed_duration_since(*charged).num_days()),
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct SnapshotLine {
+    product_id: i64,
+    product_name: String,
+    qty: f64,
+    unit_price: f64,
+    cost_price: Option<f64>,
+    cost_value: Option<f64>,
+    retail_value: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct DailySnapshot {
+    date: String,
+    captured_at: String,
+    lines: Vec<SnapshotLine>,
+    total_cost_value: f64,
+    total_retail_value: f64,
+    // products without a cost price are left out of total_cost_value
+    uncosted_products: usize,
+}
+
+// records today's (shop calendar) stock of every active product; a day can only be captured once
+#[tauri::command]
+fn capture_daily_snapshot(state: State<DbState>) -> CommandResult<DailySnapshot> {
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    let date = BusinessTz::read(&tx)
+        .map_err(map_app_err)?
+        .today()
+        .format("%Y-%m-%d")
+        .to_string();
+    let already_captured = tx
         .query_row(
-            "SELECT EXISTS(SELECT 1 FROM sales WHERE origin_sale_id = ? AND is_return = 1)",
-            params![sale_id],
+            "SELECT EXISTS(SELECT 1 FROM daily_snapshots WHERE snapshot_date = ?)",
+            params![date],
             |row| row.get::<_, i64>(0),
         )
         .map_err(map_sql_err)?
         != 0;
-    if has_return {
-        return Err(AppError::Validation("반품이 등록된 판매는 삭제할 수 없습니다.".into()).into());
+    if already_captured {
+        return Err(
+            AppError::Validation(format!("{date} 재고 스냅샷은 이미 저장되었습니다.")).into(),
+        );
     }
-    // revert stock
-    tx.execute(
-        "UPDATE products SET qty = qty + ? WHERE id = ?",
-        params![qty, product_id],
-    )
-    .map_err(map_sql_err)?;
-    // delete movement
-    tx.execute(
-        "DELETE FROM transactions WHERE sale_id = ?",
-        params![sale_id],
-    )
-    .map_err(map_sql_err)?;
-    // delete credit (non-payment) if any
+
     tx.execute(
-        "DELETE FROM credits WHERE sale_id = ? AND is_payment = 0",
-        params![sale_id],
+        "INSERT INTO daily_snapshots (snapshot_date, captured_at, product_id, product_name, qty, unit_price, cost_price)
+         SELECT ?, ?, id, name, qty, unit_price, cost_price FROM products WHERE archived = 0",
+        params![date, now_iso()],
     )
     .map_err(map_sql_err)?;
-    // delete sale
-    tx.execute("DELETE FROM sales WHERE id = ?", params![sale_id])
-        .map_err(map_sql_err)?;
+    let snapshot = fetch_daily_snapshot(&tx, &date)
+        .map_err(map_app_err)?
+        .ok_or_else(|| AppError::Validation("저장할 품목이 없습니다.".into()))?;
     tx.commit().map_err(map_sql_err)?;
-    load_app_data(&state).map_err(Into::into)
+    Ok(snapshot)
 }
 
-#[derive(Debug, Deserialize)]
-struct ReturnUpdatePayload {
-    id: i64, // return sale id
-    qty: f64,
-    note: Option<String>,
-    override_amount: Option<f64>,
+#[tauri::command]
+fn fetch_snapshot(state: State<DbState>, date: String) -> CommandResult<Option<DailySnapshot>> {
+    let date = date.trim();
+    if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+        return Err(AppError::Validation(format!("날짜 형식이 올바르지 않습니다: {date}")).into());
+    }
+    let conn = state.open().map_err(map_app_err)?;
+    fetch_daily_snapshot(&conn, date).map_err(Into::into)
 }
 
-#[tauri::command]
-fn update_return(state: State<DbState>, payload: ReturnUpdatePayload) -> CommandResult<AppData> {
-    if payload.qty <= 0.0 {
-        return Err(AppError::Validation("반품 수량은 0보다 커야 합니다.".into()).into());
+fn fetch_daily_snapshot(conn: &Connection, date: &str) -> Result<Option<DailySnapshot>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT product_id, product_name, qty, unit_price, cost_price, captured_at
+         FROM daily_snapshots
+         WHERE snapshot_date = ?
+         ORDER BY product_name COLLATE NOCASE",
+    )?;
+    let mut rows = stmt.query(params![date])?;
+
+    let mut captured_at = None;
+    let mut lines = Vec::new();
+    while let Some(row) = rows.next()? {
+        let qty: f64 = row.get(2)?;
+        let unit_price: f64 = row.get(3)?;
+        let cost_price: Option<f64> = row.get(4)?;
+        captured_at.get_or_insert(row.get::<_, String>(5)?);
+        lines.push(SnapshotLine {
+            product_id: row.get(0)?,
+            product_name: row.get(1)?,
+            qty,
+            unit_price,
+            cost_price,
+            cost_value: cost_price.map(|cost| cost * qty),
+            retail_value: unit_price * qty,
+        });
     }
-    let mut conn = state.open().map_err(map_app_err)?;
-    let tx = conn.transaction().map_err(map_sql_err)?;
-    // fetch return sale
-    let row = tx
-        .query_row(
-            "SELECT product_id, qty, price_snapshot, customer_id, is_credit FROM sales WHERE id = ? AND is_return = 1",
-            params![payload.id],
-            |row| Ok((
-                row.get::<_, i64>(0)?,
-                row.get::<_, f64>(1)?,
-                row.get::<_, f64>(2)?,
-                row.get::<_, Option<i64>>(3)?,
-                row.get::<_, i64>(4)? != 0,
-            )),
-        )
-        .optional()
-        .map_err(map_sql_err)?;
-    let (product_id, prev_qty, prev_unit, customer_id, was_credit) = match row {
-        Some(v) => v,
-        None => return Err(AppError::Validation("존재하지 않는 반품입니다.".into()).into()),
+    let Some(captured_at) = captured_at else {
+        return Ok(None);
     };
-    let qty_delta = payload.qty - prev_qty;
-    tx.execute(
-        "UPDATE products SET qty = qty + ? WHERE id = ?",
-        params![qty_delta, product_id],
-    )
-    .map_err(map_sql_err)?;
-    let unit = payload
-        .override_amount
-        .map(|v| v / payload.qty)
-        .unwrap_or(prev_unit);
-    let total = unit * payload.qty;
-    tx.execute(
-        "UPDATE sales SET qty = ?, price_snapshot = ?, total_amount = ?, note = ? WHERE id = ?",
-        params![payload.qty, unit, total, payload.note.as_deref(), payload.id],
-    )
-    .map_err(map_sql_err)?;
-    tx.execute(
-        "UPDATE transactions SET qty = ?, unit_price = ?, total_amount = ?, note = ? WHERE sale_id = ? AND kind = 'RETURN'",
-        params![payload.qty, unit, total, payload.note.as_deref(), payload.id],
-    )
-    .map_err(map_sql_err)?;
-    // credit update to reflect new return amount (update linked payment instead of diff row)
-    if was_credit {
-        // set credit amount for this specific return (if present)
-        tx.execute(
-            "UPDATE credits SET amount = ?, note = ? WHERE return_id = ?",
-            params![total, payload.note.as_deref(), payload.id],
-        )
-        .map_err(map_sql_err)?;
+
+    let decimals = amount_decimals(conn)?;
+    let total_cost: f64 = lines.iter().filter_map(|line| line.cost_value).sum();
+    let total_retail: f64 = lines.iter().map(|line| line.retail_value).sum();
+    let uncosted_products = lines
+        .iter()
+        .filter(|line| line.cost_price.is_none())
+        .count();
+    Ok(Some(DailySnapshot {
+        date: date.to_string(),
+        captured_at,
+        lines,
+        total_cost_value: round_amount(total_cost, decimals),
+        total_retail_value: round_amount(total_retail, decimals),
+        uncosted_products,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+struct PricingSuggestion {
+    product_id: i64,
+    cost_price: f64,
+    unit_price: f64,
+    // selling at cost earns nothing; anything lower loses money on every unit
+    break_even_price: f64,
+    current_margin_percent: Option<f64>,
+    current_markup_percent: Option<f64>,
+    target_margin_percent: f64,
+    suggested_unit_price: f64,
+}
+
+// margin is profit over the selling price, markup is profit over cost
+#[tauri::command]
+fn pricing_suggestion(
+    state: State<DbState>,
+    product_id: i64,
+    target_margin_percent: f64,
+) -> CommandResult<PricingSuggestion> {
+    if !(0.0..100.0).contains(&target_margin_percent) {
+        return Err(
+            AppError::Validation("목표 마진율은 0 이상 100 미만이어야 합니다.".into()).into(),
+        );
     }
-    tx.commit().map_err(map_sql_err)?;
-    load_app_data(&state).map_err(Into::into)
+
+    let conn = state.open().map_err(map_app_err)?;
+    let product = fetch_product(&conn, product_id)
+        .map_err(map_app_err)?
+        .ok_or_else(|| AppError::Validation("존재하지 않는 품목입니다.".into()))?;
+    let cost_price = product
+        .cost_price
+        .ok_or_else(|| AppError::Validation("원가가 입력되지 않은 품목입니다.".into()))?;
+
+    let decimals = amount_decimals(&conn).map_err(map_app_err)?;
+    let unit_price = product.unit_price;
+    Ok(PricingSuggestion {
+        product_id,
+        cost_price,
+        unit_price,
+        break_even_price: cost_price,
+        current_margin_percent: (unit_price > 0.0)
+            .then(|| (unit_price - cost_price) / unit_price * 100.0),
+        current_markup_percent: (cost_price > 0.0)
+            .then(|| (unit_price - cost_price) / cost_price * 100.0),
+        target_margin_percent,
+        suggested_unit_price: round_amount(
+            cost_price / (1.0 - target_margin_percent / 100.0),
+            decimals,
+        ),
+    })
 }
 
+#[derive(Debug, Serialize)]
+struct TurnoverLine {
+    product_id: i64,
+    product_name: String,
+    sku: Option<String>,
+    qty_sold: f64,
+    average_inventory: f64,
+    // false when no snapshots fall in the period and the current stock stands in
+    from_snapshots: bool,
+    turnover: Option<f64>,
+    days_of_inventory: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct TurnoverReport {
+    period_days: f64,
+    lines: Vec<TurnoverLine>,
+}
+
+// turnover is net qty sold over average stock; days of inventory is how long that stock lasts at the
+// period's sales rate. either is null when its divisor is zero
 #[tauri::command]
-fn delete_return(state: State<DbState>, return_id: i64) -> CommandResult<AppData> {
-    let mut conn = state.open().map_err(map_app_err)?;
-    let tx = conn.transaction().map_err(map_sql_err)?;
-    let row = tx
-        .query_row(
-            "SELECT product_id, qty, total_amount, customer_id, is_credit FROM sales WHERE id = ? AND is_return = 1",
-            params![return_id],
-            |row| Ok((
-                row.get::<_, i64>(0)?,
-                row.get::<_, f64>(1)?,
-                row.get::<_, f64>(2)?,
-                row.get::<_, Option<i64>>(3)?,
-                row.get::<_, i64>(4)? != 0,
-            )),
+fn turnover_report(
+    state: State<DbState>,
+    from: Option<String>,
+    to: Option<String>,
+) -> CommandResult<TurnoverReport> {
+    let conn = state.open().map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let (from, to) = resolve_date_range(tz, from.as_deref(), to.as_deref()).map_err(map_app_err)?;
+
+    // an open start runs from the first sale, an open end up to now
+    let start = match &from {
+        Some(from) => Some(from.clone()),
+        None => conn
+            .query_row("SELECT MIN(ts) FROM sales", [], |row| {
+                row.get::<_, Option<String>>(0)
+            })
+            .map_err(map_sql_err)?,
+    };
+    let end = to.clone().unwrap_or_else(now_iso);
+    let period_days = match start.as_deref().map(DateTime::parse_from_rfc3339) {
+        Some(Ok(start)) => match DateTime::parse_from_rfc3339(&end) {
+            Ok(end) => ((end - start).num_seconds() as f64 / 86_400.0).max(1.0),
+            Err(_) => 1.0,
+        },
+        _ => 1.0,
+    };
+    // snapshots are keyed by shop date; the range end is already the day after the last one
+    let snapshot_from = from
+        .as_deref()
+        .and_then(|ts| tz.local_datetime(ts))
+        .map(|dt| dt.date().format("%Y-%m-%d").to_string());
+    let snapshot_to = to
+        .as_deref()
+        .and_then(|ts| tz.local_datetime(ts))
+        .map(|dt| dt.date().format("%Y-%m-%d").to_string());
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT p.id, p.name, p.sku, p.qty,
+                    IFNULL((
+                        SELECT SUM(CASE WHEN s.is_return = 1 THEN -s.qty ELSE s.qty END)
+                        FROM sales s
+                        WHERE s.product_id = p.id
+                          AND (?1 IS NULL OR s.ts >= ?1) AND (?2 IS NULL OR s.ts < ?2)
+                    ), 0),
+                    (
+                        SELECT AVG(d.qty)
+                        FROM daily_snapshots d
+                        WHERE d.product_id = p.id
+                          AND (?3 IS NULL OR d.snapshot_date >= ?3)
+                          AND (?4 IS NULL OR d.snapshot_date < ?4)
+                    )
+             FROM products p
+             WHERE p.archived = 0
+             ORDER BY p.name COLLATE NOCASE",
         )
-        .optional()
         .map_err(map_sql_err)?;
-    let (product_id, qty, total_amount, customer_id, was_credit) = match row {
-        Some(v) => v,
-        None => return Err(AppError::Validation("존재하지 않는 반품입니다.".into()).into()),
-    };
-    tx.execute(
-        "UPDATE products SET qty = qty - ? WHERE id = ?",
-        params![qty, product_id],
-    )
-    .map_err(map_sql_err)?;
-    tx.execute("DELETE FROM transactions WHERE sale_id = ?", params![return_id])
+    let rows = stmt
+        .query_map(params![from, to, snapshot_from, snapshot_to], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, f64>(3)?,
+                row.get::<_, f64>(4)?,
+                row.get::<_, Option<f64>>(5)?,
+            ))
+        })
+        .map_err(map_sql_err)?;
+
+    let mut lines = Vec::new();
+    for row in rows {
+        let (product_id, product_name, sku, qty, sold, snapshot_average) =
+            row.map_err(map_sql_err)?;
+        let qty_sold = sold.max(0.0);
+        let average_inventory = snapshot_average.unwrap_or(qty).max(0.0);
+        let daily_sales = qty_sold / period_days;
+        lines.push(TurnoverLine {
+            product_id,
+            product_name,
+            sku,
+            qty_sold,
+            average_inventory,
+            from_snapshots: snapshot_average.is_some(),
+            turnover: (average_inventory > 0.0).then(|| qty_sold / average_inventory),
+            days_of_inventory: (daily_sales > 0.0).then(|| average_inventory / daily_sales),
+        });
+    }
+    Ok(TurnoverReport { period_days, lines })
+}
+
+#[derive(Debug, Serialize)]
+struct TaxRateSummary {
+    tax_rate: f64,
+    gross_sales: f64,
+    returns_total: f64,
+    taxable_base: f64,
+    tax_collected: f64,
+}
+
+// sale prices include tax, so each band's tax is backed out of its net takings; zero-rated sales
+// form their own band with no tax. the tax is derived from total_amount rather than stored per sale
+// so edited and repriced sales can never drift from it
+#[tauri::command]
+fn tax_summary_by_rate(
+    state: State<DbState>,
+    from: Option<String>,
+    to: Option<String>,
+) -> CommandResult<Vec<TaxRateSummary>> {
+    let conn = state.open().map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let (from, to) = resolve_date_range(tz, from.as_deref(), to.as_deref()).map_err(map_app_err)?;
+    let decimals = amount_decimals(&conn).map_err(map_app_err)?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT
+                tax_rate,
+                IFNULL(SUM(CASE WHEN is_return = 0 THEN total_amount ELSE 0 END), 0),
+                IFNULL(SUM(CASE WHEN is_return = 1 THEN total_amount ELSE 0 END), 0)
+             FROM sales
+             WHERE (?1 IS NULL OR ts >= ?1) AND (?2 IS NULL OR ts < ?2)
+             GROUP BY tax_rate
+             ORDER BY tax_rate",
+        )
         .map_err(map_sql_err)?;
-    tx.execute("DELETE FROM sales WHERE id = ?", params![return_id])
+    let rows = stmt
+        .query_map(params![from, to], |row| {
+            Ok((
+                row.get::<_, f64>(0)?,
+                row.get::<_, f64>(1)?,
+                row.get::<_, f64>(2)?,
+            ))
+        })
         .map_err(map_sql_err)?;
-    if was_credit {
-        // remove the payment that was created for this return
-        tx.execute("DELETE FROM credits WHERE return_id = ?", params![return_id])
-            .map_err(map_sql_err)?;
+
+    let mut summaries = Vec::new();
+    for row in rows {
+        let (tax_rate, gross_sales, returns_total) = row.map_err(map_sql_err)?;
+        let net_sales = gross_sales - returns_total;
+        let tax_collected = round_amount(net_sales * tax_rate / (100.0 + tax_rate), decimals);
+        summaries.push(TaxRateSummary {
+            tax_rate,
+            gross_sales,
+            returns_total,
+            taxable_base: round_amount(net_sales - tax_collected, decimals),
+            tax_collected,
+        });
     }
-    tx.commit().map_err(map_sql_err)?;
-    load_app_data(&state).map_err(Into::into)
+    Ok(summaries)
+}
+
+#[derive(Debug, Serialize)]
+struct CogsReport {
+    revenue: f64,
+    cogs: f64,
+    gross_margin: f64,
+    margin_percent: Option<f64>,
+    excluded_sales: i64,
 }
+
+// revenue and cost cover only rows with a cost snapshot so the margin compares like with like;
+// legacy rows without one are counted in excluded_sales. returns subtract at the cost they were sold at
 #[tauri::command]
-fn record_return(state: State<DbState>, payload: ReturnPayload) -> CommandResult<AppData> {
-    if payload.qty <= 0.0 {
-        return Err(AppError::Validation("반품 수량은 0보다 커야 합니다.".into()).into());
-    }
+fn cogs_report(
+    state: State<DbState>,
+    from: Option<String>,
+    to: Option<String>,
+) -> CommandResult<CogsReport> {
+    let conn = state.open().map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let (from, to) = resolve_date_range(tz, from.as_deref(), to.as_deref()).map_err(map_app_err)?;
+    let totals =
+        fetch_sales_cost_totals(&conn, from.as_deref(), to.as_deref()).map_err(map_app_err)?;
+    let revenue = totals.costed_revenue;
+    let gross_margin = revenue - totals.cogs;
+    Ok(CogsReport {
+        revenue,
+        cogs: totals.cogs,
+        gross_margin,
+        margin_percent: (revenue.abs() > f64::EPSILON).then(|| gross_margin / revenue * 100.0),
+        excluded_sales: totals.uncosted_sales,
+    })
+}
 
-    let mut conn = state.open().map_err(map_app_err)?;
-    let tx = conn.transaction().map_err(map_sql_err)?;
+struct SalesCostTotals {
+    revenue: f64,
+    costed_revenue: f64,
+    cogs: f64,
+    uncosted_sales: i64,
+}
 
-    let sql = "
-        SELECT
-            s.id,
-            s.qty,
-            s.price_snapshot,
-            s.is_credit,
-            s.customer_id,
-            IFNULL(SUM(r.qty), 0) AS returned
-        FROM sales s
-        LEFT JOIN sales r ON r.origin_sale_id = s.id AND r.is_return = 1
-        WHERE s.product_id = ?1
-          AND (
-                (?2 IS NULL AND s.customer_id IS NULL) OR
-                (?2 IS NOT NULL AND s.customer_id = ?2)
-              )
-          AND s.is_return = 0
-        GROUP BY s.id, s.qty, s.price_snapshot, s.is_credit, s.customer_id
-        HAVING s.qty - IFNULL(SUM(r.qty), 0) > 0
-        ORDER BY s.ts ASC
-    ";
+// returns count negative on every line, at the price and cost of the original sale
+fn fetch_sales_cost_totals(
+    conn: &Connection,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<SalesCostTotals, AppError> {
+    let totals = conn.query_row(
+        "SELECT
+            IFNULL(SUM(CASE WHEN is_return = 1 THEN -total_amount ELSE total_amount END), 0),
+            IFNULL(SUM(CASE WHEN cost_snapshot IS NULL THEN 0
+                            WHEN is_return = 1 THEN -total_amount
+                            ELSE total_amount END), 0),
+            IFNULL(SUM(CASE WHEN cost_snapshot IS NULL THEN 0
+                            WHEN is_return = 1 THEN -cost_snapshot * qty
+                            ELSE cost_snapshot * qty END), 0),
+            IFNULL(SUM(CASE WHEN cost_snapshot IS NULL THEN 1 ELSE 0 END), 0)
+         FROM sales
+         WHERE (?1 IS NULL OR ts >= ?1)
+           AND (?2 IS NULL OR ts < ?2)",
+        params![from, to],
+        |row| {
+            Ok(SalesCostTotals {
+                revenue: row.get(0)?,
+                costed_revenue: row.get(1)?,
+                cogs: row.get(2)?,
+                uncosted_sales: row.get(3)?,
+            })
+        },
+    )?;
+    Ok(totals)
+}
 
-    let mut stmt = tx.prepare(sql).map_err(map_sql_err)?;
-    let mut rows = stmt
-        .query(params![payload.product_id, payload.customer_id])
-        .map_err(map_sql_err)?;
+#[derive(Debug, Serialize)]
+struct ProfitAndLoss {
+    revenue: f64,
+    cogs: f64,
+    gross_profit: f64,
+    other_income: f64,
+    expenses: f64,
+    net_profit: f64,
+    uncosted_sales: i64,
+}
 
-    struct OutstandingSale {
-        sale_id: i64,
-        price_snapshot: f64,
-        customer_id: Option<i64>,
-        was_credit: bool,
-        available: f64,
-    }
+// revenue covers every sale net of returns; sales without a cost snapshot add revenue but no cost,
+// so uncosted_sales tells the owner how much of the margin is unverified
+#[tauri::command]
+fn profit_and_loss(
+    state: State<DbState>,
+    from: Option<String>,
+    to: Option<String>,
+) -> CommandResult<ProfitAndLoss> {
+    let conn = state.open().map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let (from, to) = resolve_date_range(tz, from.as_deref(), to.as_deref()).map_err(map_app_err)?;
+    let totals =
+        fetch_sales_cost_totals(&conn, from.as_deref(), to.as_deref()).map_err(map_app_err)?;
+    let other_income =
+        sum_cash_entries(&conn, CashEntryKind::Income, from.as_deref(), to.as_deref())
+            .map_err(map_app_err)?;
+    let expenses = sum_cash_entries(
+        &conn,
+        CashEntryKind::Expense,
+        from.as_deref(),
+        to.as_deref(),
+    )
+    .map_err(map_app_err)?;
+
+    let gross_profit = totals.revenue - totals.cogs;
+    Ok(ProfitAndLoss {
+        revenue: totals.revenue,
+        cogs: totals.cogs,
+        gross_profit,
+        other_income,
+        expenses,
+        net_profit: gross_profit + other_income - expenses,
+        uncosted_sales: totals.uncosted_sales,
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct BasketBucket {
+    line_items: i64,
+    // the last bucket also holds every larger basket
+    or_more: bool,
+    baskets: i64,
+}
 
-    let mut outstanding_sales: Vec<OutstandingSale> = Vec::new();
-    let mut total_available = 0.0;
+#[derive(Debug, Serialize)]
+struct BasketMetrics {
+    baskets: i64,
+    average_line_items: f64,
+    average_value: f64,
+    distribution: Vec<BasketBucket>,
+}
 
-    while let Some(row) = rows.next().map_err(map_sql_err)? {
-        let sale_id: i64 = row.get(0).map_err(map_sql_err)?;
-        let sale_qty: f64 = row.get(1).map_err(map_sql_err)?;
-        let price_snapshot: f64 = row.get(2).map_err(map_sql_err)?;
-        let was_credit = row
-            .get::<_, i64>(3)
-            .map_err(map_sql_err)?
-            != 0;
-        let customer_id = row
-            .get::<_, Option<i64>>(4)
-            .map_err(map_sql_err)?;
-        let returned: f64 = row.get(5).map_err(map_sql_err)?;
-        let available = sale_qty - returned;
-        if available > 0.0 {
-            total_available += available;
-            outstanding_sales.push(OutstandingSale {
-                sale_id,
-                price_snapshot,
-                customer_id,
-                was_credit,
-                available,
-            });
-        }
-    }
-    drop(rows);
-    drop(stmt);
+// baskets of 1 .. BASKET_BUCKETS line items, the last one open-ended
+const BASKET_BUCKETS: i64 = 5;
 
-    if outstanding_sales.is_empty() {
-        return Err(AppError::Validation("반품 가능한 판매 내역이 없습니다.".into()).into());
-    }
+// an invoice is one basket and a record_sale sale on its own is a one-item basket;
+// returns are left out so a basket reflects what was rung up
+#[tauri::command]
+fn basket_metrics(
+    state: State<DbState>,
+    from: Option<String>,
+    to: Option<String>,
+) -> CommandResult<BasketMetrics> {
+    let conn = state.open().map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let (from, to) = resolve_date_range(tz, from.as_deref(), to.as_deref()).map_err(map_app_err)?;
+    // sale ids are negated so they never collide with an invoice id
+    let mut stmt = conn
+        .prepare(
+            "SELECT COUNT(*), SUM(total_amount)
+             FROM sales
+             WHERE is_return = 0
+               AND (?1 IS NULL OR ts >= ?1)
+               AND (?2 IS NULL OR ts < ?2)
+             GROUP BY IFNULL(invoice_id, -id)",
+        )
+        .map_err(map_sql_err)?;
+    let rows = stmt
+        .query_map(params![from, to], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?))
+        })
+        .map_err(map_sql_err)?;
 
-    if total_available + f64::EPSILON < payload.qty {
-        return Err(AppError::Validation("반품 수량이 남은 수량을 초과했습니다.".into()).into());
+    let mut distribution: Vec<BasketBucket> = (1..=BASKET_BUCKETS)
+        .map(|line_items| BasketBucket {
+            line_items,
+            or_more: line_items == BASKET_BUCKETS,
+            baskets: 0,
+        })
+        .collect();
+    let mut baskets = 0;
+    let mut line_items = 0;
+    let mut value = 0.0;
+    for row in rows {
+        let (items, amount) = row.map_err(map_sql_err)?;
+        baskets += 1;
+        line_items += items;
+        value += amount;
+        let bucket = items.clamp(1, BASKET_BUCKETS) - 1;
+        distribution[bucket as usize].baskets += 1;
     }
 
-    let ts = now_iso();
+    let average = |total: f64| {
+        if baskets > 0 {
+            total / baskets as f64
+        } else {
+            0.0
+        }
+    };
+    Ok(BasketMetrics {
+        baskets,
+        average_line_items: average(line_items as f64),
+        average_value: average(value),
+        distribution,
+    })
+}
 
-    tx.execute(
-        "UPDATE products SET qty = qty + ? WHERE id = ?",
-        params![payload.qty, payload.product_id],
-    )
-    .map_err(map_sql_err)?;
+// label for the bucket of sales with no customer attached
+const WALK_IN_CUSTOMER_NAME: &str = "비회원";
 
-    let mut remaining_qty = payload.qty;
-    let mut computed_total = 0.0;
-    for entry in outstanding_sales {
-        if remaining_qty <= 0.0 {
-            break;
+#[derive(Debug, Serialize)]
+struct CustomerProfit {
+    // None is the walk-in bucket, which also takes sales of customers since deleted
+    customer_id: Option<i64>,
+    customer_name: String,
+    revenue: f64,
+    discounts: f64,
+    cogs: f64,
+    gross_profit: f64,
+    uncosted_sales: i64,
+}
+
+// revenue net of returns as in profit_and_loss, less discounts granted in the same period
+#[tauri::command]
+fn profit_by_customer(
+    state: State<DbState>,
+    from: Option<String>,
+    to: Option<String>,
+) -> CommandResult<Vec<CustomerProfit>> {
+    let conn = state.open().map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let (from, to) = resolve_date_range(tz, from.as_deref(), to.as_deref()).map_err(map_app_err)?;
+
+    let mut profits: BTreeMap<Option<i64>, CustomerProfit> = BTreeMap::new();
+    {
+        let mut stmt = conn
+            .prepare(
+                "SELECT s.customer_id, c.name,
+                        IFNULL(SUM(CASE WHEN s.is_return = 1 THEN -s.total_amount ELSE s.total_amount END), 0),
+                        IFNULL(SUM(CASE WHEN s.cost_snapshot IS NULL THEN 0
+                                        WHEN s.is_return = 1 THEN -s.cost_snapshot * s.qty
+                                        ELSE s.cost_snapshot * s.qty END), 0),
+                        IFNULL(SUM(CASE WHEN s.cost_snapshot IS NULL THEN 1 ELSE 0 END), 0)
+                 FROM sales s
+                 LEFT JOIN customers c ON c.id = s.customer_id
+                 WHERE (?1 IS NULL OR s.ts >= ?1)
+                   AND (?2 IS NULL OR s.ts < ?2)
+                 GROUP BY s.customer_id",
+            )
+            .map_err(map_sql_err)?;
+        let rows = stmt
+            .query_map(params![from, to], |row| {
+                let customer_id: Option<i64> = row.get(0)?;
+                let customer_name: Option<String> = row.get(1)?;
+                Ok(CustomerProfit {
+                    customer_id,
+                    customer_name: customer_name
+                        .unwrap_or_else(|| WALK_IN_CUSTOMER_NAME.to_string()),
+                    revenue: row.get(2)?,
+                    discounts: 0.0,
+                    cogs: row.get(3)?,
+                    gross_profit: 0.0,
+                    uncosted_sales: row.get(4)?,
+                })
+            })
+            .map_err(map_sql_err)?;
+        for row in rows {
+            let profit = row.map_err(map_sql_err)?;
+            profits.insert(profit.customer_id, profit);
         }
-        let portion = remaining_qty.min(entry.available);
-        insert_return_for_sale(
-            &tx,
-            &ts,
-            payload.product_id,
-            portion,
-            entry.price_snapshot,
-            entry.customer_id,
-            entry.was_credit,
-            entry.sale_id,
-            payload.note.as_deref(),
-        )
-        .map_err(map_app_err)?;
-        computed_total += portion * entry.price_snapshot;
-        remaining_qty -= portion;
     }
-
-    // If an override amount is provided and differs from computed_total, add an adjustment credit/payment
-    if let Some(override_amount) = payload.override_amount {
-        let diff = override_amount - computed_total;
-        if diff.abs() > f64::EPSILON {
-            if let Some(cid) = payload.customer_id {
-                tx.execute(
-                    "INSERT INTO credits (ts, customer_id, sale_id, amount, is_payment, note) VALUES (?, ?, NULL, ?, ?, ?)",
-                    params![
-                        ts,
-                        cid,
-                        diff.abs(),
-                        if diff > 0.0 { 0 } else { 1 },
-                        Some("반품 금액 조정")
-                    ],
-                )
-                .map_err(map_sql_err)?;
-            }
+    {
+        let mut stmt = conn
+            .prepare(
+                "SELECT cr.customer_id, c.name, SUM(cr.amount)
+                 FROM credits cr
+                 JOIN customers c ON c.id = cr.customer_id
+                 WHERE cr.is_discount = 1
+                   AND (?1 IS NULL OR cr.ts >= ?1)
+                   AND (?2 IS NULL OR cr.ts < ?2)
+                 GROUP BY cr.customer_id, c.name",
+            )
+            .map_err(map_sql_err)?;
+        let rows = stmt
+            .query_map(params![from, to], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, f64>(2)?,
+                ))
+            })
+            .map_err(map_sql_err)?;
+        for row in rows {
+            let (customer_id, customer_name, discounts) = row.map_err(map_sql_err)?;
+            profits
+                .entry(Some(customer_id))
+                .or_insert_with(|| CustomerProfit {
+                    customer_id: Some(customer_id),
+                    customer_name,
+                    revenue: 0.0,
+                    discounts: 0.0,
+                    cogs: 0.0,
+                    gross_profit: 0.0,
+                    uncosted_sales: 0,
+                })
+                .discounts = discounts;
         }
     }
 
-    tx.commit().map_err(map_sql_err)?;
-    load_app_data(&state).map_err(Into::into)
+    let mut profits: Vec<CustomerProfit> = profits
+        .into_values()
+        .map(|mut profit| {
+            profit.gross_profit = profit.revenue - profit.discounts - profit.cogs;
+            profit
+        })
+        .collect();
+    profits.sort_by(|a, b| b.gross_profit.total_cmp(&a.gross_profit));
+    Ok(profits)
 }
 
-fn insert_return_for_sale(
-    tx: &rusqlite::Transaction<'_>,
-    ts: &str,
-    product_id: i64,
-    qty: f64,
-    price_snapshot: f64,
-    customer_id: Option<i64>,
-    was_credit: bool,
-    origin_sale_id: i64,
-    note: Option<&str>,
-) -> Result<(), AppError> {
-    let total_amount = price_snapshot * qty;
+#[tauri::command]
+fn debtors(state: State<DbState>, min_outstanding: f64) -> CommandResult<Vec<CustomerBalance>> {
+    let conn = state.open().map_err(map_app_err)?;
+    fetch_debtors(&conn, min_outstanding.max(0.0)).map_err(Into::into)
+}
 
-    tx.execute(
-        "INSERT INTO sales (ts, product_id, qty, price_snapshot, total_amount, customer_id, note, is_credit, is_return, origin_sale_id)
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?, 1, ?)",
-        params![
-            ts,
-            product_id,
-            qty,
-            price_snapshot,
-            total_amount,
-            customer_id,
-            note,
-            if was_credit { 1 } else { 0 },
-            origin_sale_id
-        ],
-    )?;
+#[derive(Debug, Default, Serialize)]
+struct CustomerAging {
+    customer_id: i64,
+    customer_name: String,
+    customer_phone: Option<String>,
+    days_0_30: f64,
+    days_31_60: f64,
+    days_61_90: f64,
+    days_over_90: f64,
+    outstanding: f64,
+}
 
-    let return_sale_id = tx.last_insert_rowid();
+#[tauri::command]
+fn customer_aging(state: State<DbState>) -> CommandResult<Vec<CustomerAging>> {
+    let conn = state.open().map_err(map_app_err)?;
+    fetch_customer_aging(&conn).map_err(Into::into)
+}
 
-    tx.execute(
-        "INSERT INTO transactions (ts, kind, product_id, qty, unit_price, total_amount, customer_id, note, sale_id)
-         VALUES (?, 'RETURN', ?, ?, ?, ?, ?, ?, ?)",
-        params![
-            ts,
-            product_id,
-            qty,
-            price_snapshot,
-            total_amount,
-            customer_id,
-            note,
-            return_sale_id
-        ],
-    )?;
+fn fetch_customer_aging(conn: &Connection) -> Result<Vec<CustomerAging>, AppError> {
+    struct Ledger {
+        name: String,
+        phone: Option<String>,
+        charges: Vec<(String, f64)>,
+        paid: f64,
+    }
 
-    if was_credit {
-        if let Some(cid) = customer_id {
-            let credit_note = note.unwrap_or("반품 정산");
-            tx.execute(
-                "INSERT INTO credits (ts, customer_id, sale_id, return_id, amount, is_payment, note)
-                 VALUES (?, ?, ?, ?, ?, 1, ?)",
-                params![ts, cid, origin_sale_id, return_sale_id, total_amount, credit_note],
-            )?;
+    let mut ledgers: BTreeMap<i64, Ledger> = BTreeMap::new();
+    {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT c.id, c.name, c.phone, cr.ts, cr.amount, cr.is_payment, ({})
+             FROM credits cr
+             JOIN customers c ON c.id = cr.customer_id
+             ORDER BY c.id, cr.ts ASC, cr.id ASC",
+            credit_charge_sql("cr")
+        ))?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let customer_id: i64 = row.get(0)?;
+            let ts: String = row.get(3)?;
+            let amount: f64 = row.get(4)?;
+            let is_payment = row.get::<_, i64>(5)? != 0;
+            // same rule as fetch_customer_balances: only sale/invoice/transfer/adjustment charges count as debt
+            let counts_as_charge = row.get::<_, i64>(6)? != 0;
+            let ledger = match ledgers.entry(customer_id) {
+                std::collections::btree_map::Entry::Occupied(entry) => entry.into_mut(),
+                std::collections::btree_map::Entry::Vacant(entry) => entry.insert(Ledger {
+                    name: row.get(1)?,
+                    phone: row.get(2)?,
+                    charges: Vec::new(),
+                    paid: 0.0,
+                }),
+            };
+            if is_payment {
+                ledger.paid += amount;
+            } else if counts_as_charge {
+                ledger.charges.push((ts, amount));
+            }
         }
     }
 
-    Ok(())
+    let now = Utc::now();
+    let mut report = Vec::new();
+    for (customer_id, ledger) in ledgers {
+        let mut aging = CustomerAging {
+            customer_id,
+            customer_name: ledger.name,
+            customer_phone: ledger.phone,
+            ..Default::default()
+        };
+        // payments settle the oldest charges first
+        let mut unapplied = ledger.paid;
+        for (ts, amount) in ledger.charges {
+            let applied = unapplied.min(amount);
+            unapplied -= applied;
+            let open = amount - applied;
+            if open <= f64::EPSILON {
+                continue;
+            }
+            let age_days = DateTime::parse_from_rfc3339(&ts)
+                .map(|charged| now.signed_duration_since(charged).num_days())
+                .unwrap_or(0);
+            match age_days {
+                ..=30 => aging.days_0_30 += open,
+                31..=60 => aging.days_31_60 += open,
+                61..=90 => aging.days_61_90 += open,
+                _ => aging.days_over_90 += open,
+            }
+            aging.outstanding += open;
+        }
+        if aging.outstanding > f64::EPSILON {
+            report.push(aging);
+        }
+    }
+    report.sort_by(|a, b| b.outstanding.total_cmp(&a.outstanding));
+    Ok(report)
 }
 
-
 #[derive(Debug, Deserialize)]
-struct CreditPaymentPayload {
+struct CreditAdditionPayload {
     customer_id: i64,
     amount: f64,
     note: Option<String>,
 }
 
 #[tauri::command]
-fn record_credit_payment(
+fn record_credit_add(
     state: State<DbState>,
-    payload: CreditPaymentPayload,
+    payload: CreditAdditionPayload,
 ) -> CommandResult<AppData> {
     if payload.amount <= 0.0 {
-        return Err(AppError::Validation("결제 금액은 0보다 커야 합니다.".into()).into());
+        return Err(AppError::Validation("금액은 0보다 커야 합니다.".into()).into());
+    }
+    let conn = state.open().map_err(map_app_err)?;
+    // validate customer
+    let exists = conn
+        .query_row(
+            "SELECT 1 FROM customers WHERE id = ?",
+            params![payload.customer_id],
+            |_| Ok(()),
+        )
+        .optional()
+        .map_err(map_sql_err)?;
+    if exists.is_none() {
+        return Err(AppError::Validation("존재하지 않는 고객입니다.".into()).into());
+    }
+    // insert credit (is_payment = 0)
+    conn.execute(
+        "INSERT INTO credits (ts, customer_id, sale_id, amount, is_payment, note) VALUES (?, ?, NULL, ?, 0, ?)",
+        params![now_iso(), payload.customer_id, payload.amount, payload.note.as_deref()],
+    )
+    .map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+// a write-off is booked as a payment and a fee as a charge, both flagged is_adjustment so
+// reports can tell them apart from money actually received or sales made on credit
+#[tauri::command]
+fn adjust_credit(
+    state: State<DbState>,
+    customer_id: i64,
+    amount: f64,
+    is_write_off: bool,
+    note: Option<String>,
+) -> CommandResult<AppData> {
+    if amount <= 0.0 {
+        return Err(AppError::Validation("금액은 0보다 커야 합니다.".into()).into());
+    }
+    let note = note
+        .as_deref()
+        .map(str::trim)
+        .filter(|note| !note.is_empty());
+    if is_write_off && note.is_none() {
+        return Err(AppError::Validation("탕감 사유(메모)를 입력해주세요.".into()).into());
     }
+    ensure_text_len("note", note, MAX_NOTE_LEN).map_err(map_app_err)?;
 
     let conn = state.open().map_err(map_app_err)?;
-
     let exists = conn
         .query_row(
             "SELECT 1 FROM customers WHERE id = ?",
-            params![payload.customer_id],
+            params![customer_id],
             |_| Ok(()),
         )
         .optional()
         .map_err(map_sql_err)?;
-
     if exists.is_none() {
         return Err(AppError::Validation("존재하지 않는 고객입니다.".into()).into());
     }
 
+    let label = if is_write_off {
+        "외상 탕감"
+    } else {
+        "외상 조정"
+    };
+    let note = match note {
+        Some(note) => format!("[{label}] {note}"),
+        None => label.to_string(),
+    };
+    let amount = round_amount(amount, amount_decimals(&conn).map_err(map_app_err)?);
     conn.execute(
-        "INSERT INTO credits (ts, customer_id, sale_id, amount, is_payment, note) VALUES (?, ?, NULL, ?, 1, ?)",
+        "INSERT INTO credits (ts, customer_id, amount, is_payment, note, is_adjustment) VALUES (?, ?, ?, ?, ?, 1)",
         params![
             now_iso(),
-            payload.customer_id,
-            payload.amount,
-            payload.note.as_deref()
+            customer_id,
+            amount,
+            if is_write_off { 1 } else { 0 },
+            note
         ],
     )
     .map_err(map_sql_err)?;
-
     load_app_data(&state).map_err(Into::into)
 }
 
-#[derive(Debug, Deserialize)]
-struct CreditAdditionPayload {
+// booked as a payment flagged is_discount; tied to a sale it may not exceed what is still owed on it
+#[tauri::command]
+fn grant_discount(
+    state: State<DbState>,
     customer_id: i64,
+    sale_id: Option<i64>,
     amount: f64,
     note: Option<String>,
-}
-
-#[tauri::command]
-fn record_credit_add(
-    state: State<DbState>,
-    payload: CreditAdditionPayload,
 ) -> CommandResult<AppData> {
-    if payload.amount <= 0.0 {
+    if amount <= 0.0 {
         return Err(AppError::Validation("금액은 0보다 커야 합니다.".into()).into());
     }
-    let conn = state.open().map_err(map_app_err)?;
-    // validate customer
-    let exists = conn
+    let note = clean_text(note.as_deref());
+    ensure_text_len("note", note, MAX_NOTE_LEN).map_err(map_app_err)?;
+
+    let mut conn = state.open().map_err(map_app_err)?;
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    let exists = tx
         .query_row(
             "SELECT 1 FROM customers WHERE id = ?",
-            params![payload.customer_id],
+            params![customer_id],
             |_| Ok(()),
         )
         .optional()
@@ -1291,12 +8271,63 @@ fn record_credit_add(
     if exists.is_none() {
         return Err(AppError::Validation("존재하지 않는 고객입니다.".into()).into());
     }
-    // insert credit (is_payment = 0)
-    conn.execute(
-        "INSERT INTO credits (ts, customer_id, sale_id, amount, is_payment, note) VALUES (?, ?, NULL, ?, 0, ?)",
-        params![now_iso(), payload.customer_id, payload.amount, payload.note.as_deref()],
+    let amount = round_amount(amount, amount_decimals(&tx).map_err(map_app_err)?);
+
+    if let Some(sale_id) = sale_id {
+        let sale = tx
+            .query_row(
+                "SELECT customer_id, is_credit, is_return FROM sales WHERE id = ?",
+                params![sale_id],
+                |row| {
+                    Ok((
+                        row.get::<_, Option<i64>>(0)?,
+                        row.get::<_, i64>(1)? != 0,
+                        row.get::<_, i64>(2)? != 0,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(map_sql_err)?;
+        match sale {
+            None => {
+                return Err(AppError::Validation("존재하지 않는 판매입니다.".into()).into());
+            }
+            Some((owner, is_credit, is_return))
+                if owner != Some(customer_id) || !is_credit || is_return =>
+            {
+                return Err(AppError::Validation(
+                    "해당 고객의 외상 판매에만 할인할 수 있습니다.".into(),
+                )
+                .into());
+            }
+            Some(_) => {}
+        }
+        let remaining = tx
+            .query_row(
+                "SELECT IFNULL(SUM(CASE WHEN is_payment = 0 THEN amount ELSE -amount END), 0)
+                 FROM credits
+                 WHERE sale_id = ?",
+                params![sale_id],
+                |row| row.get::<_, f64>(0),
+            )
+            .map_err(map_sql_err)?;
+        if amount > remaining + f64::EPSILON {
+            return Err(
+                AppError::Validation("할인 금액이 판매의 남은 잔액을 초과합니다.".into()).into(),
+            );
+        }
+    }
+
+    let note = match note {
+        Some(note) => format!("[할인] {note}"),
+        None => "할인".to_string(),
+    };
+    tx.execute(
+        "INSERT INTO credits (ts, customer_id, sale_id, amount, is_payment, note, is_discount) VALUES (?, ?, ?, ?, 1, ?, 1)",
+        params![now_iso(), customer_id, sale_id, amount, note],
     )
     .map_err(map_sql_err)?;
+    tx.commit().map_err(map_sql_err)?;
     load_app_data(&state).map_err(Into::into)
 }
 
@@ -1308,21 +8339,150 @@ fn load_app_data(state: &DbState) -> Result<AppData, AppError> {
 fn build_app_data(conn: &Connection) -> Result<AppData, AppError> {
     let products = fetch_products(conn)?;
     let customers = fetch_customers(conn)?;
+    let suppliers = fetch_suppliers(conn)?;
     let sales = fetch_sales(conn)?;
     let stock_movements = fetch_transactions(conn)?;
     let credits = fetch_credits(conn)?;
     let customer_balances = fetch_customer_balances(conn)?;
+    let cash_entries = fetch_cash_entries_between(conn, None, None)?;
+    let settings = fetch_settings(conn)?;
 
     Ok(AppData {
         products,
         customers,
+        suppliers,
         sales,
         stock_movements,
         credits,
         customer_balances,
+        cash_entries,
+        settings,
+    })
+}
+
+fn fetch_settings(conn: &Connection) -> Result<AppSettings, AppError> {
+    let currency_code = read_currency_code(conn)?;
+    let currency_symbol = match read_setting(conn, "currency_symbol")? {
+        Some(symbol) => symbol,
+        None => default_currency_symbol(&currency_code).to_string(),
+    };
+    Ok(AppSettings {
+        currency_decimals: currency_decimals(&currency_code),
+        currency_code,
+        currency_symbol,
+        price_override_max_multiplier: read_setting_f64(
+            conn,
+            "price_override_max_multiplier",
+            10.0,
+        )?,
+        allow_below_cost_sales: read_setting_bool(conn, "allow_below_cost_sales", false)?,
+        return_note_qty_threshold: read_setting_f64(
+            conn,
+            "return_note_qty_threshold",
+            DEFAULT_RETURN_NOTE_QTY_THRESHOLD,
+        )?,
+        return_note_amount_threshold: read_setting_f64(
+            conn,
+            "return_note_amount_threshold",
+            DEFAULT_RETURN_NOTE_AMOUNT_THRESHOLD,
+        )?,
+        default_low_stock_threshold: read_setting_f64(
+            conn,
+            "default_low_stock_threshold",
+            DEFAULT_LOW_STOCK_THRESHOLD,
+        )?,
+        timezone: match BusinessTz::read(conn)? {
+            BusinessTz::Named(tz) => Some(tz.name().to_string()),
+            BusinessTz::System => None,
+        },
+        auto_backup: read_setting_bool(conn, "auto_backup", false)?,
+        backup_retention: read_backup_retention(conn)?,
+        shop_name: read_setting(conn, "shop_name")?,
+        journal_accounts: JournalAccounts::read(conn)?,
+        cash_rounding: read_setting_f64(conn, "cash_rounding", 0.0)?,
     })
 }
 
+fn default_currency_symbol(code: &str) -> &'static str {
+    match code {
+        "KRW" => "₩",
+        "USD" => "$",
+        "EUR" => "€",
+        "JPY" => "¥",
+        _ => "",
+    }
+}
+
+fn currency_decimals(code: &str) -> usize {
+    match code {
+        "KRW" | "JPY" | "VND" => 0,
+        _ => 2,
+    }
+}
+
+fn read_currency_code(conn: &Connection) -> Result<String, AppError> {
+    Ok(read_setting(conn, "currency_code")?
+        .map(|code| code.trim().to_uppercase())
+        .unwrap_or_else(|| "KRW".to_string()))
+}
+
+fn amount_decimals(conn: &Connection) -> Result<usize, AppError> {
+    Ok(currency_decimals(&read_currency_code(conn)?))
+}
+
+// every stored total is rounded to the currency's minor unit so displayed and summed amounts agree
+fn round_amount(amount: f64, decimals: usize) -> f64 {
+    let factor = 10f64.powi(decimals as i32);
+    (amount * factor).round() / factor
+}
+
+// nearest multiple of the cash_rounding setting, or the amount unchanged when it is off
+fn round_cash(conn: &Connection, amount: f64) -> Result<f64, AppError> {
+    let decimals = amount_decimals(conn)?;
+    let step = read_setting_f64(conn, "cash_rounding", 0.0)?;
+    if step <= 0.0 {
+        return Ok(round_amount(amount, decimals));
+    }
+    Ok(round_amount((amount / step).round() * step, decimals))
+}
+
+// what a sale line is charged and the rounding that got it there; cash changes hands in whole
+// coins while a credit sale stays exact until it is paid
+fn charged_amount(
+    conn: &Connection,
+    line_amount: f64,
+    is_credit: bool,
+) -> Result<(f64, f64), AppError> {
+    if is_credit {
+        return Ok((line_amount, 0.0));
+    }
+    let decimals = amount_decimals(conn)?;
+    let total = round_cash(conn, line_amount)?;
+    Ok((total, round_amount(total - line_amount, decimals)))
+}
+
+fn format_currency(amount: f64, symbol: &str, decimals: usize) -> String {
+    let fixed = format!("{:.*}", decimals, amount.abs());
+    let (int_part, frac_part) = match fixed.split_once('.') {
+        Some((int_part, frac_part)) => (int_part, Some(frac_part)),
+        None => (fixed.as_str(), None),
+    };
+    let mut grouped = String::new();
+    for (idx, ch) in int_part.chars().enumerate() {
+        if idx > 0 && (int_part.len() - idx) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(ch);
+    }
+    if let Some(frac_part) = frac_part {
+        grouped.push('.');
+        grouped.push_str(frac_part);
+    }
+    // avoid "-₩0" when a tiny negative rounds away
+    let is_negative = amount < 0.0 && fixed.chars().any(|ch| ch.is_ascii_digit() && ch != '0');
+    format!("{}{symbol}{grouped}", if is_negative { "-" } else { "" })
+}
+
 fn ensure_column(
     conn: &mut Connection,
     table: &str,
@@ -1342,25 +8502,46 @@ fn ensure_column(
     Ok(())
 }
 
+const PRODUCT_COLUMNS: &str =
+    "id, name, sku, unit_price, qty, note, low_stock_threshold, created_at, archived, cost_price, version, allow_negative_stock, reorder_multiple, qty_step, damaged_qty, tax_rate, reorder_point, preferred_supplier_id";
+
+fn product_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Product> {
+    Ok(Product {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        sku: row.get(2)?,
+        unit_price: row.get(3)?,
+        qty: row.get(4)?,
+        note: row.get(5)?,
+        low_stock_threshold: row.get(6)?,
+        created_at: row.get(7)?,
+        archived: row.get::<_, i64>(8)? != 0,
+        cost_price: row.get(9)?,
+        version: row.get(10)?,
+        allow_negative_stock: row.get::<_, i64>(11)? != 0,
+        reorder_multiple: row.get(12)?,
+        qty_step: row.get(13)?,
+        damaged_qty: row.get(14)?,
+        tax_rate: row.get(15)?,
+        reorder_point: row.get(16)?,
+        preferred_supplier_id: row.get(17)?,
+    })
+}
+
 fn fetch_products(conn: &Connection) -> Result<Vec<Product>, AppError> {
-    let mut stmt = conn.prepare(
-        "SELECT id, name, sku, unit_price, qty, note, low_stock_threshold, created_at
+    fetch_products_sorted(conn, ProductSort::NameAsc)
+}
+
+fn fetch_products_sorted(conn: &Connection, sort: ProductSort) -> Result<Vec<Product>, AppError> {
+    let sql = format!(
+        "SELECT {PRODUCT_COLUMNS}
          FROM products
          WHERE archived = 0
-         ORDER BY name COLLATE NOCASE",
-    )?;
-    let rows = stmt.query_map([], |row| {
-        Ok(Product {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            sku: row.get(2)?,
-            unit_price: row.get(3)?,
-            qty: row.get(4)?,
-            note: row.get(5)?,
-            low_stock_threshold: row.get(6)?,
-            created_at: row.get(7)?,
-        })
-    })?;
+         ORDER BY {}",
+        sort.order_by()
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], product_from_row)?;
 
     let mut products = Vec::new();
     for row in rows {
@@ -1369,14 +8550,93 @@ fn fetch_products(conn: &Connection) -> Result<Vec<Product>, AppError> {
     Ok(products)
 }
 
+fn fetch_product(conn: &Connection, product_id: i64) -> Result<Option<Product>, AppError> {
+    let sql = format!("SELECT {PRODUCT_COLUMNS} FROM products WHERE id = ?");
+    let product = conn
+        .query_row(&sql, params![product_id], product_from_row)
+        .optional()?;
+    Ok(product)
+}
+
+const CUSTOMER_COLUMNS: &str = "id, name, phone, note, created_at, version, on_hold, hold_reason";
+
+fn customer_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Customer> {
+    Ok(Customer {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        phone: row.get(2)?,
+        note: row.get(3)?,
+        created_at: row.get(4)?,
+        version: row.get(5)?,
+        on_hold: row.get::<_, i64>(6)? != 0,
+        hold_reason: row.get(7)?,
+    })
+}
+
 fn fetch_customers(conn: &Connection) -> Result<Vec<Customer>, AppError> {
+    let sql = format!("SELECT {CUSTOMER_COLUMNS} FROM customers ORDER BY name COLLATE NOCASE");
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], customer_from_row)?;
+
+    let mut customers = Vec::new();
+    for row in rows {
+        customers.push(row?);
+    }
+    Ok(customers)
+}
+
+const SALE_SELECT: &str = "SELECT
+        s.id,
+        s.ts,
+        s.product_id,
+        p.name,
+        s.qty,
+        s.price_snapshot,
+        s.total_amount,
+        s.customer_id,
+        COALESCE(c.name, s.customer_name_snapshot),
+        COALESCE(c.phone, s.customer_phone_snapshot),
+        s.note,
+        s.is_credit,
+        s.is_return,
+        s.origin_sale_id,
+        s.customer_deleted,
+        s.invoice_id,
+        s.rounding_adjustment
+    FROM sales s
+    JOIN products p ON p.id = s.product_id
+    LEFT JOIN customers c ON c.id = s.customer_id";
+
+fn sale_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<SaleRecord> {
+    Ok(SaleRecord {
+        id: row.get(0)?,
+        ts: row.get(1)?,
+        product_id: row.get(2)?,
+        product_name: row.get(3)?,
+        qty: row.get(4)?,
+        unit_price: row.get(5)?,
+        total_amount: row.get(6)?,
+        customer_id: row.get(7)?,
+        customer_name: row.get(8)?,
+        customer_phone: row.get(9)?,
+        note: row.get(10)?,
+        is_credit: row.get::<_, i64>(11)? != 0,
+        is_return: row.get::<_, i64>(12)? != 0,
+        origin_sale_id: row.get(13)?,
+        customer_deleted: row.get::<_, i64>(14)? != 0,
+        invoice_id: row.get(15)?,
+        rounding_adjustment: row.get(16)?,
+    })
+}
+
+fn fetch_suppliers(conn: &Connection) -> Result<Vec<Supplier>, AppError> {
     let mut stmt = conn.prepare(
         "SELECT id, name, phone, note, created_at
-         FROM customers
+         FROM suppliers
          ORDER BY name COLLATE NOCASE",
     )?;
     let rows = stmt.query_map([], |row| {
-        Ok(Customer {
+        Ok(Supplier {
             id: row.get(0)?,
             name: row.get(1)?,
             phone: row.get(2)?,
@@ -1385,56 +8645,25 @@ fn fetch_customers(conn: &Connection) -> Result<Vec<Customer>, AppError> {
         })
     })?;
 
-    let mut customers = Vec::new();
+    let mut suppliers = Vec::new();
     for row in rows {
-        customers.push(row?);
+        suppliers.push(row?);
     }
-    Ok(customers)
+    Ok(suppliers)
 }
 
-fn fetch_sales(conn: &Connection) -> Result<Vec<SaleRecord>, AppError> {
-    let mut stmt = conn.prepare(
-        "SELECT
-            s.id,
-            s.ts,
-            s.product_id,
-            p.name,
-            s.qty,
-            s.price_snapshot,
-            s.total_amount,
-            s.customer_id,
-            c.name,
-            c.phone,
-            s.note,
-            s.is_credit,
-            s.is_return,
-            s.origin_sale_id,
-            s.customer_deleted
-        FROM sales s
-        JOIN products p ON p.id = s.product_id
-        LEFT JOIN customers c ON c.id = s.customer_id
-        ORDER BY s.ts DESC",
-    )?;
+fn fetch_sale(conn: &Connection, sale_id: i64) -> Result<Option<SaleRecord>, AppError> {
+    let sql = format!("{SALE_SELECT} WHERE s.id = ?");
+    let sale = conn
+        .query_row(&sql, params![sale_id], sale_from_row)
+        .optional()?;
+    Ok(sale)
+}
 
-    let rows = stmt.query_map([], |row| {
-        Ok(SaleRecord {
-            id: row.get(0)?,
-            ts: row.get(1)?,
-            product_id: row.get(2)?,
-            product_name: row.get(3)?,
-            qty: row.get(4)?,
-            unit_price: row.get(5)?,
-            total_amount: row.get(6)?,
-            customer_id: row.get(7)?,
-            customer_name: row.get(8)?,
-            customer_phone: row.get(9)?,
-            note: row.get(10)?,
-            is_credit: row.get::<_, i64>(11)? != 0,
-            is_return: row.get::<_, i64>(12)? != 0,
-            origin_sale_id: row.get(13)?,
-            customer_deleted: row.get::<_, i64>(14)? != 0,
-        })
-    })?;
+fn fetch_sales(conn: &Connection) -> Result<Vec<SaleRecord>, AppError> {
+    let sql = format!("{SALE_SELECT} ORDER BY s.ts DESC");
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], sale_from_row)?;
 
     let mut sales = Vec::new();
     for row in rows {
@@ -1443,9 +8672,7 @@ fn fetch_sales(conn: &Connection) -> Result<Vec<SaleRecord>, AppError> {
     Ok(sales)
 }
 
-fn fetch_transactions(conn: &Connection) -> Result<Vec<StockMovement>, AppError> {
-    let mut stmt = conn.prepare(
-        "SELECT
+const MOVEMENT_SELECT: &str = "SELECT
             t.id,
             t.ts,
             t.kind,
@@ -1458,112 +8685,536 @@ fn fetch_transactions(conn: &Connection) -> Result<Vec<StockMovement>, AppError>
             t.customer_id,
             c.name,
             t.note,
-            t.sale_id
+            t.sale_id,
+            t.reverses_id,
+            t.supplier_id,
+            t.receipt_id,
+            t.tag
         FROM transactions t
         JOIN products p ON p.id = t.product_id
-        LEFT JOIN customers c ON c.id = t.customer_id
-        ORDER BY t.ts DESC",
-    )?;
+        LEFT JOIN customers c ON c.id = t.customer_id";
+
+fn movement_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<StockMovement> {
+    let kind_str: String = row.get(2)?;
+    let kind = TransactionKind::from_db(&kind_str).unwrap_or(TransactionKind::In);
+    Ok(StockMovement {
+        id: row.get(0)?,
+        ts: row.get(1)?,
+        kind,
+        product_id: row.get(3)?,
+        product_name: row.get(4)?,
+        qty: row.get(5)?,
+        unit_price: row.get(6)?,
+        total_amount: row.get(7)?,
+        counterparty: row.get(8)?,
+        customer_id: row.get(9)?,
+        customer_name: row.get(10)?,
+        note: row.get(11)?,
+        sale_id: row.get(12)?,
+        reverses_id: row.get(13)?,
+        supplier_id: row.get(14)?,
+        receipt_id: row.get(15)?,
+        tag: row.get(16)?,
+    })
+}
 
-    let rows = stmt.query_map([], |row| {
-        let kind_str: String = row.get(2)?;
-        let kind = TransactionKind::from_db(&kind_str).unwrap_or(TransactionKind::In);
-        Ok(StockMovement {
-            id: row.get(0)?,
-            ts: row.get(1)?,
-            kind,
-            product_id: row.get(3)?,
-            product_name: row.get(4)?,
-            qty: row.get(5)?,
-            unit_price: row.get(6)?,
-            total_amount: row.get(7)?,
-            counterparty: row.get(8)?,
-            customer_id: row.get(9)?,
-            customer_name: row.get(10)?,
-            note: row.get(11)?,
-            sale_id: row.get(12)?,
+fn fetch_transactions(conn: &Connection) -> Result<Vec<StockMovement>, AppError> {
+    let sql = format!("{MOVEMENT_SELECT} ORDER BY t.ts DESC");
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], movement_from_row)?;
+
+    let mut transactions = Vec::new();
+    for row in rows {
+        transactions.push(row?);
+    }
+    Ok(transactions)
+}
+
+const CREDIT_SELECT: &str = "SELECT
+            cr.id,
+            cr.ts,
+            cr.customer_id,
+            c.name,
+            c.phone,
+            cr.sale_id,
+            cr.amount,
+            cr.is_payment,
+            cr.note,
+            cr.is_adjustment,
+            cr.is_discount
+        FROM credits cr
+        JOIN customers c ON c.id = cr.customer_id";
+
+fn credit_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<CreditEntry> {
+    Ok(CreditEntry {
+        id: row.get(0)?,
+        ts: row.get(1)?,
+        customer_id: row.get(2)?,
+        customer_name: row.get(3)?,
+        customer_phone: row.get(4)?,
+        sale_id: row.get(5)?,
+        amount: row.get(6)?,
+        is_payment: row.get::<_, i64>(7)? != 0,
+        note: row.get(8)?,
+        is_adjustment: row.get::<_, i64>(9)? != 0,
+        is_discount: row.get::<_, i64>(10)? != 0,
+    })
+}
+
+fn fetch_credits(conn: &Connection) -> Result<Vec<CreditEntry>, AppError> {
+    let sql = format!("{CREDIT_SELECT} ORDER BY cr.ts DESC");
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], credit_from_row)?;
+
+    let mut credits = Vec::new();
+    for row in rows {
+        credits.push(row?);
+    }
+    Ok(credits)
+}
+
+// totals come from the trigger-maintained cache; recalc_balances rebuilds it from the ledger
+const CUSTOMER_BALANCE_QUERY: &str = "SELECT
+        c.id,
+        c.name,
+        c.phone,
+        c.cached_credit,
+        c.cached_paid,
+        (SELECT MAX(cr.ts) FROM credits cr WHERE cr.customer_id = c.id) AS last_activity
+    FROM customers c";
+
+// a credits row that adds to what the customer owes; payments, discounts and return refunds
+// never do. {t} stands for the table alias, NEW/OLD inside the balance triggers
+const CREDIT_CHARGE_SQL: &str = "{t}.is_payment = 0 AND ({t}.sale_id IS NOT NULL OR {t}.invoice_id IS NOT NULL OR {t}.transfer_id IS NOT NULL OR {t}.is_adjustment = 1)";
+
+fn credit_charge_sql(alias: &str) -> String {
+    CREDIT_CHARGE_SQL.replace("{t}", alias)
+}
+
+fn rebuild_balance_cache_sql() -> String {
+    format!(
+        "UPDATE customers SET
+        cached_credit = IFNULL((
+            SELECT SUM(CASE WHEN {} THEN cr.amount ELSE 0 END)
+            FROM credits cr WHERE cr.customer_id = customers.id
+        ), 0),
+        cached_paid = IFNULL((
+            SELECT SUM(CASE WHEN cr.is_payment = 1 THEN cr.amount ELSE 0 END)
+            FROM credits cr WHERE cr.customer_id = customers.id
+        ), 0)",
+        credit_charge_sql("cr")
+    )
+}
+
+// the ledger is the source of truth; this repairs any drift in the cached totals
+#[tauri::command]
+fn recalc_balances(state: State<DbState>) -> CommandResult<AppData> {
+    let conn = state.open().map_err(map_app_err)?;
+    conn.execute(&rebuild_balance_cache_sql(), [])
+        .map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+fn customer_balance_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<CustomerBalance> {
+    let total_credit: f64 = row.get(3)?;
+    let total_paid: f64 = row.get(4)?;
+    Ok(CustomerBalance {
+        customer_id: row.get(0)?,
+        customer_name: row.get(1)?,
+        customer_phone: row.get(2)?,
+        total_credit,
+        total_paid,
+        outstanding: total_credit - total_paid,
+        last_activity: row.get(5)?,
+    })
+}
+
+fn fetch_customer_balances(conn: &Connection) -> Result<Vec<CustomerBalance>, AppError> {
+    let sql = format!("{CUSTOMER_BALANCE_QUERY} ORDER BY c.name COLLATE NOCASE");
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], customer_balance_from_row)?;
+
+    let mut balances = Vec::new();
+    for row in rows {
+        balances.push(row?);
+    }
+    Ok(balances)
+}
+
+fn fetch_debtors(
+    conn: &Connection,
+    min_outstanding: f64,
+) -> Result<Vec<CustomerBalance>, AppError> {
+    let sql = format!(
+        "{CUSTOMER_BALANCE_QUERY}
+         WHERE c.cached_credit - c.cached_paid > ?
+         ORDER BY c.cached_credit - c.cached_paid DESC, last_activity ASC"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![min_outstanding], customer_balance_from_row)?;
+
+    let mut balances = Vec::new();
+    for row in rows {
+        balances.push(row?);
+    }
+    Ok(balances)
+}
+
+#[derive(Debug, Serialize)]
+struct WalCheckpoint {
+    busy: bool,
+    log_frames: i64,
+    checkpointed_frames: i64,
+}
+
+// flushes the WAL into the main database file so a plain file copy is complete
+#[tauri::command]
+fn checkpoint_wal(state: State<DbState>) -> CommandResult<WalCheckpoint> {
+    let conn = state.open().map_err(map_app_err)?;
+    run_wal_checkpoint(&conn).map_err(Into::into)
+}
+
+fn run_wal_checkpoint(conn: &Connection) -> Result<WalCheckpoint, AppError> {
+    let checkpoint = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| {
+        Ok(WalCheckpoint {
+            busy: row.get::<_, i64>(0)? != 0,
+            log_frames: row.get(1)?,
+            checkpointed_frames: row.get(2)?,
         })
     })?;
+    Ok(checkpoint)
+}
+
+const BACKUP_DIR: &str = "backups";
+const BACKUP_PREFIX: &str = "inventory-ledger-";
+const DEFAULT_BACKUP_RETENTION: usize = 7;
+const MAX_BACKUP_RETENTION: i64 = 100;
+
+fn read_backup_retention(conn: &Connection) -> Result<usize, AppError> {
+    let retention = read_setting(conn, "backup_retention")?
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .filter(|count| *count > 0)
+        .unwrap_or(DEFAULT_BACKUP_RETENTION);
+    Ok(retention)
+}
+
+// SQLite's online backup copies committed pages even while the WAL holds some of them,
+// which a plain file copy of the main database would miss
+fn write_backup(conn: &Connection, dir: &Path, retention: usize) -> Result<PathBuf, AppError> {
+    fs::create_dir_all(dir)?;
+    let stamp = Local::now().format("%Y%m%d-%H%M%S");
+    let target = dir.join(format!("{BACKUP_PREFIX}{stamp}.db"));
+    // fold the WAL into the main file first so the copy does not lean on a -wal beside it;
+    // a busy checkpoint is fine, the backup still reads committed pages through the WAL
+    run_wal_checkpoint(conn)?;
+    conn.backup(rusqlite::DatabaseName::Main, &target, None)?;
+    prune_backups(dir, retention)?;
+    Ok(target)
+}
+
+// backup names embed a sortable timestamp, so the oldest sort first
+fn backup_files(dir: &Path) -> Result<Vec<PathBuf>, AppError> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+        let is_backup = name.starts_with(BACKUP_PREFIX) && name.ends_with(".db");
+        if is_backup && path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn prune_backups(dir: &Path, retention: usize) -> Result<(), AppError> {
+    let files = backup_files(dir)?;
+    let excess = files.len().saturating_sub(retention);
+    for path in &files[..excess] {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct BackupFile {
+    file_name: String,
+    path: String,
+    size_bytes: u64,
+    modified_at: Option<String>,
+}
+
+// newest first
+#[tauri::command]
+fn list_backups(state: State<DbState>) -> CommandResult<Vec<BackupFile>> {
+    let files = backup_files(&state.backup_dir()).map_err(map_app_err)?;
+    let mut backups = Vec::new();
+    for path in files.into_iter().rev() {
+        let metadata = fs::metadata(&path).map_err(|e| map_app_err(e.into()))?;
+        backups.push(BackupFile {
+            file_name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            path: path.to_string_lossy().into_owned(),
+            size_bytes: metadata.len(),
+            modified_at: metadata
+                .modified()
+                .ok()
+                .map(|time| DateTime::<Utc>::from(time).to_rfc3339()),
+        });
+    }
+    Ok(backups)
+}
+
+// retention applies right away, so lowering it removes the oldest backups immediately
+#[tauri::command]
+fn configure_backups(
+    state: State<DbState>,
+    enabled: bool,
+    retention: i64,
+) -> CommandResult<AppData> {
+    if !(1..=MAX_BACKUP_RETENTION).contains(&retention) {
+        return Err(AppError::Validation(format!(
+            "보관 개수는 1에서 {MAX_BACKUP_RETENTION} 사이여야 합니다."
+        ))
+        .into());
+    }
+    let conn = state.open().map_err(map_app_err)?;
+    for (key, value) in [
+        ("auto_backup", if enabled { "1" } else { "0" }.to_string()),
+        ("backup_retention", retention.to_string()),
+    ] {
+        conn.execute(
+            "INSERT INTO settings (key, value) VALUES (?, ?)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        )
+        .map_err(map_sql_err)?;
+    }
+    prune_backups(&state.backup_dir(), retention as usize).map_err(map_app_err)?;
+    load_app_data(&state).map_err(Into::into)
+}
+
+// the frontend must send this back verbatim before reset_all_data wipes anything
+const RESET_CONFIRM_TOKEN: &str = "모든 데이터 삭제";
+
+// every table but settings, children before the rows they reference
+const RESET_TABLES: &[&str] = &[
+    "credits",
+    "balance_transfers",
+    "product_lots",
+    "transactions",
+    "sales",
+    "invoices",
+    "goods_receipts",
+    "price_history",
+    "daily_snapshots",
+    "drawer_reconciliations",
+    "cash_entries",
+    "products",
+    "customers",
+    "suppliers",
+];
+
+// wipes every record but keeps the schema and settings; a backup is written first so a
+// mistaken wipe can still be restored
+#[tauri::command]
+fn reset_all_data(state: State<DbState>, confirm_token: String) -> CommandResult<AppData> {
+    if confirm_token != RESET_CONFIRM_TOKEN {
+        return Err(AppError::Validation(format!(
+            "확인 문구가 일치하지 않습니다. '{RESET_CONFIRM_TOKEN}'을(를) 입력해주세요."
+        ))
+        .into());
+    }
+    let mut conn = state.open().map_err(map_app_err)?;
+    let retention = read_backup_retention(&conn).map_err(map_app_err)?;
+    write_backup(&conn, &state.backup_dir(), retention).map_err(map_app_err)?;
 
-    let mut transactions = Vec::new();
-    for row in rows {
-        transactions.push(row?);
+    let tx = conn.transaction().map_err(map_sql_err)?;
+    for table in RESET_TABLES {
+        tx.execute(&format!("DELETE FROM {table}"), [])
+            .map_err(map_sql_err)?;
     }
-    Ok(transactions)
+    // ids start over too, so the first real record is #1
+    tx.execute(
+        &format!(
+            "DELETE FROM sqlite_sequence WHERE name IN ({})",
+            vec!["?"; RESET_TABLES.len()].join(", ")
+        ),
+        rusqlite::params_from_iter(RESET_TABLES),
+    )
+    .map_err(map_sql_err)?;
+    tx.commit().map_err(map_sql_err)?;
+    load_app_data(&state).map_err(Into::into)
 }
 
-fn fetch_credits(conn: &Connection) -> Result<Vec<CreditEntry>, AppError> {
-    let mut stmt = conn.prepare(
-        "SELECT
-            cr.id,
-            cr.ts,
-            cr.customer_id,
-            c.name,
-            c.phone,
-            cr.sale_id,
-            cr.amount,
-            cr.is_payment,
-            cr.note
-        FROM credits cr
-        JOIN customers c ON c.id = cr.customer_id
-        ORDER BY cr.ts DESC",
-    )?;
+#[derive(Debug, Serialize)]
+struct ForeignKeyProblem {
+    table: String,
+    rowid: Option<i64>,
+    parent: String,
+    fkid: i64,
+}
 
-    let rows = stmt.query_map([], |row| {
-        Ok(CreditEntry {
-            id: row.get(0)?,
-            ts: row.get(1)?,
-            customer_id: row.get(2)?,
-            customer_name: row.get(3)?,
-            customer_phone: row.get(4)?,
-            sale_id: row.get(5)?,
-            amount: row.get(6)?,
-            is_payment: row.get::<_, i64>(7)? != 0,
-            note: row.get(8)?,
-        })
-    })?;
+#[derive(Debug, Serialize)]
+struct DatabaseHealth {
+    ok: bool,
+    integrity_problems: Vec<String>,
+    foreign_key_problems: Vec<ForeignKeyProblem>,
+}
 
-    let mut credits = Vec::new();
-    for row in rows {
-        credits.push(row?);
+#[tauri::command]
+fn database_health(state: State<DbState>) -> CommandResult<DatabaseHealth> {
+    let conn = state.open().map_err(map_app_err)?;
+    check_database_health(&conn).map_err(Into::into)
+}
+
+fn check_database_health(conn: &Connection) -> Result<DatabaseHealth, AppError> {
+    // integrity_check reports a single "ok" row when nothing is wrong
+    let mut integrity_problems = Vec::new();
+    {
+        let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        for row in rows {
+            let message = row?;
+            if message != "ok" {
+                integrity_problems.push(message);
+            }
+        }
     }
-    Ok(credits)
+
+    let mut foreign_key_problems = Vec::new();
+    {
+        let mut stmt = conn.prepare("PRAGMA foreign_key_check")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(ForeignKeyProblem {
+                table: row.get(0)?,
+                rowid: row.get(1)?,
+                parent: row.get(2)?,
+                fkid: row.get(3)?,
+            })
+        })?;
+        for row in rows {
+            foreign_key_problems.push(row?);
+        }
+    }
+
+    Ok(DatabaseHealth {
+        ok: integrity_problems.is_empty() && foreign_key_problems.is_empty(),
+        integrity_problems,
+        foreign_key_problems,
+    })
 }
 
-fn fetch_customer_balances(conn: &Connection) -> Result<Vec<CustomerBalance>, AppError> {
-    let mut stmt = conn.prepare(
-        "SELECT
-            c.id,
-            c.name,
-            c.phone,
-            IFNULL(SUM(CASE WHEN cr.is_payment = 0 AND cr.sale_id IS NOT NULL THEN cr.amount ELSE 0 END), 0) AS total_credit,
-            IFNULL(SUM(CASE WHEN cr.is_payment = 1 THEN cr.amount ELSE 0 END), 0) AS total_paid,
-            MAX(cr.ts)
-        FROM customers c
-        LEFT JOIN credits cr ON cr.customer_id = c.id
-        GROUP BY c.id, c.name
-        ORDER BY c.name COLLATE NOCASE",
+// domain links the foreign key check cannot see: returns whose origin was nulled or points at
+// another return, credits that disagree with their sale's customer, and rows edited with foreign keys off
+#[derive(Debug, Serialize)]
+struct OrphanReport {
+    total: usize,
+    returns_without_origin: Vec<i64>,
+    credits_without_sale: Vec<i64>,
+    credits_customer_mismatch: Vec<i64>,
+    transactions_without_product: Vec<i64>,
+    transactions_without_sale: Vec<i64>,
+}
+
+#[tauri::command]
+fn find_orphans(state: State<DbState>) -> CommandResult<OrphanReport> {
+    let conn = state.open().map_err(map_app_err)?;
+    fetch_orphan_report(&conn).map_err(Into::into)
+}
+
+fn fetch_orphan_report(conn: &Connection) -> Result<OrphanReport, AppError> {
+    let returns_without_origin = query_ids(
+        conn,
+        "SELECT r.id FROM sales r
+         WHERE r.is_return = 1
+           AND NOT EXISTS (SELECT 1 FROM sales s WHERE s.id = r.origin_sale_id AND s.is_return = 0)
+         ORDER BY r.id",
+    )?;
+    let credits_without_sale = query_ids(
+        conn,
+        "SELECT cr.id FROM credits cr
+         WHERE (cr.sale_id IS NOT NULL AND NOT EXISTS (SELECT 1 FROM sales s WHERE s.id = cr.sale_id))
+            OR (cr.return_id IS NOT NULL AND NOT EXISTS (SELECT 1 FROM sales s WHERE s.id = cr.return_id))
+         ORDER BY cr.id",
+    )?;
+    let credits_customer_mismatch = query_ids(
+        conn,
+        "SELECT cr.id FROM credits cr
+         JOIN sales s ON s.id = COALESCE(cr.sale_id, cr.return_id)
+         WHERE s.customer_id IS NOT NULL AND s.customer_id != cr.customer_id
+         ORDER BY cr.id",
+    )?;
+    let transactions_without_product = query_ids(
+        conn,
+        "SELECT t.id FROM transactions t
+         WHERE NOT EXISTS (SELECT 1 FROM products p WHERE p.id = t.product_id)
+         ORDER BY t.id",
+    )?;
+    let transactions_without_sale = query_ids(
+        conn,
+        "SELECT t.id FROM transactions t
+         WHERE t.sale_id IS NOT NULL
+           AND NOT EXISTS (SELECT 1 FROM sales s WHERE s.id = t.sale_id)
+         ORDER BY t.id",
     )?;
 
-    let rows = stmt.query_map([], |row| {
-        let total_credit: f64 = row.get(3)?;
-        let total_paid: f64 = row.get(4)?;
-        Ok(CustomerBalance {
-            customer_id: row.get(0)?,
-            customer_name: row.get(1)?,
-            customer_phone: row.get(2)?,
-            total_credit,
-            total_paid,
-            outstanding: total_credit - total_paid,
-            last_activity: row.get(5)?,
-        })
-    })?;
+    Ok(OrphanReport {
+        total: returns_without_origin.len()
+            + credits_without_sale.len()
+            + credits_customer_mismatch.len()
+            + transactions_without_product.len()
+            + transactions_without_sale.len(),
+        returns_without_origin,
+        credits_without_sale,
+        credits_customer_mismatch,
+        transactions_without_product,
+        transactions_without_sale,
+    })
+}
 
-    let mut balances = Vec::new();
+fn query_ids(conn: &Connection, sql: &str) -> Result<Vec<i64>, AppError> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map([], |row| row.get::<_, i64>(0))?;
+    let mut ids = Vec::new();
     for row in rows {
-        balances.push(row?);
+        ids.push(row?);
     }
-    Ok(balances)
+    Ok(ids)
+}
+
+#[derive(Debug, Serialize)]
+struct AppInfo {
+    app_version: String,
+    schema_version: i64,
+    supported_schema_version: i64,
+    // the file was written by a newer build; the frontend should warn before editing
+    schema_ahead: bool,
+    database_path: String,
+    // set when the backup taken at startup failed, so the frontend can warn about it
+    auto_backup_error: Option<String>,
+}
+
+#[tauri::command]
+fn app_info(state: State<DbState>) -> CommandResult<AppInfo> {
+    let conn = state.open().map_err(map_app_err)?;
+    let schema_version = read_schema_version(&conn).map_err(map_app_err)?;
+    Ok(AppInfo {
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        schema_version,
+        supported_schema_version: SCHEMA_VERSION,
+        schema_ahead: schema_version > SCHEMA_VERSION,
+        database_path: state.path.display().to_string(),
+        auto_backup_error: state.auto_backup_error.clone(),
+    })
 }
 
 #[tauri::command]
@@ -1601,3 +9252,844 @@ fn save_csv(app: tauri::AppHandle, filename: String, content: String) -> Command
         .map(|s| s.to_string())
         .unwrap_or_else(|| String::from("saved")))
 }
+
+#[tauri::command]
+fn export_customer_statement_csv(
+    state: State<DbState>,
+    customer_id: i64,
+    from: Option<String>,
+    to: Option<String>,
+    dest_path: String,
+) -> CommandResult<String> {
+    let target = csv_target(dest_path);
+    let conn = state.open().map_err(map_app_err)?;
+    let statement = fetch_customer_statement(&conn, customer_id, from.as_deref(), to.as_deref())
+        .map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let decimals = amount_decimals(&conn).map_err(map_app_err)?;
+    let amount = |value: f64| format!("{value:.decimals$}");
+
+    let mut content = String::from(CSV_BOM);
+    let mut push_row = |fields: &[&str]| push_csv_row(&mut content, fields);
+    let customer = &statement.customer;
+    push_row(&["고객", &customer.name]);
+    push_row(&["연락처", customer.phone.as_deref().unwrap_or("")]);
+    push_row(&[
+        "기간",
+        from.as_deref().unwrap_or(""),
+        to.as_deref().unwrap_or(""),
+    ]);
+    push_row(&[]);
+    push_row(&["일시", "구분", "내용", "외상", "결제", "잔액"]);
+    push_row(&[
+        "",
+        "이월 잔액",
+        "",
+        "",
+        "",
+        &amount(statement.opening_balance),
+    ]);
+    for line in &statement.lines {
+        push_row(&[
+            &csv_timestamp(tz, &line.ts),
+            line.kind.label(),
+            line.description.as_deref().unwrap_or(""),
+            &amount(line.charge),
+            &amount(line.payment),
+            &amount(line.balance),
+        ]);
+    }
+    push_row(&[
+        "",
+        "기말 잔액",
+        "",
+        "",
+        "",
+        &amount(statement.closing_balance),
+    ]);
+
+    fs::write(&target, content).map_err(|e| AppError::Io(e).to_string())?;
+
+    Ok(target
+        .to_str()
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| String::from("saved")))
+}
+
+#[tauri::command]
+fn export_movements_csv(
+    state: State<DbState>,
+    from: Option<String>,
+    to: Option<String>,
+    kind: Option<TransactionKind>,
+    dest_path: String,
+) -> CommandResult<String> {
+    let target = csv_target(dest_path);
+    let conn = state.open().map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let (from, to) = resolve_date_range(tz, from.as_deref(), to.as_deref()).map_err(map_app_err)?;
+    let sql = format!(
+        "{MOVEMENT_SELECT}
+         WHERE (?1 IS NULL OR t.kind = ?1)
+           AND (?2 IS NULL OR t.ts >= ?2)
+           AND (?3 IS NULL OR t.ts < ?3)
+         ORDER BY t.ts, t.id"
+    );
+    let mut stmt = conn.prepare(&sql).map_err(map_sql_err)?;
+    let rows = stmt
+        .query_map(
+            params![kind.map(|kind| kind.as_str()), from, to],
+            movement_from_row,
+        )
+        .map_err(map_sql_err)?;
+
+    let optional_number = |value: Option<f64>| value.map(|n| n.to_string()).unwrap_or_default();
+    let mut content = String::from(CSV_BOM);
+    push_csv_row(
+        &mut content,
+        &[
+            "일시",
+            "구분",
+            "품명",
+            "수량",
+            "단가",
+            "금액",
+            "거래처",
+            "고객",
+            "메모",
+        ],
+    );
+    for row in rows {
+        let movement = row.map_err(map_sql_err)?;
+        push_csv_row(
+            &mut content,
+            &[
+                &csv_timestamp(tz, &movement.ts),
+                movement.kind.as_str(),
+                &movement.product_name,
+                &movement.qty.to_string(),
+                &optional_number(movement.unit_price),
+                &optional_number(movement.total_amount),
+                movement.counterparty.as_deref().unwrap_or(""),
+                movement.customer_name.as_deref().unwrap_or(""),
+                movement.note.as_deref().unwrap_or(""),
+            ],
+        );
+    }
+
+    fs::write(&target, content).map_err(|e| AppError::Io(e).to_string())?;
+
+    Ok(target
+        .to_str()
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| String::from("saved")))
+}
+
+// one row per business event; on_account picks receivable over cash for the money side.
+// a credit return's refund row (return_id) is covered by the return itself, transfers only
+// move a balance between customers and stock conversions (conversion_of) move value between
+// products, so none of them is journaled. balance adjustments are corrections rather than
+// money taken, and a reversed purchase cancels out with its reversal, so both sides are left out
+const JOURNAL_EVENTS_SQL: &str = "
+    SELECT s.ts, CASE WHEN s.is_return = 1 THEN 'RETURN' ELSE 'SALE' END, s.is_credit,
+           s.total_amount, p.name, s.id
+    FROM sales s
+    JOIN products p ON p.id = s.product_id
+    WHERE (?1 IS NULL OR s.ts >= ?1) AND (?2 IS NULL OR s.ts < ?2)
+    UNION ALL
+    SELECT cr.ts, CASE WHEN cr.is_discount = 1 THEN 'DISCOUNT' ELSE 'PAYMENT' END, 1,
+           cr.amount, c.name, cr.id
+    FROM credits cr
+    JOIN customers c ON c.id = cr.customer_id
+    WHERE cr.is_payment = 1 AND cr.is_adjustment = 0
+      AND cr.return_id IS NULL AND cr.transfer_id IS NULL
+      AND (?1 IS NULL OR cr.ts >= ?1) AND (?2 IS NULL OR cr.ts < ?2)
+    UNION ALL
+    SELECT t.ts, 'PURCHASE', 0, t.total_amount, p.name, t.id
+    FROM transactions t
+    JOIN products p ON p.id = t.product_id
+    WHERE t.kind = 'IN' AND IFNULL(t.total_amount, 0) > 0 AND t.conversion_of IS NULL
+      AND t.reverses_id IS NULL
+      AND NOT EXISTS (SELECT 1 FROM transactions r WHERE r.reverses_id = t.id)
+      AND (?1 IS NULL OR t.ts >= ?1) AND (?2 IS NULL OR t.ts < ?2)
+    ORDER BY 1, 2, 6";
+
+// two lines per entry (debit then credit) sharing an entry number, accounts from settings
+#[tauri::command]
+fn export_journal(
+    state: State<DbState>,
+    from: Option<String>,
+    to: Option<String>,
+    dest_path: String,
+) -> CommandResult<String> {
+    let target = csv_target(dest_path);
+    let conn = state.open().map_err(map_app_err)?;
+    let tz = BusinessTz::read(&conn).map_err(map_app_err)?;
+    let (from, to) = resolve_date_range(tz, from.as_deref(), to.as_deref()).map_err(map_app_err)?;
+    let accounts = JournalAccounts::read(&conn).map_err(map_app_err)?;
+    let decimals = amount_decimals(&conn).map_err(map_app_err)?;
+
+    let mut stmt = conn.prepare(JOURNAL_EVENTS_SQL).map_err(map_sql_err)?;
+    let rows = stmt
+        .query_map(params![from, to], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)? != 0,
+                row.get::<_, f64>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, i64>(5)?,
+            ))
+        })
+        .map_err(map_sql_err)?;
+
+    let mut content = String::from(CSV_BOM);
+    push_csv_row(
+        &mut content,
+        &["일시", "전표", "계정", "차변", "대변", "적요"],
+    );
+    let mut entry_no = 0;
+    for row in rows {
+        let (ts, event, on_account, amount, name, source_id) = row.map_err(map_sql_err)?;
+        let money = if on_account {
+            &accounts.receivable
+        } else {
+            &accounts.cash
+        };
+        let (debit, credit, description) = match event.as_str() {
+            "SALE" => (
+                money,
+                &accounts.revenue,
+                format!("판매 #{source_id} {name}"),
+            ),
+            "RETURN" => (
+                &accounts.revenue,
+                money,
+                format!("반품 #{source_id} {name}"),
+            ),
+            "PAYMENT" => (
+                &accounts.cash,
+                &accounts.receivable,
+                format!("외상 결제 {name}"),
+            ),
+            "DISCOUNT" => (
+                &accounts.revenue,
+                &accounts.receivable,
+                format!("외상 할인 {name}"),
+            ),
+            _ => (&accounts.inventory, &accounts.cash, format!("입고 {name}")),
+        };
+        entry_no += 1;
+        let ts = csv_timestamp(tz, &ts);
+        let entry = entry_no.to_string();
+        let amount = format!("{:.*}", decimals, round_amount(amount, decimals));
+        push_csv_row(
+            &mut content,
+            &[&ts, &entry, debit, &amount, "", &description],
+        );
+        push_csv_row(
+            &mut content,
+            &[&ts, &entry, credit, "", &amount, &description],
+        );
+    }
+
+    fs::write(&target, content).map_err(|e| AppError::Io(e).to_string())?;
+
+    Ok(target
+        .to_str()
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| String::from("saved")))
+}
+
+// the BOM lets Excel open Korean text without mangling it
+const CSV_BOM: &str = "\u{feff}";
+
+fn csv_target(dest_path: String) -> PathBuf {
+    let mut target = PathBuf::from(dest_path);
+    let has_extension = target
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+    if !has_extension {
+        target.set_extension("csv");
+    }
+    target
+}
+
+fn push_csv_row(content: &mut String, fields: &[&str]) {
+    let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+    content.push_str(&row.join(","));
+    content.push_str("\r\n");
+}
+
+fn csv_timestamp(tz: BusinessTz, ts: &str) -> String {
+    tz.local_datetime(ts)
+        .map(|local| local.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| ts.to_string())
+}
+
+// RFC 4180 records paired with the 1-based line each starts on; quoted fields may span lines
+fn parse_csv(content: &str) -> Vec<(usize, Vec<String>)> {
+    let content = content.strip_prefix(CSV_BOM).unwrap_or(content);
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = content.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if in_quotes {
+            match ch {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => {
+                    if ch == '\n' {
+                        line += 1;
+                    }
+                    field.push(ch);
+                }
+            }
+            continue;
+        }
+        match ch {
+            '"' => in_quotes = true,
+            ',' => fields.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                fields.push(std::mem::take(&mut field));
+                records.push((record_line, std::mem::take(&mut fields)));
+                line += 1;
+                record_line = line;
+            }
+            _ => field.push(ch),
+        }
+    }
+    if !field.is_empty() || !fields.is_empty() {
+        fields.push(field);
+        records.push((record_line, fields));
+    }
+    records
+}
+
+// quote fields that contain separators, quotes or line breaks, doubling embedded quotes
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[tauri::command]
+fn export_xlsx(state: State<DbState>, dest_path: String) -> CommandResult<String> {
+    let mut target = PathBuf::from(dest_path);
+    let has_extension = target
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("xlsx"));
+    if !has_extension {
+        target.set_extension("xlsx");
+    }
+
+    let data = load_app_data(&state).map_err(map_app_err)?;
+    write_ledger_workbook(&data, &target).map_err(map_app_err)?;
+
+    Ok(target
+        .to_str()
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| String::from("saved")))
+}
+
+fn write_ledger_workbook(data: &AppData, target: &std::path::Path) -> Result<(), AppError> {
+    let settings = &data.settings;
+    let amount_pattern = if settings.currency_decimals == 0 {
+        "#,##0".to_string()
+    } else {
+        format!("#,##0.{}", "0".repeat(settings.currency_decimals))
+    };
+    let formats = XlsxFormats {
+        header: Format::new().set_bold(),
+        amount: Format::new()
+            .set_num_format(format!("\"{}\"{amount_pattern}", settings.currency_symbol)),
+        qty: Format::new().set_num_format("#,##0.##"),
+        datetime: Format::new().set_num_format("yyyy-mm-dd hh:mm"),
+        tz: BusinessTz::from_setting(settings.timezone.as_deref()),
+    };
+    let mut workbook = Workbook::new();
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("상품")?;
+    write_xlsx_header(
+        sheet,
+        &[
+            "ID",
+            "품명",
+            "SKU",
+            "단가",
+            "원가",
+            "재고",
+            "저재고 기준",
+            "메모",
+            "등록일",
+        ],
+        &formats,
+    )?;
+    for (idx, product) in data.products.iter().enumerate() {
+        let row = idx as u32 + 1;
+        sheet.write_number(row, 0, product.id as f64)?;
+        sheet.write_string(row, 1, &product.name)?;
+        write_xlsx_text(sheet, row, 2, product.sku.as_deref())?;
+        sheet.write_number_with_format(row, 3, product.unit_price, &formats.amount)?;
+        if let Some(cost) = product.cost_price {
+            sheet.write_number_with_format(row, 4, cost, &formats.amount)?;
+        }
+        sheet.write_number_with_format(row, 5, product.qty, &formats.qty)?;
+        sheet.write_number_with_format(row, 6, product.low_stock_threshold, &formats.qty)?;
+        write_xlsx_text(sheet, row, 7, product.note.as_deref())?;
+        write_xlsx_datetime(sheet, row, 8, &product.created_at, &formats)?;
+    }
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("고객")?;
+    write_xlsx_header(sheet, &["ID", "이름", "연락처", "메모", "등록일"], &formats)?;
+    for (idx, customer) in data.customers.iter().enumerate() {
+        let row = idx as u32 + 1;
+        sheet.write_number(row, 0, customer.id as f64)?;
+        sheet.write_string(row, 1, &customer.name)?;
+        write_xlsx_text(sheet, row, 2, customer.phone.as_deref())?;
+        write_xlsx_text(sheet, row, 3, customer.note.as_deref())?;
+        write_xlsx_datetime(sheet, row, 4, &customer.created_at, &formats)?;
+    }
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("판매")?;
+    write_xlsx_header(
+        sheet,
+        &[
+            "ID",
+            "일시",
+            "품명",
+            "수량",
+            "단가",
+            "금액",
+            "고객",
+            "연락처",
+            "외상",
+            "반품",
+            "원 판매 ID",
+            "메모",
+        ],
+        &formats,
+    )?;
+    for (idx, sale) in data.sales.iter().enumerate() {
+        let row = idx as u32 + 1;
+        sheet.write_number(row, 0, sale.id as f64)?;
+        write_xlsx_datetime(sheet, row, 1, &sale.ts, &formats)?;
+        sheet.write_string(row, 2, &sale.product_name)?;
+        sheet.write_number_with_format(row, 3, sale.qty, &formats.qty)?;
+        sheet.write_number_with_format(row, 4, sale.unit_price, &formats.amount)?;
+        sheet.write_number_with_format(row, 5, sale.total_amount, &formats.amount)?;
+        write_xlsx_text(sheet, row, 6, sale.customer_name.as_deref())?;
+        write_xlsx_text(sheet, row, 7, sale.customer_phone.as_deref())?;
+        sheet.write_boolean(row, 8, sale.is_credit)?;
+        sheet.write_boolean(row, 9, sale.is_return)?;
+        if let Some(origin) = sale.origin_sale_id {
+            sheet.write_number(row, 10, origin as f64)?;
+        }
+        write_xlsx_text(sheet, row, 11, sale.note.as_deref())?;
+    }
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("입출고")?;
+    write_xlsx_header(
+        sheet,
+        &[
+            "ID",
+            "일시",
+            "구분",
+            "품명",
+            "수량",
+            "단가",
+            "금액",
+            "거래처",
+            "고객",
+            "메모",
+            "판매 ID",
+        ],
+        &formats,
+    )?;
+    for (idx, movement) in data.stock_movements.iter().enumerate() {
+        let row = idx as u32 + 1;
+        sheet.write_number(row, 0, movement.id as f64)?;
+        write_xlsx_datetime(sheet, row, 1, &movement.ts, &formats)?;
+        sheet.write_string(row, 2, movement.kind.as_str())?;
+        sheet.write_string(row, 3, &movement.product_name)?;
+        sheet.write_number_with_format(row, 4, movement.qty, &formats.qty)?;
+        if let Some(price) = movement.unit_price {
+            sheet.write_number_with_format(row, 5, price, &formats.amount)?;
+        }
+        if let Some(total) = movement.total_amount {
+            sheet.write_number_with_format(row, 6, total, &formats.amount)?;
+        }
+        write_xlsx_text(sheet, row, 7, movement.counterparty.as_deref())?;
+        write_xlsx_text(sheet, row, 8, movement.customer_name.as_deref())?;
+        write_xlsx_text(sheet, row, 9, movement.note.as_deref())?;
+        if let Some(sale_id) = movement.sale_id {
+            sheet.write_number(row, 10, sale_id as f64)?;
+        }
+    }
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("외상")?;
+    write_xlsx_header(
+        sheet,
+        &[
+            "ID",
+            "일시",
+            "고객",
+            "연락처",
+            "판매 ID",
+            "금액",
+            "구분",
+            "메모",
+        ],
+        &formats,
+    )?;
+    for (idx, credit) in data.credits.iter().enumerate() {
+        let row = idx as u32 + 1;
+        sheet.write_number(row, 0, credit.id as f64)?;
+        write_xlsx_datetime(sheet, row, 1, &credit.ts, &formats)?;
+        sheet.write_string(row, 2, &credit.customer_name)?;
+        write_xlsx_text(sheet, row, 3, credit.customer_phone.as_deref())?;
+        if let Some(sale_id) = credit.sale_id {
+            sheet.write_number(row, 4, sale_id as f64)?;
+        }
+        sheet.write_number_with_format(row, 5, credit.amount, &formats.amount)?;
+        sheet.write_string(
+            row,
+            6,
+            if credit.is_payment {
+                "결제"
+            } else {
+                "외상"
+            },
+        )?;
+        write_xlsx_text(sheet, row, 7, credit.note.as_deref())?;
+    }
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("외상 잔액")?;
+    write_xlsx_header(
+        sheet,
+        &[
+            "고객",
+            "연락처",
+            "외상 합계",
+            "결제 합계",
+            "미수금",
+            "최근 거래",
+        ],
+        &formats,
+    )?;
+    for (idx, balance) in data.customer_balances.iter().enumerate() {
+        let row = idx as u32 + 1;
+        sheet.write_string(row, 0, &balance.customer_name)?;
+        write_xlsx_text(sheet, row, 1, balance.customer_phone.as_deref())?;
+        sheet.write_number_with_format(row, 2, balance.total_credit, &formats.amount)?;
+        sheet.write_number_with_format(row, 3, balance.total_paid, &formats.amount)?;
+        sheet.write_number_with_format(row, 4, balance.outstanding, &formats.amount)?;
+        if let Some(ts) = balance.last_activity.as_deref() {
+            write_xlsx_datetime(sheet, row, 5, ts, &formats)?;
+        }
+    }
+
+    workbook.save(target)?;
+    Ok(())
+}
+
+struct XlsxFormats {
+    header: Format,
+    amount: Format,
+    qty: Format,
+    datetime: Format,
+    tz: BusinessTz,
+}
+
+fn write_xlsx_header(
+    sheet: &mut Worksheet,
+    headers: &[&str],
+    formats: &XlsxFormats,
+) -> Result<(), XlsxError> {
+    for (col, header) in headers.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *header, &formats.header)?;
+    }
+    sheet.set_freeze_panes(1, 0)?;
+    Ok(())
+}
+
+fn write_xlsx_text(
+    sheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    value: Option<&str>,
+) -> Result<(), XlsxError> {
+    if let Some(value) = value {
+        sheet.write_string(row, col, value)?;
+    }
+    Ok(())
+}
+
+// stored timestamps are UTC RFC3339; the sheet shows the shop's wall-clock time
+fn write_xlsx_datetime(
+    sheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    ts: &str,
+    formats: &XlsxFormats,
+) -> Result<(), XlsxError> {
+    match formats.tz.local_datetime(ts) {
+        Some(local) => {
+            let value = ExcelDateTime::from_ymd(
+                local.year() as u16,
+                local.month() as u8,
+                local.day() as u8,
+            )?
+            .and_hms(local.hour() as u16, local.minute() as u8, local.second())?;
+            sheet.write_datetime_with_format(row, col, &value, &formats.datetime)?;
+        }
+        None => {
+            sheet.write_string(row, col, ts)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_conn() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute("PRAGMA foreign_keys = ON;", []).unwrap();
+        DbState::run_migrations(&mut conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn busy_timeout_waits_for_a_concurrent_writer() {
+        let path = std::env::temp_dir().join(format!("ledger-busy-{}.db", std::process::id()));
+        let state = DbState {
+            path: path.clone(),
+            auto_backup_error: None,
+        };
+        let setup = state.open().unwrap();
+        setup
+            .execute_batch("PRAGMA journal_mode = WAL; CREATE TABLE IF NOT EXISTS t (x INTEGER);")
+            .unwrap();
+
+        // holds the write lock for a moment, like a sale committing during an export
+        let writer = state.open().unwrap();
+        writer
+            .execute_batch("BEGIN IMMEDIATE; INSERT INTO t VALUES (1);")
+            .unwrap();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            writer.execute_batch("COMMIT;").unwrap();
+        });
+
+        let other = state.open().unwrap();
+        other.execute("INSERT INTO t VALUES (2)", []).unwrap();
+        handle.join().unwrap();
+
+        let count: i64 = other
+            .query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2);
+        drop((setup, other));
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+    }
+
+    // one product sold twice (3 + 2) with 1 returned against the first sale
+    fn seed_sales_with_return(conn: &Connection) -> i64 {
+        conn.execute(
+            "INSERT INTO products (name, unit_price, qty) VALUES ('사과', 1000, 10)",
+            [],
+        )
+        .unwrap();
+        let product_id = conn.last_insert_rowid();
+        conn.execute_batch(&format!(
+            "INSERT INTO sales (id, ts, product_id, qty, price_snapshot, total_amount)
+             VALUES (1, '2026-01-01T01:00:00+00:00', {product_id}, 3, 1000, 3000),
+                    (2, '2026-01-01T02:00:00+00:00', {product_id}, 2, 1000, 2000);
+             INSERT INTO sales (id, ts, product_id, qty, price_snapshot, total_amount, is_return, origin_sale_id)
+             VALUES (3, '2026-01-01T03:00:00+00:00', {product_id}, 1, 1000, 1000, 1, 1);"
+        ))
+        .unwrap();
+        product_id
+    }
+
+    #[test]
+    fn top_products_net_subtracts_returns() {
+        let conn = test_conn();
+        seed_sales_with_return(&conn);
+        let net = fetch_top_products(&conn, None, None, 10, true).unwrap();
+        assert_eq!(net.len(), 1);
+        assert_eq!(net[0].qty_sold, 4.0);
+        assert_eq!(net[0].revenue, 4000.0);
+    }
+
+    #[test]
+    fn top_products_gross_counts_only_sales() {
+        let conn = test_conn();
+        seed_sales_with_return(&conn);
+        let gross = fetch_top_products(&conn, None, None, 10, false).unwrap();
+        assert_eq!(gross.len(), 1);
+        assert_eq!(gross[0].qty_sold, 5.0);
+        assert_eq!(gross[0].revenue, 5000.0);
+    }
+
+    #[test]
+    fn product_sales_modes_include_or_skip_returns() {
+        let conn = test_conn();
+        let product_id = seed_sales_with_return(&conn);
+        let net = fetch_product_sales(&conn, product_id, None, None, true).unwrap();
+        assert_eq!(net.len(), 3);
+        assert!(net.iter().any(|sale| sale.is_return));
+        let gross = fetch_product_sales(&conn, product_id, None, None, false).unwrap();
+        assert_eq!(gross.len(), 2);
+        assert!(gross.iter().all(|sale| !sale.is_return));
+    }
+
+    #[test]
+    fn product_update_reports_every_invalid_field() {
+        let form = ProductUpdateForm {
+            id: 1,
+            name: "  ".into(),
+            sku: None,
+            unit_price: -1.0,
+            note: None,
+            low_stock_threshold: None,
+            cost_price: Some(Some(-5.0)),
+            version: 1,
+            allow_negative_stock: None,
+            reorder_multiple: None,
+            qty_step: Some(Some(0.0)),
+            tax_rate: None,
+            reorder_point: None,
+            preferred_supplier_id: None,
+        };
+        let mut errors = Vec::new();
+        check_product_fields(&mut errors, (&form).into());
+        let fields: Vec<_> = errors.iter().map(|error| error.field).collect();
+        assert_eq!(fields, ["name", "unit_price", "cost_price", "qty_step"]);
+    }
+
+    #[test]
+    fn product_update_tells_a_missing_key_from_null() {
+        let form: ProductUpdateForm = serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "name": "사과",
+            "unit_price": 1000.0,
+            "version": 1,
+            "cost_price": null,
+        }))
+        .unwrap();
+        assert_eq!(form.cost_price, Some(None));
+        assert_eq!(form.reorder_point, None);
+    }
+
+    #[test]
+    fn setting_values_are_checked_against_their_readers() {
+        assert!(validate_setting("backup_retention", "0").is_err());
+        assert!(validate_setting("backup_retention", "30").is_ok());
+        assert!(validate_setting("auto_backup", "yes").is_err());
+        assert!(validate_setting("currency_code", "KRWW").is_err());
+        assert!(validate_setting("default_low_stock_threshold", "-1").is_err());
+        assert!(validate_setting("shop_name", "아무 이름").is_ok());
+    }
+
+    #[test]
+    fn balance_triggers_agree_with_a_rebuild() {
+        let conn = test_conn();
+        conn.execute_batch(
+            "INSERT INTO customers (id, name) VALUES (1, '김철수');
+             INSERT INTO products (id, name, unit_price) VALUES (1, '사과', 1000);
+             INSERT INTO sales (id, ts, product_id, qty, price_snapshot, total_amount, customer_id, is_credit)
+             VALUES (1, '2026-01-01T00:00:00+00:00', 1, 3, 1000, 3000, 1, 1);
+             INSERT INTO credits (ts, customer_id, sale_id, amount, is_payment)
+             VALUES ('2026-01-01T00:00:00+00:00', 1, 1, 3000, 0),
+                    ('2026-01-02T00:00:00+00:00', 1, 1, 1000, 1),
+                    ('2026-01-03T00:00:00+00:00', 1, NULL, 500, 0);
+             UPDATE credits SET amount = 2500 WHERE sale_id = 1 AND is_payment = 0;",
+        )
+        .unwrap();
+        let cached = |conn: &Connection| {
+            conn.query_row(
+                "SELECT cached_credit, cached_paid FROM customers WHERE id = 1",
+                [],
+                |row| Ok((row.get::<_, f64>(0)?, row.get::<_, f64>(1)?)),
+            )
+            .unwrap()
+        };
+        // the loose 500 row is neither a charge nor a payment
+        assert_eq!(cached(&conn), (2500.0, 1000.0));
+        conn.execute(&rebuild_balance_cache_sql(), []).unwrap();
+        assert_eq!(cached(&conn), (2500.0, 1000.0));
+    }
+
+    #[test]
+    fn duplicate_client_token_is_recognised() {
+        let conn = test_conn();
+        let insert = "INSERT INTO products (id, name) VALUES (1, '사과')
+            ON CONFLICT DO NOTHING;
+            INSERT INTO sales (ts, product_id, qty, price_snapshot, total_amount, client_token)
+            VALUES ('2026-01-01T00:00:00+00:00', 1, 1, 1000, 1000, 'tok-1')";
+        conn.execute_batch(insert).unwrap();
+        let err = conn.execute_batch(insert).unwrap_err();
+        assert!(is_client_token_conflict(&err));
+    }
+
+    #[test]
+    fn clean_text_drops_blank_input() {
+        assert_eq!(clean_text(None), None);
+        assert_eq!(clean_text(Some("")), None);
+        assert_eq!(clean_text(Some("   ")), None);
+        assert_eq!(clean_text(Some("\t\n ")), None);
+        assert_eq!(clean_text(Some("  메모 ")), Some("메모"));
+    }
+
+    #[test]
+    fn whitespace_sku_is_stored_as_null() {
+        let conn = test_conn();
+        assert_eq!(
+            ensure_sku_available(&conn, Some("   "), None).unwrap(),
+            None
+        );
+        assert_eq!(
+            ensure_sku_available(&conn, Some(" A-1 "), None).unwrap(),
+            Some("A-1".to_string())
+        );
+    }
+
+    #[test]
+    fn whitespace_lot_fields_count_as_absent() {
+        let payload = StockEntryPayload {
+            product_id: 1,
+            qty: 1.0,
+            kind: None,
+            unit_price: None,
+            counterparty: Some("  ".into()),
+            customer_id: None,
+            note: Some(" ".into()),
+            lot_code: Some("   ".into()),
+            expiry_date: Some(" ".into()),
+        };
+        assert_eq!(stock_entry_lot(&payload).unwrap(), (None, None));
+        assert_eq!(clean_text(payload.counterparty.as_deref()), None);
+        assert_eq!(clean_text(payload.note.as_deref()), None);
+    }
+}
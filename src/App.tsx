@@ -40,6 +40,7 @@ type TabKey =
 type ProductFormState = {
   mode: "create" | "edit";
   id?: number;
+  version?: number;
   name: string;
   unit_price: string;
   low_stock_threshold: string;
@@ -51,6 +52,7 @@ type ProductFormState = {
 type CustomerFormState = {
   mode: "create" | "edit";
   id?: number;
+  version?: number;
   name: string;
   phone: string;
   note: string;
@@ -1115,7 +1117,11 @@ function App() {
       if (result) {
         setProductForm(createEmptyProductForm());
       }
-    } else if (productForm.mode === "edit" && productForm.id != null) {
+    } else if (
+      productForm.mode === "edit" &&
+      productForm.id != null &&
+      productForm.version != null
+    ) {
       const payload = {
         id: productForm.id,
         name: productForm.name.trim(),
@@ -1124,6 +1130,7 @@ function App() {
         low_stock_threshold: productForm.low_stock_threshold
           ? parseNumber(productForm.low_stock_threshold)
           : null,
+        version: productForm.version,
       };
 
       const prevProduct = data?.products.find((p) => p.id === productForm.id) ?? null;
@@ -1158,6 +1165,7 @@ function App() {
     setProductForm({
       mode: "edit",
       id: product.id,
+      version: product.version,
       name: product.name,
       unit_price: product.unit_price.toString(),
       low_stock_threshold: product.low_stock_threshold.toString(),
@@ -1207,12 +1215,17 @@ const handleCustomerSubmit = async (event: FormEvent<HTMLFormElement>) => {
     if (result) {
       setCustomerForm(createEmptyCustomerForm());
     }
-  } else if (customerForm.mode === "edit" && customerForm.id != null) {
+  } else if (
+    customerForm.mode === "edit" &&
+    customerForm.id != null &&
+    customerForm.version != null
+  ) {
     const payload = {
       id: customerForm.id,
       name: customerForm.name.trim(),
       phone,
       note: sanitizeNullable(customerForm.note),
+      version: customerForm.version,
     };
     const result = await runAction(() => updateCustomer(payload));
     if (result) {
@@ -1225,6 +1238,7 @@ const handleCustomerSubmit = async (event: FormEvent<HTMLFormElement>) => {
     setCustomerForm({
       mode: "edit",
       id: customer.id,
+      version: customer.version,
       name: customer.name,
       phone: customer.phone ?? "",
       note: customer.note ?? "",
@@ -3,6 +3,7 @@ import type {
   AppData,
   CreditEntry,
   CreditPaymentPayload,
+  CreditPaymentResult,
   Customer,
   CustomerBalance,
   CustomerFormPayload,
@@ -161,6 +162,7 @@ async function local_create_product(payload: ProductFormPayload): Promise<AppDat
     note: payload.note ?? null,
     low_stock_threshold: payload.low_stock_threshold ?? 0,
     created_at: nowIso(),
+    version: 1,
   };
   const initialQty = payload.initial_qty ?? null;
   if (initialQty && initialQty > 0) {
@@ -196,6 +198,7 @@ async function local_update_product(payload: ProductUpdatePayload): Promise<AppD
   target.unit_price = payload.unit_price;
   target.note = payload.note ?? null;
   target.low_stock_threshold = payload.low_stock_threshold ?? 0;
+  target.version += 1;
   saveState(state);
   return materialize(state);
 }
@@ -218,6 +221,7 @@ async function local_create_customer(payload: CustomerFormPayload): Promise<AppD
     phone: payload.phone,
     note: payload.note ?? null,
     created_at: nowIso(),
+    version: 1,
   };
   state.customers.unshift(customer);
   saveState(state);
@@ -231,6 +235,7 @@ async function local_update_customer(payload: CustomerUpdatePayload): Promise<Ap
   target.name = payload.name;
   target.phone = payload.phone;
   target.note = payload.note ?? null;
+  target.version += 1;
   // Also update denormalized names for existing records
   state.sales.forEach((s) => {
     if (s.customer_id === payload.id) {
@@ -474,10 +479,17 @@ async function local_record_return(payload: ReturnPayload): Promise<AppData> {
   return materialize(state);
 }
 
-async function local_record_credit_payment(payload: CreditPaymentPayload): Promise<AppData> {
+async function local_record_credit_payment(
+  payload: CreditPaymentPayload,
+): Promise<CreditPaymentResult> {
   const state = loadState();
   const customer = state.customers.find((c) => c.id === payload.customer_id);
-  if (!customer) return materialize(state);
+  const result = (data: AppData): CreditPaymentResult => ({
+    requested_amount: payload.amount,
+    rounded_amount: payload.amount,
+    data,
+  });
+  if (!customer) return result(materialize(state));
   const creditId = bumpId("credit");
   state.credits.unshift({
     id: creditId,
@@ -491,7 +503,7 @@ async function local_record_credit_payment(payload: CreditPaymentPayload): Promi
     note: payload.note ?? null,
   });
   saveState(state);
-  return materialize(state);
+  return result(materialize(state));
 }
 
 // Web mock: 외상 추가(미수 증가)
@@ -773,7 +785,8 @@ export async function recordReturn(payload: ReturnPayload): Promise<AppData> {
 export async function recordCreditPayment(
   payload: CreditPaymentPayload,
 ): Promise<AppData> {
-  return call<AppData>("record_credit_payment", { payload });
+  const result = await call<CreditPaymentResult>("record_credit_payment", { payload });
+  return result.data;
 }
 
 // 외상 추가(미수 증가)
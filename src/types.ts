@@ -7,6 +7,7 @@ export interface Product {
   note: string | null;
   low_stock_threshold: number;
   created_at: string;
+  version: number;
 }
 
 export interface Customer {
@@ -15,6 +16,7 @@ export interface Customer {
   phone: string | null;
   note: string | null;
   created_at: string;
+  version: number;
 }
 
 export type TransactionKind = "IN" | "OUT" | "RETURN";
@@ -99,6 +101,8 @@ export interface ProductUpdatePayload {
   unit_price: number;
   note?: string | null;
   low_stock_threshold?: number | null;
+  // the version the edit started from; a stale one is rejected
+  version: number;
 }
 
 export interface CustomerFormPayload {
@@ -112,6 +116,8 @@ export interface CustomerUpdatePayload {
   name: string;
   phone: string;
   note?: string | null;
+  // the version the edit started from; a stale one is rejected
+  version: number;
 }
 
 export interface StockEntryPayload {
@@ -148,6 +154,12 @@ export interface CreditPaymentPayload {
   note?: string | null;
 }
 
+export interface CreditPaymentResult {
+  requested_amount: number;
+  rounded_amount: number;
+  data: AppData;
+}
+
 export interface CreditAdditionPayload {
   customer_id: number;
   amount: number;